@@ -0,0 +1,82 @@
+//! Throughput of the volatility calculation with hundreds of symbols: the
+//! former single `Mutex<Vec<(String, VolatilityCalculator)>>` with a linear
+//! find per message, against calculators sharded over worker tasks.
+//!
+//! Run with `cargo bench --bench sharded_calculators`.
+
+use binance_volatility::math::VolatilityCalculator;
+use binance_volatility::workers::ShardedCalculators;
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+const WINDOW_SECS: u64 = 30;
+const SAMPLES_PER_SYMBOL: usize = 50;
+
+// Messages interleave symbols the way a multiplexed stream does
+fn messages(symbols: &[String]) -> Vec<(String, SystemTime, f64)> {
+    let start = SystemTime::now();
+    (0..SAMPLES_PER_SYMBOL)
+        .flat_map(|step| {
+            symbols.iter().enumerate().map(move |(i, symbol)| {
+                let timestamp = start + Duration::from_millis(step as u64 * 100);
+                (symbol.clone(), timestamp, 100.0 + i as f64 + (step % 7) as f64 * 0.01)
+            })
+        })
+        .collect()
+}
+
+fn global_mutex(symbols: &[String], messages: &[(String, SystemTime, f64)]) {
+    let calculators: Arc<Mutex<Vec<(String, VolatilityCalculator)>>> = Arc::new(Mutex::new(
+        symbols
+            .iter()
+            .map(|symbol| (symbol.clone(), VolatilityCalculator::new(WINDOW_SECS)))
+            .collect(),
+    ));
+    for (symbol, timestamp, price) in messages {
+        let symbol = symbol.clone();
+        let mut calculators = calculators.lock().unwrap();
+        if let Some((_, calculator)) = calculators.iter_mut().find(|(s, _)| s == &symbol) {
+            calculator.add_value_at(*timestamp, *price);
+            criterion::black_box(calculator.calculate_volatility());
+        }
+    }
+}
+
+async fn sharded(shards: usize, messages: &[(String, SystemTime, f64)]) {
+    let sink = Arc::new(|_: &str, _: &str, volatility: Option<f64>| {
+        criterion::black_box(volatility);
+    });
+    let mut calculators = ShardedCalculators::spawn(shards, WINDOW_SECS, 1_024, sink);
+    for (symbol, timestamp, price) in messages {
+        let id = calculators.intern(symbol, "1m").await;
+        calculators.add(id, *timestamp, *price).await;
+    }
+    calculators.shutdown().await;
+}
+
+fn bench_calculators(c: &mut Criterion) {
+    let runtime = tokio::runtime::Builder::new_multi_thread().enable_all().build().unwrap();
+    let mut group = c.benchmark_group("volatility_calculation");
+    group.sample_size(20);
+
+    for symbol_count in [100, 500] {
+        let symbols: Vec<String> = (0..symbol_count).map(|i| format!("sym{}usdt", i)).collect();
+        let messages = messages(&symbols);
+        group.throughput(Throughput::Elements(messages.len() as u64));
+
+        group.bench_with_input(BenchmarkId::new("global_mutex", symbol_count), &messages, |b, messages| {
+            b.iter(|| global_mutex(&symbols, messages))
+        });
+        for shards in [4, 8] {
+            let id = BenchmarkId::new(format!("sharded_{}", shards), symbol_count);
+            group.bench_with_input(id, &messages, |b, messages| {
+                b.iter(|| runtime.block_on(sharded(shards, messages)))
+            });
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_calculators);
+criterion_main!(benches);
@@ -0,0 +1,266 @@
+use serde::Deserialize;
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt;
+
+use crate::client::models::de_levels;
+
+/// Number of levels summed per side when reporting book depth.
+pub const DEFAULT_DEPTH_LEVELS: usize = 10;
+
+// Diff events kept while waiting for a snapshot
+const MAX_BUFFERED_UPDATES: usize = 1000;
+
+// Prices are keyed in fixed point; Binance quotes at most 8 decimals
+const PRICE_SCALE: f64 = 1e8;
+
+fn price_key(price: f64) -> u64 {
+    (price * PRICE_SCALE).round() as u64
+}
+
+fn key_price(key: u64) -> f64 {
+    key as f64 / PRICE_SCALE
+}
+
+/// One `depthUpdate` event from a `@depth` diff stream.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct DepthUpdate {
+    #[serde(rename = "E")]
+    pub event_time: u64,
+    #[serde(rename = "U")]
+    pub first_update_id: u64,
+    #[serde(rename = "u")]
+    pub final_update_id: u64,
+    #[serde(rename = "b", deserialize_with = "de_levels")]
+    pub bids: Vec<(f64, f64)>,
+    #[serde(rename = "a", deserialize_with = "de_levels")]
+    pub asks: Vec<(f64, f64)>,
+}
+
+/// Full book snapshot from the REST `/api/v3/depth` endpoint.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct DepthSnapshot {
+    #[serde(rename = "lastUpdateId")]
+    pub last_update_id: u64,
+    #[serde(deserialize_with = "de_levels")]
+    pub bids: Vec<(f64, f64)>,
+    #[serde(deserialize_with = "de_levels")]
+    pub asks: Vec<(f64, f64)>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum BookError {
+    /// The snapshot is older than the first buffered diff; fetch a new one.
+    StaleSnapshot { last_update_id: u64, first_buffered: u64 },
+    /// A diff does not continue from the last applied update id.
+    Gap { expected: u64, got: u64 },
+}
+
+impl fmt::Display for BookError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BookError::StaleSnapshot { last_update_id, first_buffered } => write!(
+                f,
+                "snapshot lastUpdateId {} is older than first buffered update {}",
+                last_update_id, first_buffered
+            ),
+            BookError::Gap { expected, got } => {
+                write!(f, "expected update id {} but received {}", expected, got)
+            }
+        }
+    }
+}
+
+impl std::error::Error for BookError {}
+
+/// Top-of-book figures derived from the local book.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BookSummary {
+    pub symbol: String,
+    pub event_time: u64,
+    pub best_bid: f64,
+    pub best_ask: f64,
+    pub mid_price: f64,
+    pub microprice: f64,
+    pub spread: f64,
+    pub bid_depth: f64, // Summed quantity over the top N bid levels
+    pub ask_depth: f64, // Summed quantity over the top N ask levels
+}
+
+/// Which book-derived price feeds the volatility calculation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BookPrice {
+    Mid,
+    Microprice,
+}
+
+impl BookSummary {
+    pub fn price(&self, kind: BookPrice) -> f64 {
+        match kind {
+            BookPrice::Mid => self.mid_price,
+            BookPrice::Microprice => self.microprice,
+        }
+    }
+}
+
+/// Local L2 order book.
+#[derive(Debug, Default)]
+pub struct OrderBook {
+    bids: BTreeMap<u64, f64>,
+    asks: BTreeMap<u64, f64>,
+    last_update_id: u64,
+}
+
+impl OrderBook {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn last_update_id(&self) -> u64 {
+        self.last_update_id
+    }
+
+    /// Replaces the book contents with a snapshot.
+    pub fn load_snapshot(&mut self, snapshot: &DepthSnapshot) {
+        self.bids.clear();
+        self.asks.clear();
+        update_levels(&mut self.bids, &snapshot.bids);
+        update_levels(&mut self.asks, &snapshot.asks);
+        self.last_update_id = snapshot.last_update_id;
+    }
+
+    /// Applies a diff without sequence checks.
+    fn apply(&mut self, update: &DepthUpdate) {
+        update_levels(&mut self.bids, &update.bids);
+        update_levels(&mut self.asks, &update.asks);
+        self.last_update_id = update.final_update_id;
+    }
+
+    pub fn best_bid(&self) -> Option<(f64, f64)> {
+        self.bids.iter().next_back().map(|(key, qty)| (key_price(*key), *qty))
+    }
+
+    pub fn best_ask(&self) -> Option<(f64, f64)> {
+        self.asks.iter().next().map(|(key, qty)| (key_price(*key), *qty))
+    }
+
+    /// Mid-price, microprice, spread and top-N depth; `None` if a side is empty.
+    pub fn summary(&self, symbol: &str, event_time: u64, levels: usize) -> Option<BookSummary> {
+        let (bid, bid_qty) = self.best_bid()?;
+        let (ask, ask_qty) = self.best_ask()?;
+
+        // Microprice weights each side's price by the opposite side's size
+        let microprice = if bid_qty + ask_qty > 0.0 {
+            (bid * ask_qty + ask * bid_qty) / (bid_qty + ask_qty)
+        } else {
+            (bid + ask) / 2.0
+        };
+
+        Some(BookSummary {
+            symbol: symbol.to_string(),
+            event_time,
+            best_bid: bid,
+            best_ask: ask,
+            mid_price: (bid + ask) / 2.0,
+            microprice,
+            spread: ask - bid,
+            bid_depth: self.bids.values().rev().take(levels).sum(),
+            ask_depth: self.asks.values().take(levels).sum(),
+        })
+    }
+}
+
+fn update_levels(side: &mut BTreeMap<u64, f64>, levels: &[(f64, f64)]) {
+    for (price, quantity) in levels {
+        if *quantity == 0.0 {
+            side.remove(&price_key(*price));
+        } else {
+            side.insert(price_key(*price), *quantity);
+        }
+    }
+}
+
+/// Result of feeding a diff into [`BookSync`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UpdateOutcome {
+    Applied,
+    Buffered, // Waiting for a snapshot
+    Ignored,  // Already covered by the snapshot
+}
+
+/// Keeps an [`OrderBook`] in sync with a diff stream following Binance's
+/// documented procedure: buffer diffs, load a REST snapshot, drop diffs the
+/// snapshot already covers, then require each diff to continue from the
+/// previous final update id.
+#[derive(Debug, Default)]
+pub struct BookSync {
+    book: OrderBook,
+    buffer: VecDeque<DepthUpdate>,
+    synced: bool,
+}
+
+impl BookSync {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn book(&self) -> &OrderBook {
+        &self.book
+    }
+
+    pub fn is_synced(&self) -> bool {
+        self.synced
+    }
+
+    /// Drops the book and buffered diffs; the next snapshot starts over.
+    pub fn reset(&mut self) {
+        self.book = OrderBook::new();
+        self.buffer.clear();
+        self.synced = false;
+    }
+
+    pub fn on_update(&mut self, update: DepthUpdate) -> Result<UpdateOutcome, BookError> {
+        if !self.synced {
+            if self.buffer.len() >= MAX_BUFFERED_UPDATES {
+                self.buffer.pop_front();
+            }
+            self.buffer.push_back(update);
+            return Ok(UpdateOutcome::Buffered);
+        }
+        self.apply_in_sequence(&update)
+    }
+
+    pub fn on_snapshot(&mut self, snapshot: &DepthSnapshot) -> Result<(), BookError> {
+        if let Some(first) = self.buffer.front() {
+            if snapshot.last_update_id < first.first_update_id {
+                return Err(BookError::StaleSnapshot {
+                    last_update_id: snapshot.last_update_id,
+                    first_buffered: first.first_update_id,
+                });
+            }
+        }
+
+        self.book.load_snapshot(snapshot);
+        self.synced = true;
+
+        let buffered: Vec<DepthUpdate> = self.buffer.drain(..).collect();
+        for update in &buffered {
+            if let Err(e) = self.apply_in_sequence(update) {
+                self.reset();
+                return Err(e);
+            }
+        }
+        Ok(())
+    }
+
+    fn apply_in_sequence(&mut self, update: &DepthUpdate) -> Result<UpdateOutcome, BookError> {
+        let expected = self.book.last_update_id() + 1;
+        if update.final_update_id < expected {
+            return Ok(UpdateOutcome::Ignored);
+        }
+        if update.first_update_id > expected {
+            return Err(BookError::Gap { expected, got: update.first_update_id });
+        }
+        self.book.apply(update);
+        Ok(UpdateOutcome::Applied)
+    }
+}
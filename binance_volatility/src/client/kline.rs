@@ -0,0 +1,228 @@
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+
+use super::models::KlinePayload;
+
+/// Kline intervals supported by Binance spot streams.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum KlineInterval {
+    OneSecond,
+    OneMinute,
+    ThreeMinutes,
+    FiveMinutes,
+    FifteenMinutes,
+    ThirtyMinutes,
+    OneHour,
+    TwoHours,
+    FourHours,
+    SixHours,
+    EightHours,
+    TwelveHours,
+    OneDay,
+    ThreeDays,
+    OneWeek,
+    OneMonth,
+}
+
+impl KlineInterval {
+    /// Interval code used by Binance, e.g. `5m`.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            KlineInterval::OneSecond => "1s",
+            KlineInterval::OneMinute => "1m",
+            KlineInterval::ThreeMinutes => "3m",
+            KlineInterval::FiveMinutes => "5m",
+            KlineInterval::FifteenMinutes => "15m",
+            KlineInterval::ThirtyMinutes => "30m",
+            KlineInterval::OneHour => "1h",
+            KlineInterval::TwoHours => "2h",
+            KlineInterval::FourHours => "4h",
+            KlineInterval::SixHours => "6h",
+            KlineInterval::EightHours => "8h",
+            KlineInterval::TwelveHours => "12h",
+            KlineInterval::OneDay => "1d",
+            KlineInterval::ThreeDays => "3d",
+            KlineInterval::OneWeek => "1w",
+            KlineInterval::OneMonth => "1M",
+        }
+    }
+
+    /// Stream name suffix, e.g. `kline_5m`.
+    pub fn stream_suffix(&self) -> &'static str {
+        match self {
+            KlineInterval::OneSecond => "kline_1s",
+            KlineInterval::OneMinute => "kline_1m",
+            KlineInterval::ThreeMinutes => "kline_3m",
+            KlineInterval::FiveMinutes => "kline_5m",
+            KlineInterval::FifteenMinutes => "kline_15m",
+            KlineInterval::ThirtyMinutes => "kline_30m",
+            KlineInterval::OneHour => "kline_1h",
+            KlineInterval::TwoHours => "kline_2h",
+            KlineInterval::FourHours => "kline_4h",
+            KlineInterval::SixHours => "kline_6h",
+            KlineInterval::EightHours => "kline_8h",
+            KlineInterval::TwelveHours => "kline_12h",
+            KlineInterval::OneDay => "kline_1d",
+            KlineInterval::ThreeDays => "kline_3d",
+            KlineInterval::OneWeek => "kline_1w",
+            KlineInterval::OneMonth => "kline_1M",
+        }
+    }
+
+    /// Nominal candle length in milliseconds (a month is taken as 30 days).
+    pub fn millis(&self) -> u64 {
+        const MINUTE: u64 = 60_000;
+        match self {
+            KlineInterval::OneSecond => 1_000,
+            KlineInterval::OneMinute => MINUTE,
+            KlineInterval::ThreeMinutes => 3 * MINUTE,
+            KlineInterval::FiveMinutes => 5 * MINUTE,
+            KlineInterval::FifteenMinutes => 15 * MINUTE,
+            KlineInterval::ThirtyMinutes => 30 * MINUTE,
+            KlineInterval::OneHour => 60 * MINUTE,
+            KlineInterval::TwoHours => 120 * MINUTE,
+            KlineInterval::FourHours => 240 * MINUTE,
+            KlineInterval::SixHours => 360 * MINUTE,
+            KlineInterval::EightHours => 480 * MINUTE,
+            KlineInterval::TwelveHours => 720 * MINUTE,
+            KlineInterval::OneDay => 1_440 * MINUTE,
+            KlineInterval::ThreeDays => 3 * 1_440 * MINUTE,
+            KlineInterval::OneWeek => 7 * 1_440 * MINUTE,
+            KlineInterval::OneMonth => 30 * 1_440 * MINUTE,
+        }
+    }
+}
+
+impl fmt::Display for KlineInterval {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for KlineInterval {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let interval = match s {
+            "1s" => KlineInterval::OneSecond,
+            "1m" => KlineInterval::OneMinute,
+            "3m" => KlineInterval::ThreeMinutes,
+            "5m" => KlineInterval::FiveMinutes,
+            "15m" => KlineInterval::FifteenMinutes,
+            "30m" => KlineInterval::ThirtyMinutes,
+            "1h" => KlineInterval::OneHour,
+            "2h" => KlineInterval::TwoHours,
+            "4h" => KlineInterval::FourHours,
+            "6h" => KlineInterval::SixHours,
+            "8h" => KlineInterval::EightHours,
+            "12h" => KlineInterval::TwelveHours,
+            "1d" => KlineInterval::OneDay,
+            "3d" => KlineInterval::ThreeDays,
+            "1w" => KlineInterval::OneWeek,
+            "1M" => KlineInterval::OneMonth,
+            other => return Err(format!("Unsupported kline interval: {}", other)),
+        };
+        Ok(interval)
+    }
+}
+
+// Largest page `/api/v3/klines` returns
+const MAX_BACKFILL_LIMIT: u32 = 1000;
+
+/// Number of candles needed to cover `window`, capped at one REST page.
+pub fn backfill_limit(window: Duration, interval: KlineInterval) -> u32 {
+    let candles = (window.as_millis() / interval.millis() as u128) + 1;
+    candles.min(MAX_BACKFILL_LIMIT as u128) as u32
+}
+
+/// Which kline updates are forwarded to the pipeline.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KlineMode {
+    /// Only the final update of each candle (`k.x == true`), once per candle.
+    ClosedOnly,
+    /// Intra-candle updates as well, deduplicated: an open-candle update is
+    /// forwarded only when its close differs from the last forwarded update
+    /// of the same candle, and each candle's final update is forwarded
+    /// exactly once.
+    IntraCandle,
+}
+
+/// A kline update forwarded to the pipeline.
+#[derive(Debug, Clone, PartialEq)]
+pub struct KlineEvent {
+    pub symbol: String,
+    pub interval: String,
+    pub event_time: u64,
+    pub open_time: u64,
+    pub close_time: u64,
+    pub open: f64,
+    pub high: f64,
+    pub low: f64,
+    pub close: f64,
+    pub volume: f64,
+    pub is_closed: bool,
+}
+
+impl KlineEvent {
+    pub fn new(symbol: &str, event_time: u64, kline: &KlinePayload) -> Self {
+        Self {
+            symbol: symbol.to_string(),
+            interval: kline.interval.clone(),
+            event_time,
+            open_time: kline.open_time,
+            close_time: kline.close_time,
+            open: kline.open,
+            high: kline.high,
+            low: kline.low,
+            close: kline.close,
+            volume: kline.volume,
+            is_closed: kline.is_closed,
+        }
+    }
+}
+
+/// Applies a [`KlineMode`] to the kline updates of a single stream.
+#[derive(Debug, Clone)]
+pub struct KlineFilter {
+    mode: KlineMode,
+    last_open_time: Option<u64>, // Candle of the last forwarded update
+    last_close: Option<f64>,     // Close of the last forwarded update
+    last_closed_open_time: Option<u64>,
+}
+
+impl KlineFilter {
+    pub fn new(mode: KlineMode) -> Self {
+        Self {
+            mode,
+            last_open_time: None,
+            last_close: None,
+            last_closed_open_time: None,
+        }
+    }
+
+    /// Returns whether the update should be forwarded, recording it if so.
+    pub fn accept(&mut self, open_time: u64, close: f64, is_closed: bool) -> bool {
+        // Candles at or before the last closed one are replays
+        if let Some(closed) = self.last_closed_open_time {
+            if open_time <= closed {
+                return false;
+            }
+        }
+
+        if is_closed {
+            self.last_closed_open_time = Some(open_time);
+        } else {
+            if self.mode == KlineMode::ClosedOnly {
+                return false;
+            }
+            if self.last_open_time == Some(open_time) && self.last_close == Some(close) {
+                return false;
+            }
+        }
+
+        self.last_open_time = Some(open_time);
+        self.last_close = Some(close);
+        true
+    }
+}
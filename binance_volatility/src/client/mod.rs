@@ -1,96 +1,472 @@
-use tokio_tungstenite::{connect_async, MaybeTlsStream, WebSocketStream};
-use tokio::net::TcpStream;
-use tokio::sync::mpsc::UnboundedSender;
-use tokio::time::{sleep, Duration};
-use futures_util::StreamExt;
-use log::{info, error};
+use tokio::time::Duration;
+use std::collections::HashMap;
+use log::info;
+use volatility_core::channel::{Conflate, EventSender};
+use volatility_core::event::{now_millis, EventDetail, MarketEvent};
+use volatility_core::source::{HealthTracker, TickChannel};
 
+use crate::book::{BookSummary, DEFAULT_DEPTH_LEVELS};
 
+pub mod kline;
+pub mod models;
+pub mod rest;
+pub mod sequence;
+mod source;
+pub mod stream;
+pub mod symbols;
+
+use kline::{KlineEvent, KlineInterval, KlineMode};
+use models::{parse_message, BinanceMessage, MessageError};
+
+use rest::{RestClient, BINANCE_REST_URL, EXCHANGE_INFO_PATH};
+use sequence::StreamGap;
+use stream::{run_subscription, StreamHandler};
+use symbols::{validate_symbols, ExchangeInfo, SymbolError, SymbolMetadata};
+
+// Binance drops connections after 24 hours; replace them well before that
+const DEFAULT_MAX_CONNECTION_AGE: Duration = Duration::from_secs(23 * 60 * 60);
+
+/// Binance market data streams that can be subscribed to for a symbol.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StreamKind {
+    Kline(KlineInterval),
+    Trade,
+    AggTrade,
+    Depth,
+    BookTicker,
+    /// USDⓈ-M futures mark price, index price and funding rate; requires a
+    /// client on the futures base URL.
+    MarkPrice,
+}
+
+impl StreamKind {
+    /// Stream name suffix appended to the symbol, e.g. `ethusdc@trade`.
+    pub fn suffix(&self) -> &'static str {
+        match self {
+            StreamKind::Kline(interval) => interval.stream_suffix(),
+            StreamKind::Trade => "trade",
+            StreamKind::AggTrade => "aggTrade",
+            StreamKind::Depth => "depth@100ms",
+            StreamKind::BookTicker => "bookTicker",
+            StreamKind::MarkPrice => "markPrice@1s",
+        }
+    }
+}
+
+/// A single stream to open: one symbol and one stream kind.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Subscription {
+    pub symbol: String,
+    pub kind: StreamKind,
+    pub kline_mode: KlineMode, // Only used by kline streams
+}
+
+impl Subscription {
+    /// Kline subscriptions default to closed candles only.
+    pub fn new(symbol: &str, kind: StreamKind) -> Self {
+        Self {
+            symbol: symbol.to_string(),
+            kind,
+            kline_mode: KlineMode::ClosedOnly,
+        }
+    }
+
+    pub fn with_kline_mode(mut self, kline_mode: KlineMode) -> Self {
+        self.kline_mode = kline_mode;
+        self
+    }
+
+    pub(crate) fn stream_name(&self) -> String {
+        format!("{}@{}", self.symbol, self.kind.suffix())
+    }
+}
+
+/// Tick-level trade from either the `@trade` or the `@aggTrade` stream.
+///
+/// For `@trade` the first and last trade ids are both the trade id; for
+/// `@aggTrade` the trade id is the aggregate id and the range covers the
+/// individual trades that were aggregated.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TradeEvent {
+    pub symbol: String,
+    pub kind: StreamKind,
+    pub price: f64,
+    pub quantity: f64,
+    pub is_buyer_maker: bool,
+    pub trade_id: u64,
+    pub first_trade_id: u64,
+    pub last_trade_id: u64,
+    pub event_time: u64, // Milliseconds since epoch
+}
+
+/// Best bid/ask update from the `@bookTicker` stream.
+///
+/// Spot book tickers carry no event time, so the receive time is recorded.
+#[derive(Debug, Clone, PartialEq)]
+pub struct QuoteEvent {
+    pub symbol: String,
+    pub update_id: u64,
+    pub bid_price: f64,
+    pub bid_quantity: f64,
+    pub ask_price: f64,
+    pub ask_quantity: f64,
+    pub receive_time: u64, // Milliseconds since epoch
+}
+
+impl QuoteEvent {
+    pub fn mid_price(&self) -> f64 {
+        (self.bid_price + self.ask_price) / 2.0
+    }
+
+    pub fn spread(&self) -> f64 {
+        self.ask_price - self.bid_price
+    }
+
+    /// Quoted spread in basis points of the mid-price.
+    pub fn spread_bps(&self) -> f64 {
+        self.spread() / self.mid_price() * 10_000.0
+    }
+}
+
+/// Mark price update from a perpetual futures `@markPrice` stream.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MarkPriceEvent {
+    pub symbol: String,
+    pub event_time: u64, // Milliseconds since epoch
+    pub mark_price: f64,
+    pub index_price: f64,
+    pub funding_rate: f64, // Rate applied at the next funding time
+    pub next_funding_time: u64,
+}
+
+impl MarkPriceEvent {
+    /// Premium of the mark price over the index, as a fraction of the index.
+    pub fn premium(&self) -> f64 {
+        (self.mark_price - self.index_price) / self.index_price
+    }
+
+    /// Perp-spot basis in basis points of `spot_price`.
+    pub fn basis_bps(&self, spot_price: f64) -> f64 {
+        (self.mark_price - spot_price) / spot_price * 10_000.0
+    }
+}
+
+/// Messages forwarded from the WebSocket tasks to the processing pipeline.
+#[derive(Debug, Clone, PartialEq)]
+pub enum StreamEvent {
+    Kline(KlineEvent),
+    Trade(TradeEvent),
+    Book(BookSummary),
+    Quote(QuoteEvent),
+    MarkPrice(MarkPriceEvent),
+    /// Messages detected as missing from a stream.
+    Gap(StreamGap),
+    /// A message that could not be decoded; carries no price.
+    Error { symbol: String, error: MessageError },
+}
+
+impl StreamEvent {
+    pub fn symbol(&self) -> &str {
+        match self {
+            StreamEvent::Kline(kline) => &kline.symbol,
+            StreamEvent::Trade(trade) => &trade.symbol,
+            StreamEvent::Book(book) => &book.symbol,
+            StreamEvent::Quote(quote) => &quote.symbol,
+            StreamEvent::MarkPrice(mark) => &mark.symbol,
+            StreamEvent::Gap(gap) => &gap.symbol,
+            StreamEvent::Error { symbol, .. } => symbol,
+        }
+    }
+
+    /// Exchange event time in milliseconds since epoch.
+    pub fn event_time(&self) -> Option<u64> {
+        match self {
+            StreamEvent::Kline(kline) => Some(kline.event_time),
+            StreamEvent::Trade(trade) => Some(trade.event_time),
+            StreamEvent::Book(book) => Some(book.event_time),
+            StreamEvent::Quote(quote) => Some(quote.receive_time),
+            StreamEvent::MarkPrice(mark) => Some(mark.event_time),
+            StreamEvent::Gap(_) | StreamEvent::Error { .. } => None,
+        }
+    }
+
+    /// Which of its symbol's price series the event belongs to: the kline
+    /// interval (`1m`, `1h`, ...), or the stream for tick-level prices.
+    pub fn series(&self) -> Option<&str> {
+        match self {
+            StreamEvent::Kline(kline) => Some(&kline.interval),
+            StreamEvent::Trade(trade) => Some(trade.kind.suffix()),
+            StreamEvent::Book(_) => Some("depth"),
+            StreamEvent::Quote(_) => Some("bookTicker"),
+            StreamEvent::MarkPrice(_) => Some("markPrice"),
+            StreamEvent::Gap(_) | StreamEvent::Error { .. } => None,
+        }
+    }
+
+    /// Price to feed into the volatility calculation.
+    pub fn price(&self) -> Option<f64> {
+        match self {
+            StreamEvent::Kline(kline) => Some(kline.close),
+            StreamEvent::Trade(trade) => Some(trade.price),
+            StreamEvent::Book(book) => Some(book.mid_price),
+            StreamEvent::Quote(quote) => Some(quote.mid_price()),
+            StreamEvent::MarkPrice(mark) => Some(mark.mark_price),
+            StreamEvent::Gap(_) | StreamEvent::Error { .. } => None,
+        }
+    }
+
+    /// The event in the venue-neutral model, for trades, klines (as bars),
+    /// quotes and book summaries (as quotes priced at the mid, sized by the
+    /// top-N depth). Mark prices, gaps and errors have no equivalent.
+    pub fn to_market_event(&self, receive_time: u64) -> Option<MarketEvent> {
+        let (size, detail) = match self {
+            StreamEvent::Trade(trade) => (
+                Some(trade.quantity),
+                EventDetail::Trade {
+                    trade_id: Some(trade.trade_id),
+                    is_buyer_maker: Some(trade.is_buyer_maker),
+                },
+            ),
+            StreamEvent::Kline(kline) => (
+                Some(kline.volume),
+                EventDetail::Bar {
+                    open_time: kline.open_time,
+                    close_time: kline.close_time,
+                    open: kline.open,
+                    high: kline.high,
+                    low: kline.low,
+                    close: kline.close,
+                    volume: kline.volume,
+                    closed: kline.is_closed,
+                },
+            ),
+            StreamEvent::Quote(quote) => (
+                None,
+                EventDetail::Quote {
+                    bid: quote.bid_price,
+                    bid_size: quote.bid_quantity,
+                    ask: quote.ask_price,
+                    ask_size: quote.ask_quantity,
+                },
+            ),
+            StreamEvent::Book(book) => (
+                None,
+                EventDetail::Quote {
+                    bid: book.best_bid,
+                    bid_size: book.bid_depth,
+                    ask: book.best_ask,
+                    ask_size: book.ask_depth,
+                },
+            ),
+            StreamEvent::MarkPrice(_) | StreamEvent::Gap(_) | StreamEvent::Error { .. } => return None,
+        };
+        Some(MarketEvent {
+            source: "binance".to_string(),
+            instrument: self.symbol().to_string(),
+            event_time: self.event_time(),
+            receive_time,
+            price: self.price()?,
+            size,
+            detail,
+        })
+    }
+}
+
+/// Conflation keeps the latest event per symbol and stream type. Gaps and
+/// errors are never conflated.
+impl Conflate for StreamEvent {
+    fn conflation_key(&self) -> Option<String> {
+        let kind = match self {
+            StreamEvent::Kline(kline) => kline.interval.as_str(),
+            StreamEvent::Trade(_) => "trade",
+            StreamEvent::Book(_) => "book",
+            StreamEvent::Quote(_) => "quote",
+            StreamEvent::MarkPrice(_) => "markPrice",
+            StreamEvent::Gap(_) | StreamEvent::Error { .. } => return None,
+        };
+        Some(format!("{}:{}", self.symbol(), kind))
+    }
+}
+
+#[derive(Clone)]
 pub struct BinanceClient {
     base_url: String,
-    sender: UnboundedSender<(String, f64)>,
+    sender: EventSender<StreamEvent>,
+    rest: RestClient,
+    depth_levels: usize,
+    backfill_window: Option<Duration>,
+    max_connection_age: Duration,
+    gap_backfill: bool,
+    exchange_info_path: String,
+    // Used when the client acts as a `PriceSource`
+    ticks: TickChannel,
+    health: HealthTracker,
 }
 
 impl BinanceClient {
-    pub async fn new(base_url: &str, sender: UnboundedSender<(String, f64)>) -> Result<Self, Box<dyn std::error::Error>> {
+    /// Events go to `sender`: a bounded `EventSender` with a backpressure
+    /// policy, or a plain unbounded tokio sender.
+    pub async fn new(
+        base_url: &str,
+        sender: impl Into<EventSender<StreamEvent>>,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
         Ok(Self {
             base_url: base_url.to_string(),
-            sender,
+            sender: sender.into(),
+            rest: RestClient::new(BINANCE_REST_URL),
+            depth_levels: DEFAULT_DEPTH_LEVELS,
+            backfill_window: None,
+            max_connection_age: DEFAULT_MAX_CONNECTION_AGE,
+            gap_backfill: false,
+            exchange_info_path: EXCHANGE_INFO_PATH.to_string(),
+            ticks: TickChannel::new(),
+            health: HealthTracker::new(),
         })
     }
+
+    /// Overrides the REST endpoint used for order book snapshots and backfill.
+    pub fn with_rest_url(mut self, rest_url: &str) -> Self {
+        self.rest = RestClient::new(rest_url);
+        self
+    }
+
+    /// Number of levels per side summed into the reported book depth.
+    pub fn with_depth_levels(mut self, depth_levels: usize) -> Self {
+        self.depth_levels = depth_levels;
+        self
+    }
+
+    /// Seeds kline streams with enough closed candles from `/api/v3/klines`
+    /// to cover `window_secs` before going live.
+    pub fn with_backfill(mut self, window_secs: u64) -> Self {
+        self.backfill_window = Some(Duration::from_secs(window_secs));
+        self
+    }
+
+    /// Overrides the exchangeInfo path on the REST endpoint, e.g.
+    /// `/fapi/v1/exchangeInfo` for futures.
+    pub fn with_exchange_info_path(mut self, path: &str) -> Self {
+        self.exchange_info_path = path.to_string();
+        self
+    }
+
+    pub async fn exchange_info(&self) -> Result<ExchangeInfo, SymbolError> {
+        self.rest
+            .exchange_info(&self.exchange_info_path)
+            .await
+            .map_err(|e| SymbolError::Fetch(e.to_string()))
+    }
+
+    /// Checks the symbols against exchangeInfo, failing on the first one
+    /// that is unknown or not trading.
+    pub async fn validate_symbols(&self, symbols: &[String]) -> Result<HashMap<String, SymbolMetadata>, SymbolError> {
+        validate_symbols(&self.exchange_info().await?, symbols)
+    }
+
+    /// Fetches the candles or trades missing from a detected gap over REST
+    /// before forwarding the message that revealed it. Depth gaps are always
+    /// filled by resynchronizing the book.
+    pub fn with_gap_backfill(mut self, gap_backfill: bool) -> Self {
+        self.gap_backfill = gap_backfill;
+        self
+    }
+
+    /// How long a connection is used before it is replaced. Must stay below
+    /// Binance's 24 hour limit.
+    pub fn with_max_connection_age(mut self, max_connection_age: Duration) -> Self {
+        self.max_connection_age = max_connection_age;
+        self
+    }
+
+    /// Streams closed 1m klines for every symbol.
     pub async fn start_multi_symbol_stream(&self, symbols: Vec<String>) {
-        for symbol in symbols {
-            let sender_clone = self.sender.clone();
+        let subscriptions = symbols
+            .iter()
+            .map(|symbol| Subscription::new(symbol, StreamKind::Kline(KlineInterval::OneMinute)))
+            .collect();
+        self.start_streams(subscriptions).await;
+    }
+
+    /// Opens one WebSocket connection per subscription, rolling each over
+    /// to a fresh connection before `max_connection_age` elapses.
+    pub async fn start_streams(&self, subscriptions: Vec<Subscription>) {
+        for subscription in subscriptions {
             let base_url = self.base_url.clone();
-            
+            let backfill_window = self.backfill_window;
+            let max_connection_age = self.max_connection_age;
+            let mut handler = StreamHandler::new(
+                subscription.clone(),
+                self.sender.clone(),
+                self.rest.clone(),
+                self.depth_levels,
+                self.gap_backfill,
+            );
+
             tokio::spawn(async move {
-                let mut retry_attempts = 0;
-
-                loop {
-                    match connect_to_websocket(&base_url, &symbol).await {
-                        Ok(mut stream) => {
-                            info!("Connected to WebSocket for symbol: {}", symbol);
-
-                            while let Some(Ok(message)) = stream.next().await {
-                                if let Ok(text) = message.to_text() {
-                                    process_message(text, &symbol, &sender_clone).await;
-                                }
-                            }
-
-                            error!("WebSocket connection for {} closed unexpectedly. Reconnecting...", symbol);
-                        }
-                        Err(e) => {
-                            retry_attempts += 1;
-                            error!(
-                                "Failed to connect to WebSocket for {} (attempt {}): {}",
-                                symbol, retry_attempts, e
-                            );
-                        }
-                    }
-
-                    // Backoff between retries
-                    let backoff = Duration::from_secs(retry_attempts.min(10) as u64);
-                    info!(
-                        "Retrying connection to {} in {} seconds...",
-                        symbol, backoff.as_secs()
-                    );
-                    sleep(backoff).await;
+                if let (StreamKind::Kline(interval), Some(window)) = (subscription.kind, backfill_window) {
+                    handler.backfill_klines(interval, window).await;
                 }
+                run_subscription(base_url, handler, max_connection_age).await;
             });
         }
     }
 }
 
-async fn connect_to_websocket(
-    base_url: &str,
-    symbol: &str,
-) -> Result<WebSocketStream<MaybeTlsStream<TcpStream>>, Box<dyn std::error::Error + Send + Sync>> {
-    let url = format!("{}/{}@kline_1m", base_url, symbol);
-    let (stream, _) = connect_async(&url).await?;
-    Ok(stream)
-}
-
-async fn process_message(
-    message: &str,
-    symbol: &str,
-    sender: &UnboundedSender<(String, f64)>,
-) {
-    match serde_json::from_str::<serde_json::Value>(message) {
-        Ok(json) => {
-            if let Some(close_price) = extract_close_price(&json) {
-                if let Err(e) = sender.send((symbol.to_string(), close_price)) {
-                    error!("Failed to send data for {}: {}", symbol, e);
-                } else {
-                    info!("Streamed Data [{}]: Close price = {}", symbol, close_price);
-                }
-            }
+/// Decodes a raw stream message into a pipeline event.
+///
+/// Control responses yield `Ok(None)`; error payloads and unrecognized
+/// messages are returned as typed errors.
+pub fn parse_event(text: &str, subscription: &Subscription) -> Result<Option<StreamEvent>, MessageError> {
+    let symbol = subscription.symbol.clone();
+    let event = match parse_message(text, subscription.kind)? {
+        BinanceMessage::Kline(message) => {
+            StreamEvent::Kline(KlineEvent::new(&symbol, message.event_time, &message.kline))
         }
-        Err(e) => {
-            error!("Failed to parse WebSocket message for {}: {}", symbol, e);
+        BinanceMessage::Trade(trade) => StreamEvent::Trade(TradeEvent {
+            symbol,
+            kind: StreamKind::Trade,
+            price: trade.price,
+            quantity: trade.quantity,
+            is_buyer_maker: trade.is_buyer_maker,
+            trade_id: trade.trade_id,
+            first_trade_id: trade.trade_id,
+            last_trade_id: trade.trade_id,
+            event_time: trade.event_time,
+        }),
+        BinanceMessage::AggTrade(trade) => StreamEvent::Trade(TradeEvent {
+            symbol,
+            kind: StreamKind::AggTrade,
+            price: trade.price,
+            quantity: trade.quantity,
+            is_buyer_maker: trade.is_buyer_maker,
+            trade_id: trade.agg_trade_id,
+            first_trade_id: trade.first_trade_id,
+            last_trade_id: trade.last_trade_id,
+            event_time: trade.event_time,
+        }),
+        BinanceMessage::BookTicker(ticker) => StreamEvent::Quote(QuoteEvent {
+            symbol,
+            update_id: ticker.update_id,
+            bid_price: ticker.bid_price,
+            bid_quantity: ticker.bid_quantity,
+            ask_price: ticker.ask_price,
+            ask_quantity: ticker.ask_quantity,
+            receive_time: now_millis(),
+        }),
+        BinanceMessage::MarkPrice(mark) => StreamEvent::MarkPrice(MarkPriceEvent {
+            symbol,
+            event_time: mark.event_time,
+            mark_price: mark.mark_price,
+            index_price: mark.index_price,
+            funding_rate: mark.funding_rate,
+            next_funding_time: mark.next_funding_time,
+        }),
+        // Depth diffs are stateful and handled by the stream's `BookSync`
+        BinanceMessage::DepthUpdate(_) => return Ok(None),
+        BinanceMessage::Control(response) => {
+            info!("Control response for {}: {:?}", subscription.stream_name(), response);
+            return Ok(None);
         }
-    }
+    };
+    Ok(Some(event))
 }
-
-fn extract_close_price(json: &serde_json::Value) -> Option<f64> {
-    json["k"]["c"].as_str()?.parse::<f64>().ok()
-}
\ No newline at end of file
@@ -0,0 +1,354 @@
+use serde::de::{self, Deserializer};
+use serde::Deserialize;
+use std::fmt;
+
+use super::{StreamKind, TradeEvent};
+use crate::book::DepthUpdate;
+
+/// Parses a Binance decimal, sent as a string (`"2050.10"`) or occasionally
+/// as a JSON number. Non-finite values are rejected.
+pub(crate) fn de_decimal<'de, D>(deserializer: D) -> Result<f64, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Raw {
+        Str(String),
+        Num(f64),
+    }
+
+    let value = match Raw::deserialize(deserializer)? {
+        Raw::Str(s) => s.trim().parse::<f64>().map_err(de::Error::custom)?,
+        Raw::Num(n) => n,
+    };
+    if !value.is_finite() {
+        return Err(de::Error::custom(format!("non-finite decimal {}", value)));
+    }
+    Ok(value)
+}
+
+/// Parses book levels sent as `[["price", "qty"], ...]`.
+pub(crate) fn de_levels<'de, D>(deserializer: D) -> Result<Vec<(f64, f64)>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    struct Level(
+        #[serde(deserialize_with = "de_decimal")] f64,
+        #[serde(deserialize_with = "de_decimal")] f64,
+    );
+
+    let levels = Vec::<Level>::deserialize(deserializer)?;
+    Ok(levels.into_iter().map(|Level(price, qty)| (price, qty)).collect())
+}
+
+/// `@kline_<interval>` event.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct KlineMessage {
+    #[serde(rename = "E")]
+    pub event_time: u64,
+    #[serde(rename = "s")]
+    pub symbol: String,
+    #[serde(rename = "k")]
+    pub kline: KlinePayload,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct KlinePayload {
+    #[serde(rename = "t")]
+    pub open_time: u64,
+    #[serde(rename = "T")]
+    pub close_time: u64,
+    #[serde(rename = "i")]
+    pub interval: String,
+    #[serde(rename = "o", deserialize_with = "de_decimal")]
+    pub open: f64,
+    #[serde(rename = "h", deserialize_with = "de_decimal")]
+    pub high: f64,
+    #[serde(rename = "l", deserialize_with = "de_decimal")]
+    pub low: f64,
+    #[serde(rename = "c", deserialize_with = "de_decimal")]
+    pub close: f64,
+    #[serde(rename = "v", deserialize_with = "de_decimal")]
+    pub volume: f64,
+    #[serde(rename = "x")]
+    pub is_closed: bool,
+}
+
+/// One row of the REST `/api/v3/klines` response:
+/// `[openTime, open, high, low, close, volume, closeTime, quoteVolume,
+///   trades, takerBuyBase, takerBuyQuote, ignore]`.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct RestKline(
+    pub u64,
+    #[serde(deserialize_with = "de_decimal")] pub f64,
+    #[serde(deserialize_with = "de_decimal")] pub f64,
+    #[serde(deserialize_with = "de_decimal")] pub f64,
+    #[serde(deserialize_with = "de_decimal")] pub f64,
+    #[serde(deserialize_with = "de_decimal")] pub f64,
+    pub u64,
+    de::IgnoredAny,
+    de::IgnoredAny,
+    de::IgnoredAny,
+    de::IgnoredAny,
+    de::IgnoredAny,
+);
+
+impl RestKline {
+    /// Converts to the stream payload shape; candles ending after `now_ms`
+    /// are still open.
+    pub fn into_payload(self, interval: &str, now_ms: u64) -> KlinePayload {
+        KlinePayload {
+            open_time: self.0,
+            close_time: self.6,
+            interval: interval.to_string(),
+            open: self.1,
+            high: self.2,
+            low: self.3,
+            close: self.4,
+            volume: self.5,
+            is_closed: self.6 < now_ms,
+        }
+    }
+}
+
+/// `@trade` event.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct TradeMessage {
+    #[serde(rename = "E")]
+    pub event_time: u64,
+    #[serde(rename = "s")]
+    pub symbol: String,
+    #[serde(rename = "t")]
+    pub trade_id: u64,
+    #[serde(rename = "p", deserialize_with = "de_decimal")]
+    pub price: f64,
+    #[serde(rename = "q", deserialize_with = "de_decimal")]
+    pub quantity: f64,
+    #[serde(rename = "T")]
+    pub trade_time: u64,
+    #[serde(rename = "m")]
+    pub is_buyer_maker: bool,
+}
+
+/// `@aggTrade` event.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct AggTradeMessage {
+    #[serde(rename = "E")]
+    pub event_time: u64,
+    #[serde(rename = "s")]
+    pub symbol: String,
+    #[serde(rename = "a")]
+    pub agg_trade_id: u64,
+    #[serde(rename = "p", deserialize_with = "de_decimal")]
+    pub price: f64,
+    #[serde(rename = "q", deserialize_with = "de_decimal")]
+    pub quantity: f64,
+    #[serde(rename = "f")]
+    pub first_trade_id: u64,
+    #[serde(rename = "l")]
+    pub last_trade_id: u64,
+    #[serde(rename = "T")]
+    pub trade_time: u64,
+    #[serde(rename = "m")]
+    pub is_buyer_maker: bool,
+}
+
+/// One entry of the REST `/api/v3/historicalTrades` response.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct RestTrade {
+    pub id: u64,
+    #[serde(deserialize_with = "de_decimal")]
+    pub price: f64,
+    #[serde(rename = "qty", deserialize_with = "de_decimal")]
+    pub quantity: f64,
+    pub time: u64,
+    #[serde(rename = "isBuyerMaker")]
+    pub is_buyer_maker: bool,
+}
+
+impl RestTrade {
+    /// Converts to a trade event timestamped at the trade time.
+    pub fn into_event(self, symbol: &str) -> TradeEvent {
+        TradeEvent {
+            symbol: symbol.to_string(),
+            kind: StreamKind::Trade,
+            price: self.price,
+            quantity: self.quantity,
+            is_buyer_maker: self.is_buyer_maker,
+            trade_id: self.id,
+            first_trade_id: self.id,
+            last_trade_id: self.id,
+            event_time: self.time,
+        }
+    }
+}
+
+/// One entry of the REST `/api/v3/aggTrades` response.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct RestAggTrade {
+    #[serde(rename = "a")]
+    pub agg_trade_id: u64,
+    #[serde(rename = "p", deserialize_with = "de_decimal")]
+    pub price: f64,
+    #[serde(rename = "q", deserialize_with = "de_decimal")]
+    pub quantity: f64,
+    #[serde(rename = "f")]
+    pub first_trade_id: u64,
+    #[serde(rename = "l")]
+    pub last_trade_id: u64,
+    #[serde(rename = "T")]
+    pub trade_time: u64,
+    #[serde(rename = "m")]
+    pub is_buyer_maker: bool,
+}
+
+impl RestAggTrade {
+    /// Converts to a trade event timestamped at the trade time.
+    pub fn into_event(self, symbol: &str) -> TradeEvent {
+        TradeEvent {
+            symbol: symbol.to_string(),
+            kind: StreamKind::AggTrade,
+            price: self.price,
+            quantity: self.quantity,
+            is_buyer_maker: self.is_buyer_maker,
+            trade_id: self.agg_trade_id,
+            first_trade_id: self.first_trade_id,
+            last_trade_id: self.last_trade_id,
+            event_time: self.trade_time,
+        }
+    }
+}
+
+/// `@bookTicker` event.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct BookTickerMessage {
+    #[serde(rename = "u")]
+    pub update_id: u64,
+    #[serde(rename = "s")]
+    pub symbol: String,
+    #[serde(rename = "b", deserialize_with = "de_decimal")]
+    pub bid_price: f64,
+    #[serde(rename = "B", deserialize_with = "de_decimal")]
+    pub bid_quantity: f64,
+    #[serde(rename = "a", deserialize_with = "de_decimal")]
+    pub ask_price: f64,
+    #[serde(rename = "A", deserialize_with = "de_decimal")]
+    pub ask_quantity: f64,
+}
+
+/// `@markPrice` event from the USDⓈ-M futures streams.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct MarkPriceMessage {
+    #[serde(rename = "E")]
+    pub event_time: u64,
+    #[serde(rename = "s")]
+    pub symbol: String,
+    #[serde(rename = "p", deserialize_with = "de_decimal")]
+    pub mark_price: f64,
+    #[serde(rename = "i", deserialize_with = "de_decimal")]
+    pub index_price: f64,
+    #[serde(rename = "P", deserialize_with = "de_decimal")]
+    pub estimated_settle_price: f64,
+    #[serde(rename = "r", deserialize_with = "de_decimal")]
+    pub funding_rate: f64,
+    #[serde(rename = "T")]
+    pub next_funding_time: u64,
+}
+
+/// Reply to a SUBSCRIBE / UNSUBSCRIBE / LIST_SUBSCRIPTIONS request.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct ControlResponse {
+    pub result: serde_json::Value,
+    pub id: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+struct ApiError {
+    code: i64,
+    msg: String,
+}
+
+// Errors come either bare or wrapped in a control response
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ErrorResponse {
+    Wrapped { error: ApiError },
+    Bare(ApiError),
+}
+
+/// A decoded message from a Binance market data stream.
+#[derive(Debug, Clone, PartialEq)]
+pub enum BinanceMessage {
+    Kline(KlineMessage),
+    Trade(TradeMessage),
+    AggTrade(AggTradeMessage),
+    BookTicker(BookTickerMessage),
+    MarkPrice(MarkPriceMessage),
+    DepthUpdate(DepthUpdate),
+    Control(ControlResponse),
+}
+
+/// Stream messages that could not be turned into market data.
+#[derive(Debug, Clone, PartialEq)]
+pub enum MessageError {
+    /// Binance returned an error payload.
+    Api { code: i64, msg: String },
+    /// Valid JSON that is neither the expected event nor a known control message.
+    Unknown(String),
+    /// The payload is not valid JSON.
+    Malformed(String),
+}
+
+impl MessageError {
+    /// Short label used for metrics.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            MessageError::Api { .. } => "api_error",
+            MessageError::Unknown(_) => "unknown",
+            MessageError::Malformed(_) => "malformed",
+        }
+    }
+}
+
+impl fmt::Display for MessageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MessageError::Api { code, msg } => write!(f, "Binance error {}: {}", code, msg),
+            MessageError::Unknown(detail) => write!(f, "unexpected message: {}", detail),
+            MessageError::Malformed(detail) => write!(f, "malformed message: {}", detail),
+        }
+    }
+}
+
+impl std::error::Error for MessageError {}
+
+/// Decodes a raw message received on a stream of the given kind.
+pub fn parse_message(text: &str, kind: StreamKind) -> Result<BinanceMessage, MessageError> {
+    let expected = match kind {
+        StreamKind::Kline(_) => serde_json::from_str(text).map(BinanceMessage::Kline),
+        StreamKind::Trade => serde_json::from_str(text).map(BinanceMessage::Trade),
+        StreamKind::AggTrade => serde_json::from_str(text).map(BinanceMessage::AggTrade),
+        StreamKind::BookTicker => serde_json::from_str(text).map(BinanceMessage::BookTicker),
+        StreamKind::MarkPrice => serde_json::from_str(text).map(BinanceMessage::MarkPrice),
+        StreamKind::Depth => serde_json::from_str(text).map(BinanceMessage::DepthUpdate),
+    };
+
+    let e = match expected {
+        Ok(message) => return Ok(message),
+        Err(e) => e,
+    };
+
+    if e.is_syntax() || e.is_eof() {
+        return Err(MessageError::Malformed(e.to_string()));
+    }
+    if let Ok(response) = serde_json::from_str::<ErrorResponse>(text) {
+        let (ErrorResponse::Wrapped { error } | ErrorResponse::Bare(error)) = response;
+        return Err(MessageError::Api { code: error.code, msg: error.msg });
+    }
+    if let Ok(control) = serde_json::from_str::<ControlResponse>(text) {
+        return Ok(BinanceMessage::Control(control));
+    }
+    Err(MessageError::Unknown(e.to_string()))
+}
@@ -0,0 +1,121 @@
+use hyper::client::HttpConnector;
+use hyper::{Body, Client, Uri};
+use hyper_tls::HttpsConnector;
+use serde::de::DeserializeOwned;
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use super::kline::KlineInterval;
+use super::models::{KlinePayload, RestAggTrade, RestKline, RestTrade};
+use super::symbols::ExchangeInfo;
+use crate::book::DepthSnapshot;
+
+pub const BINANCE_REST_URL: &str = "https://api.binance.com";
+pub const BINANCE_FUTURES_REST_URL: &str = "https://fapi.binance.com";
+pub const EXCHANGE_INFO_PATH: &str = "/api/v3/exchangeInfo";
+pub const FUTURES_EXCHANGE_INFO_PATH: &str = "/fapi/v1/exchangeInfo";
+
+type RestResult<T> = Result<T, Box<dyn std::error::Error + Send + Sync>>;
+
+/// Minimal client for the Binance public REST API.
+///
+/// The base URL is configurable so tests and local stand-ins can serve the
+/// same endpoints over plain HTTP.
+#[derive(Clone)]
+pub struct RestClient {
+    base_url: String,
+    http: Client<HttpsConnector<HttpConnector>, Body>,
+}
+
+impl RestClient {
+    pub fn new(base_url: &str) -> Self {
+        Self {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            http: Client::builder().build(HttpsConnector::new()),
+        }
+    }
+
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    /// Issues a GET request and deserializes the JSON response body.
+    pub async fn get_json<T: DeserializeOwned>(&self, path: &str, query: &str) -> RestResult<T> {
+        let uri: Uri = format!("{}{}?{}", self.base_url, path, query).parse()?;
+        let response = self.http.get(uri).await?;
+        let status = response.status();
+        let body = hyper::body::to_bytes(response.into_body()).await?;
+
+        if !status.is_success() {
+            return Err(format!(
+                "GET {} failed with {}: {}",
+                path,
+                status,
+                String::from_utf8_lossy(&body)
+            )
+            .into());
+        }
+
+        Ok(serde_json::from_slice(&body)?)
+    }
+
+    /// Fetches an order book snapshot from `/api/v3/depth`.
+    pub async fn depth_snapshot(&self, symbol: &str, limit: u32) -> RestResult<DepthSnapshot> {
+        let query = format!("symbol={}&limit={}", symbol.to_uppercase(), limit);
+        self.get_json("/api/v3/depth", &query).await
+    }
+
+    /// Fetches the most recent `limit` klines from `/api/v3/klines`, oldest first.
+    pub async fn klines(&self, symbol: &str, interval: KlineInterval, limit: u32) -> RestResult<Vec<KlinePayload>> {
+        let query = format!("symbol={}&interval={}&limit={}", symbol.to_uppercase(), interval, limit);
+        self.get_klines(&query, interval).await
+    }
+
+    /// Fetches the klines opening between `start_time` and `end_time`
+    /// (inclusive, milliseconds), up to one page.
+    pub async fn klines_between(
+        &self,
+        symbol: &str,
+        interval: KlineInterval,
+        start_time: u64,
+        end_time: u64,
+    ) -> RestResult<Vec<KlinePayload>> {
+        let query = format!(
+            "symbol={}&interval={}&startTime={}&endTime={}&limit=1000",
+            symbol.to_uppercase(),
+            interval,
+            start_time,
+            end_time
+        );
+        self.get_klines(&query, interval).await
+    }
+
+    async fn get_klines(&self, query: &str, interval: KlineInterval) -> RestResult<Vec<KlinePayload>> {
+        let rows: Vec<RestKline> = self.get_json("/api/v3/klines", query).await?;
+        let now_ms = SystemTime::now().duration_since(UNIX_EPOCH)?.as_millis() as u64;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| row.into_payload(interval.as_str(), now_ms))
+            .collect())
+    }
+
+    /// Fetches up to `limit` trades starting at trade id `from_id` from
+    /// `/api/v3/historicalTrades`.
+    pub async fn historical_trades(&self, symbol: &str, from_id: u64, limit: u32) -> RestResult<Vec<RestTrade>> {
+        let query = format!("symbol={}&fromId={}&limit={}", symbol.to_uppercase(), from_id, limit);
+        self.get_json("/api/v3/historicalTrades", &query).await
+    }
+
+    /// Fetches up to `limit` aggregate trades starting at aggregate id
+    /// `from_id` from `/api/v3/aggTrades`.
+    pub async fn agg_trades(&self, symbol: &str, from_id: u64, limit: u32) -> RestResult<Vec<RestAggTrade>> {
+        let query = format!("symbol={}&fromId={}&limit={}", symbol.to_uppercase(), from_id, limit);
+        self.get_json("/api/v3/aggTrades", &query).await
+    }
+
+    /// Fetches trading rules for all symbols from the exchangeInfo endpoint at `path`.
+    pub async fn exchange_info(&self, path: &str) -> RestResult<ExchangeInfo> {
+        self.get_json(path, "").await
+    }
+}
@@ -0,0 +1,76 @@
+use std::fmt;
+
+/// How a message's ids relate to the last one seen on the same stream.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Continuity {
+    /// Nothing seen yet, so continuity cannot be judged.
+    First,
+    /// Picks up exactly where the previous message ended.
+    Contiguous,
+    /// Already covered by an earlier message.
+    Duplicate,
+    /// Ids `first_missing..=last_missing` never arrived.
+    Gap { first_missing: u64, last_missing: u64 },
+}
+
+/// Tracks a monotonically increasing id sequence for one stream: trade ids,
+/// aggTrade first/last trade ids, candle indices or book ticker update ids.
+#[derive(Debug, Clone, Default)]
+pub struct SequenceFilter {
+    last_id: Option<u64>,
+}
+
+impl SequenceFilter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn last_id(&self) -> Option<u64> {
+        self.last_id
+    }
+
+    /// Records a message covering ids `first..=last` unless it is a duplicate.
+    pub fn observe(&mut self, first: u64, last: u64) -> Continuity {
+        let continuity = match self.last_id {
+            None => Continuity::First,
+            Some(prev) if last <= prev => return Continuity::Duplicate,
+            Some(prev) if first > prev + 1 => Continuity::Gap {
+                first_missing: prev + 1,
+                last_missing: first - 1,
+            },
+            Some(_) => Continuity::Contiguous,
+        };
+        self.last_id = Some(last);
+        continuity
+    }
+
+    /// Returns whether `id` is new, recording it if so. For sequences that
+    /// are increasing but not contiguous, such as book ticker update ids.
+    pub fn accept(&mut self, id: u64) -> bool {
+        self.observe(id, id) != Continuity::Duplicate
+    }
+}
+
+/// Messages missing from a stream, reported once per detected gap.
+///
+/// Ids are in the stream's own units: trade ids for trade and aggTrade
+/// streams, candle open times for klines, and update ids for depth.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StreamGap {
+    pub symbol: String,
+    pub stream: &'static str, // Stream suffix, e.g. `trade`
+    pub first_missing: u64,
+    pub last_missing: u64,
+    pub missing: u64, // Number of trades, candles or updates lost
+    pub detected_at: u64, // Milliseconds since epoch
+}
+
+impl fmt::Display for StreamGap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} missing from {}@{} ({}..={})",
+            self.missing, self.symbol, self.stream, self.first_missing, self.last_missing
+        )
+    }
+}
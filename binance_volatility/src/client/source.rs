@@ -0,0 +1,65 @@
+use async_trait::async_trait;
+use futures_util::stream::BoxStream;
+use volatility_core::channel::{bounded, BackpressurePolicy};
+use volatility_core::event::{now_millis, MarketEvent};
+use volatility_core::source::{ConnectionState, Health, PriceSource, SourceError, DEFAULT_TICK_CAPACITY};
+
+use super::{BinanceClient, StreamEvent, StreamKind, Subscription};
+
+/// Binance as a venue-neutral source of `@trade` ticks. The client's own
+/// event channel is left untouched; ticks flow through a separate channel.
+#[async_trait]
+impl PriceSource for BinanceClient {
+    fn venue(&self) -> &str {
+        "binance"
+    }
+
+    /// Streams connect per subscription, so this checks the REST API is reachable.
+    async fn connect(&mut self) -> Result<(), SourceError> {
+        self.exchange_info()
+            .await
+            .map_err(|e| SourceError::Connect(e.to_string()))?;
+        self.health.set_state(ConnectionState::Connected);
+        Ok(())
+    }
+
+    async fn subscribe(&mut self, symbols: &[String]) -> Result<(), SourceError> {
+        if self.health.snapshot().state == ConnectionState::Idle {
+            return Err(SourceError::NotConnected);
+        }
+
+        let (sender, mut events) = bounded(DEFAULT_TICK_CAPACITY, BackpressurePolicy::Block);
+        let client = BinanceClient { sender, ..self.clone() };
+        let subscriptions = symbols
+            .iter()
+            .map(|symbol| Subscription::new(&symbol.to_lowercase(), StreamKind::Trade))
+            .collect();
+        client.start_streams(subscriptions).await;
+
+        let ticks = self.ticks.sender();
+        let health = self.health.clone();
+        tokio::spawn(async move {
+            while let Some(event) = events.recv().await {
+                if !matches!(event, StreamEvent::Trade(_)) {
+                    continue;
+                }
+                let Some(mut tick) = event.to_market_event(now_millis()) else { continue };
+                tick.instrument = tick.instrument.to_uppercase();
+                health.record_tick(tick.timestamp());
+                if ticks.send(tick).await.is_err() {
+                    health.set_state(ConnectionState::Closed);
+                    return;
+                }
+            }
+        });
+        Ok(())
+    }
+
+    fn ticks(&mut self) -> Result<BoxStream<'static, MarketEvent>, SourceError> {
+        self.ticks.take_stream()
+    }
+
+    fn health(&self) -> Health {
+        self.health.snapshot()
+    }
+}
@@ -0,0 +1,534 @@
+use futures_util::{SinkExt, StreamExt};
+use log::{info, warn, error};
+use std::collections::VecDeque;
+use std::pin::pin;
+use tokio::net::TcpStream;
+use tokio::time::{sleep, timeout, Duration, Instant};
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::{connect_async, MaybeTlsStream, WebSocketStream};
+use volatility_core::channel::EventSender;
+use volatility_core::event::now_millis;
+
+use super::kline::{backfill_limit, KlineEvent, KlineFilter, KlineInterval};
+use super::models::{parse_message, BinanceMessage, KlinePayload};
+use super::rest::RestClient;
+use super::sequence::{Continuity, SequenceFilter, StreamGap};
+use super::{parse_event, StreamEvent, StreamKind, Subscription, TradeEvent};
+use crate::book::{BookError, BookSync, UpdateOutcome};
+
+pub(crate) type WsStream = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+// Snapshot size requested when (re)synchronizing an order book
+const DEPTH_SNAPSHOT_LIMIT: u32 = 1000;
+
+// Delay before the first snapshot retry after a failed resync, doubling up to the cap
+const SNAPSHOT_RETRY_MIN: Duration = Duration::from_secs(1);
+const SNAPSHOT_RETRY_MAX: Duration = Duration::from_secs(30);
+
+// Largest page the REST trade endpoints return
+const MAX_TRADE_BACKFILL: u64 = 1000;
+
+// Binance pings every 20 seconds; silence for this long means the connection is dead
+const IDLE_TIMEOUT: Duration = Duration::from_secs(60);
+
+// How long both connections are read after the replacement delivers data,
+// capped at a tenth of the connection age
+const ROLLOVER_OVERLAP: Duration = Duration::from_secs(5);
+
+// Replacement connections that fail to deliver within this time are abandoned
+const ROLLOVER_TIMEOUT: Duration = Duration::from_secs(30);
+
+// Delay before retrying a failed rollover on the still-open connection
+const ROLLOVER_RETRY: Duration = Duration::from_secs(60);
+
+/// Per-subscription state that survives reconnects and rollovers, so
+/// messages delivered twice are only forwarded once and messages that never
+/// arrived are reported as gaps.
+pub(crate) struct StreamHandler {
+    subscription: Subscription,
+    sender: EventSender<StreamEvent>,
+    rest: RestClient,
+    depth_levels: usize,
+    gap_backfill: bool,
+    kline_filter: KlineFilter,
+    // Trade id ranges, closed candle indices, book ticker update ids or
+    // mark price event times
+    sequence: SequenceFilter,
+    last_trade_id: Option<u64>, // Id of the last forwarded trade or aggTrade
+    book: BookSync,
+    snapshot_retry_at: Option<Instant>, // No snapshot is fetched before this
+    snapshot_backoff: Duration,
+}
+
+impl StreamHandler {
+    pub(crate) fn new(
+        subscription: Subscription,
+        sender: EventSender<StreamEvent>,
+        rest: RestClient,
+        depth_levels: usize,
+        gap_backfill: bool,
+    ) -> Self {
+        let kline_filter = KlineFilter::new(subscription.kline_mode);
+        Self {
+            subscription,
+            sender,
+            rest,
+            depth_levels,
+            gap_backfill,
+            kline_filter,
+            sequence: SequenceFilter::new(),
+            last_trade_id: None,
+            book: BookSync::new(),
+            snapshot_retry_at: None,
+            snapshot_backoff: SNAPSHOT_RETRY_MIN,
+        }
+    }
+
+    /// Replays recent closed candles through the stream's kline filter so the
+    /// live stream picks up after the last backfilled candle.
+    pub(crate) async fn backfill_klines(&mut self, interval: KlineInterval, window: Duration) {
+        let symbol = self.subscription.symbol.clone();
+        let limit = backfill_limit(window, interval);
+        if u128::from(limit) * u128::from(interval.millis()) <= window.as_millis() {
+            warn!(
+                "Backfill of {} {} klines is capped at {} candles, short of the {}s window",
+                symbol, interval, limit, window.as_secs()
+            );
+        }
+        let klines = match self.rest.klines(&symbol, interval, limit).await {
+            Ok(klines) => klines,
+            Err(e) => {
+                error!("Failed to backfill {} klines for {}: {}", interval, symbol, e);
+                return;
+            }
+        };
+
+        let seeded = self.send_closed_klines(interval, &klines).await;
+        info!("Backfilled {} closed {} klines for {}", seeded, interval, symbol);
+    }
+
+    /// Forwards closed REST candles the kline filter has not seen yet,
+    /// returning how many were sent.
+    async fn send_closed_klines(&mut self, interval: KlineInterval, klines: &[KlinePayload]) -> usize {
+        let symbol = &self.subscription.symbol;
+        let mut sent = 0;
+        for kline in klines.iter().filter(|kline| kline.is_closed) {
+            if !self.kline_filter.accept(kline.open_time, kline.close, true) {
+                continue;
+            }
+            if let Some(candle) = candle_index(interval, kline.open_time) {
+                self.sequence.observe(candle, candle);
+            }
+            // Closed candles are timestamped at their close
+            let event = StreamEvent::Kline(KlineEvent::new(symbol, kline.close_time, kline));
+            if let Err(e) = self.sender.send(event).await {
+                error!("Failed to send backfilled kline for {}: {}", symbol, e);
+                break;
+            }
+            sent += 1;
+        }
+        sent
+    }
+
+    /// Logs a gap and forwards it to the pipeline.
+    async fn report_gap(&self, first_missing: u64, last_missing: u64, missing: u64) {
+        let gap = StreamGap {
+            symbol: self.subscription.symbol.clone(),
+            stream: self.subscription.kind.suffix(),
+            first_missing,
+            last_missing,
+            missing,
+            detected_at: now_millis(),
+        };
+        warn!("Stream gap detected: {}", gap);
+        if let Err(e) = self.sender.send(StreamEvent::Gap(gap)).await {
+            error!("Failed to send gap for {}: {}", self.subscription.symbol, e);
+        }
+    }
+
+    /// Checks a kline update against the closed candles seen so far: every
+    /// candle before an open one, and every candle up to a closed one,
+    /// should have closed already.
+    async fn check_kline(&mut self, interval: KlineInterval, open_time: u64, is_closed: bool) {
+        let Some(candle) = candle_index(interval, open_time) else { return };
+        let through = if is_closed { candle } else { candle - 1 };
+        if let Continuity::Gap { first_missing, last_missing } = self.sequence.observe(candle, through) {
+            let start_time = first_missing * interval.millis();
+            let end_time = last_missing * interval.millis();
+            self.report_gap(start_time, end_time, last_missing - first_missing + 1).await;
+            if self.gap_backfill {
+                self.fill_kline_gap(interval, start_time, end_time).await;
+            }
+        }
+    }
+
+    async fn fill_kline_gap(&mut self, interval: KlineInterval, start_time: u64, end_time: u64) {
+        let symbol = self.subscription.symbol.clone();
+        match self.rest.klines_between(&symbol, interval, start_time, end_time).await {
+            Ok(klines) => {
+                let sent = self.send_closed_klines(interval, &klines).await;
+                info!("Filled gap in {} {} klines with {} candles", symbol, interval, sent);
+            }
+            Err(e) => error!("Failed to fill {} kline gap for {}: {}", interval, symbol, e),
+        }
+    }
+
+    /// Checks a trade's id range for continuity. Returns false for trades
+    /// that were already forwarded.
+    async fn check_trade(&mut self, trade: &TradeEvent) -> bool {
+        match self.sequence.observe(trade.first_trade_id, trade.last_trade_id) {
+            Continuity::Duplicate => return false,
+            Continuity::Gap { first_missing, last_missing } => {
+                self.report_gap(first_missing, last_missing, last_missing - first_missing + 1).await;
+                if self.gap_backfill {
+                    self.fill_trade_gap(trade.trade_id).await;
+                }
+            }
+            Continuity::First | Continuity::Contiguous => {}
+        }
+        self.last_trade_id = Some(trade.trade_id);
+        true
+    }
+
+    /// Fetches the trades (or aggregate trades) between the last forwarded
+    /// one and `next_id`, up to one REST page.
+    async fn fill_trade_gap(&mut self, next_id: u64) {
+        let Some(last_id) = self.last_trade_id else { return };
+        let symbol = self.subscription.symbol.clone();
+        let from_id = last_id + 1;
+        let limit = next_id.saturating_sub(from_id).min(MAX_TRADE_BACKFILL) as u32;
+        if limit == 0 {
+            return;
+        }
+
+        let trades: Result<Vec<TradeEvent>, _> = match self.subscription.kind {
+            StreamKind::AggTrade => self
+                .rest
+                .agg_trades(&symbol, from_id, limit)
+                .await
+                .map(|trades| trades.into_iter().map(|trade| trade.into_event(&symbol)).collect()),
+            _ => self
+                .rest
+                .historical_trades(&symbol, from_id, limit)
+                .await
+                .map(|trades| trades.into_iter().map(|trade| trade.into_event(&symbol)).collect()),
+        };
+
+        match trades {
+            Ok(trades) => {
+                let mut sent = 0;
+                for trade in trades.into_iter().filter(|trade| trade.trade_id < next_id) {
+                    if let Err(e) = self.sender.send(StreamEvent::Trade(trade)).await {
+                        error!("Failed to send backfilled trade for {}: {}", symbol, e);
+                        return;
+                    }
+                    sent += 1;
+                }
+                info!("Filled gap in {}@{} with {} trades", symbol, self.subscription.kind.suffix(), sent);
+            }
+            Err(e) => error!("Failed to fill trade gap for {}: {}", symbol, e),
+        }
+    }
+
+    async fn handle_text(&mut self, text: &str) {
+        if self.subscription.kind == StreamKind::Depth {
+            self.handle_depth(text).await;
+        } else {
+            self.handle_event(text).await;
+        }
+    }
+
+    async fn handle_event(&mut self, text: &str) {
+        let event = match parse_event(text, &self.subscription) {
+            Ok(Some(event)) => event,
+            Ok(None) => return,
+            Err(error) => {
+                let symbol = &self.subscription.symbol;
+                error!("Failed to decode WebSocket message for {}: {}", symbol, error);
+                StreamEvent::Error { symbol: symbol.clone(), error }
+            }
+        };
+
+        let is_new = match &event {
+            StreamEvent::Kline(kline) => {
+                if let StreamKind::Kline(interval) = self.subscription.kind {
+                    self.check_kline(interval, kline.open_time, kline.is_closed).await;
+                }
+                self.kline_filter.accept(kline.open_time, kline.close, kline.is_closed)
+            }
+            StreamEvent::Trade(trade) => self.check_trade(trade).await,
+            // Book ticker ids are shared across symbols, so only order is checked
+            StreamEvent::Quote(quote) => self.sequence.accept(quote.update_id),
+            StreamEvent::MarkPrice(mark) => self.sequence.accept(mark.event_time),
+            _ => true,
+        };
+        if !is_new {
+            return;
+        }
+
+        let symbol = &self.subscription.symbol;
+        let price = event.price();
+        if let Err(e) = self.sender.send(event).await {
+            error!("Failed to send data for {}: {}", symbol, e);
+        } else if let Some(price) = price {
+            info!("Streamed Data [{}]: Price = {}", self.subscription.stream_name(), price);
+        }
+    }
+
+    // Delays the next snapshot fetch, doubling the delay after each failure
+    fn back_off_snapshot(&mut self) {
+        info!(
+            "Retrying order book snapshot for {} in {}s",
+            self.subscription.symbol,
+            self.snapshot_backoff.as_secs()
+        );
+        self.snapshot_retry_at = Some(Instant::now() + self.snapshot_backoff);
+        self.snapshot_backoff = (self.snapshot_backoff * 2).min(SNAPSHOT_RETRY_MAX);
+    }
+
+    /// Maintains the local order book, resynchronizing from a REST snapshot
+    /// on startup and whenever a sequence gap is detected.
+    async fn handle_depth(&mut self, text: &str) {
+        let symbol = self.subscription.symbol.clone();
+        let update = match parse_message(text, StreamKind::Depth) {
+            Ok(BinanceMessage::DepthUpdate(update)) => update,
+            Ok(_) => return,
+            Err(error) => {
+                error!("Failed to decode depth message for {}: {}", symbol, error);
+                let _ = self.sender.send(StreamEvent::Error { symbol, error }).await;
+                return;
+            }
+        };
+        let event_time = update.event_time;
+
+        match self.book.on_update(update) {
+            Ok(UpdateOutcome::Applied) => {}
+            Ok(UpdateOutcome::Ignored) => return,
+            Ok(UpdateOutcome::Buffered) => {
+                // One snapshot per attempt; diffs arriving before the retry are only buffered
+                if self.snapshot_retry_at.is_some_and(|at| Instant::now() < at) {
+                    return;
+                }
+                let synced = match self.rest.depth_snapshot(&symbol, DEPTH_SNAPSHOT_LIMIT).await {
+                    Ok(snapshot) => match self.book.on_snapshot(&snapshot) {
+                        Ok(()) => {
+                            info!("Order book for {} synchronized at update {}", symbol, snapshot.last_update_id);
+                            true
+                        }
+                        Err(e) => {
+                            warn!("Order book sync for {} failed: {}", symbol, e);
+                            false
+                        }
+                    },
+                    Err(e) => {
+                        error!("Failed to fetch depth snapshot for {}: {}", symbol, e);
+                        false
+                    }
+                };
+                if !synced {
+                    self.back_off_snapshot();
+                    return;
+                }
+                self.snapshot_retry_at = None;
+                self.snapshot_backoff = SNAPSHOT_RETRY_MIN;
+            }
+            Err(e) => {
+                // The resync from a fresh snapshot is what fills the gap
+                if let BookError::Gap { expected, got } = e {
+                    self.report_gap(expected, got - 1, got - expected).await;
+                }
+                warn!("Order book gap for {}: {}. Resynchronizing...", symbol, e);
+                self.book.reset();
+                return;
+            }
+        }
+
+        if let Some(summary) = self.book.book().summary(&symbol, event_time, self.depth_levels) {
+            if let Err(e) = self.sender.send(StreamEvent::Book(summary)).await {
+                error!("Failed to send book data for {}: {}", symbol, e);
+            }
+        }
+    }
+}
+
+/// Candle index of `open_time`; `None` for monthly candles, which vary in length.
+fn candle_index(interval: KlineInterval, open_time: u64) -> Option<u64> {
+    match interval {
+        KlineInterval::OneMonth => None,
+        _ => Some(open_time / interval.millis()),
+    }
+}
+
+async fn connect_to_websocket(
+    base_url: &str,
+    stream_name: &str,
+) -> Result<WsStream, Box<dyn std::error::Error + Send + Sync>> {
+    let url = format!("{}/{}", base_url, stream_name);
+    let (stream, _) = connect_async(&url).await?;
+    Ok(stream)
+}
+
+/// Reads the next text frame, answering pings along the way. Returns `None`
+/// once the connection is closed, errors, or stays silent past the idle timeout.
+pub(crate) async fn next_text(stream: &mut WsStream, stream_name: &str) -> Option<String> {
+    loop {
+        match timeout(IDLE_TIMEOUT, stream.next()).await {
+            Err(_) => {
+                warn!("No frames on {} for {}s; treating connection as dead", stream_name, IDLE_TIMEOUT.as_secs());
+                return None;
+            }
+            Ok(None) => return None,
+            Ok(Some(Err(e))) => {
+                error!("WebSocket error on {}: {}", stream_name, e);
+                return None;
+            }
+            Ok(Some(Ok(Message::Text(text)))) => return Some(text.to_string()),
+            Ok(Some(Ok(Message::Ping(payload)))) => {
+                if let Err(e) = stream.send(Message::Pong(payload)).await {
+                    error!("Failed to answer ping on {}: {}", stream_name, e);
+                    return None;
+                }
+            }
+            Ok(Some(Ok(Message::Close(frame)))) => {
+                info!("Server closed {}: {:?}", stream_name, frame);
+                return None;
+            }
+            Ok(Some(Ok(_))) => continue,
+        }
+    }
+}
+
+/// Runs one subscription forever: connects with backoff, and replaces each
+/// connection before `max_connection_age` (Binance closes them after 24h)
+/// by opening the new one first and reading both until they overlap.
+pub(crate) async fn run_subscription(
+    base_url: String,
+    mut handler: StreamHandler,
+    max_connection_age: Duration,
+) {
+    let stream_name = handler.subscription.stream_name();
+    let overlap_window = ROLLOVER_OVERLAP.min(max_connection_age / 10);
+    let mut retry_attempts = 0;
+    let mut current: Option<WsStream> = None;
+
+    loop {
+        let mut stream = match current.take() {
+            Some(stream) => stream,
+            None => match connect_to_websocket(&base_url, &stream_name).await {
+                Ok(stream) => {
+                    info!("Connected to WebSocket stream: {}", stream_name);
+                    retry_attempts = 0;
+                    stream
+                }
+                Err(e) => {
+                    retry_attempts += 1;
+                    error!(
+                        "Failed to connect to WebSocket for {} (attempt {}): {}",
+                        stream_name, retry_attempts, e
+                    );
+
+                    // Backoff between retries
+                    let backoff = Duration::from_secs(retry_attempts.min(10) as u64);
+                    info!(
+                        "Retrying connection to {} in {} seconds...",
+                        stream_name, backoff.as_secs()
+                    );
+                    sleep(backoff).await;
+                    continue;
+                }
+            },
+        };
+
+        let mut rollover = pin!(sleep(max_connection_age));
+        loop {
+            tokio::select! {
+                text = next_text(&mut stream, &stream_name) => match text {
+                    Some(text) => handler.handle_text(&text).await,
+                    None => break,
+                },
+                _ = &mut rollover => {
+                    match connect_to_websocket(&base_url, &stream_name).await {
+                        Ok(replacement) => {
+                            info!("Rolling over WebSocket stream {} to a new connection", stream_name);
+                            match overlap(&mut handler, stream, replacement, &stream_name, overlap_window).await {
+                                Ok(replacement) => current = Some(replacement),
+                                Err(old) => {
+                                    warn!("Replacement connection for {} failed; keeping the old one", stream_name);
+                                    stream = old;
+                                    rollover.as_mut().reset(Instant::now() + ROLLOVER_RETRY);
+                                    continue;
+                                }
+                            }
+                            break;
+                        }
+                        Err(e) => {
+                            error!("Failed to open replacement connection for {}: {}", stream_name, e);
+                            rollover.as_mut().reset(Instant::now() + ROLLOVER_RETRY);
+                        }
+                    }
+                }
+            }
+        }
+
+        if current.is_none() {
+            error!("WebSocket connection for {} closed unexpectedly. Reconnecting...", stream_name);
+        }
+    }
+}
+
+/// Reads the old connection while queueing the replacement's messages, so
+/// the old connection's backlog is forwarded before anything the replacement
+/// saw first. Switches once the old connection delivers the replacement's
+/// first message, or `overlap_window` after the replacement delivered data,
+/// then replays the queue; the handler drops anything seen twice. Returns
+/// the replacement, or the old connection if the replacement never delivered.
+async fn overlap(
+    handler: &mut StreamHandler,
+    mut old: WsStream,
+    mut replacement: WsStream,
+    stream_name: &str,
+    overlap_window: Duration,
+) -> Result<WsStream, WsStream> {
+    let mut deadline = pin!(sleep(ROLLOVER_TIMEOUT));
+    let mut pending: VecDeque<String> = VecDeque::new();
+    let mut old_open = true;
+
+    // Once the old connection closes, nothing is left to wait for
+    while old_open || pending.is_empty() {
+        tokio::select! {
+            biased;
+            text = next_text(&mut old, stream_name), if old_open => match text {
+                Some(text) => {
+                    let caught_up = pending.front() == Some(&text);
+                    handler.handle_text(&text).await;
+                    if caught_up {
+                        break;
+                    }
+                }
+                None => old_open = false,
+            },
+            text = next_text(&mut replacement, stream_name) => match text {
+                Some(text) => {
+                    if pending.is_empty() {
+                        deadline.as_mut().reset(Instant::now() + overlap_window);
+                    }
+                    pending.push_back(text);
+                }
+                None => return Err(old),
+            },
+            _ = &mut deadline => break,
+        }
+    }
+
+    if pending.is_empty() {
+        let _ = replacement.close(None).await;
+        return Err(old);
+    }
+    if old_open {
+        let _ = old.close(None).await;
+    }
+    for text in pending {
+        handler.handle_text(&text).await;
+    }
+    Ok(replacement)
+}
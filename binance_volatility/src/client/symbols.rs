@@ -0,0 +1,132 @@
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fmt;
+
+use super::models::de_decimal;
+
+/// Response of `/api/v3/exchangeInfo` (or `/fapi/v1/exchangeInfo`),
+/// reduced to the fields used here.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct ExchangeInfo {
+    pub symbols: Vec<SymbolInfo>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct SymbolInfo {
+    pub symbol: String,
+    pub status: String,
+    #[serde(rename = "baseAsset")]
+    pub base_asset: String,
+    #[serde(rename = "quoteAsset")]
+    pub quote_asset: String,
+    #[serde(default)]
+    pub filters: Vec<SymbolFilter>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(tag = "filterType")]
+pub enum SymbolFilter {
+    #[serde(rename = "PRICE_FILTER")]
+    Price {
+        #[serde(rename = "tickSize", deserialize_with = "de_decimal")]
+        tick_size: f64,
+    },
+    #[serde(rename = "LOT_SIZE")]
+    LotSize {
+        #[serde(rename = "stepSize", deserialize_with = "de_decimal")]
+        step_size: f64,
+    },
+    #[serde(other)]
+    Other,
+}
+
+/// Trading rules and assets for a validated symbol.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SymbolMetadata {
+    pub symbol: String, // As configured, e.g. `ethusdc`
+    pub status: String,
+    pub base_asset: String,
+    pub quote_asset: String,
+    pub tick_size: Option<f64>,
+    pub step_size: Option<f64>,
+}
+
+impl SymbolMetadata {
+    fn new(symbol: &str, info: &SymbolInfo) -> Self {
+        let mut tick_size = None;
+        let mut step_size = None;
+        for filter in &info.filters {
+            match filter {
+                SymbolFilter::Price { tick_size: tick } => tick_size = Some(*tick),
+                SymbolFilter::LotSize { step_size: step } => step_size = Some(*step),
+                SymbolFilter::Other => {}
+            }
+        }
+
+        Self {
+            symbol: symbol.to_string(),
+            status: info.status.clone(),
+            base_asset: info.base_asset.clone(),
+            quote_asset: info.quote_asset.clone(),
+            tick_size,
+            step_size,
+        }
+    }
+
+    /// `value` expressed in price ticks, if the tick size is known.
+    pub fn in_ticks(&self, value: f64) -> Option<f64> {
+        self.tick_size.filter(|tick| *tick > 0.0).map(|tick| value / tick)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SymbolError {
+    /// The exchange does not list the symbol.
+    Unknown(String),
+    /// The symbol is listed but not trading, e.g. `BREAK` or `HALT`.
+    NotTrading { symbol: String, status: String },
+    /// `exchangeInfo` could not be fetched or decoded.
+    Fetch(String),
+}
+
+impl fmt::Display for SymbolError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SymbolError::Unknown(symbol) => write!(f, "unknown symbol '{}'", symbol),
+            SymbolError::NotTrading { symbol, status } => {
+                write!(f, "symbol '{}' is not trading (status {})", symbol, status)
+            }
+            SymbolError::Fetch(detail) => write!(f, "failed to fetch exchangeInfo: {}", detail),
+        }
+    }
+}
+
+impl std::error::Error for SymbolError {}
+
+/// Checks that every symbol is listed and `TRADING`, returning metadata
+/// keyed by the symbol as given.
+pub fn validate_symbols(
+    info: &ExchangeInfo,
+    symbols: &[String],
+) -> Result<HashMap<String, SymbolMetadata>, SymbolError> {
+    let listed: HashMap<&str, &SymbolInfo> = info
+        .symbols
+        .iter()
+        .map(|symbol| (symbol.symbol.as_str(), symbol))
+        .collect();
+
+    let mut metadata = HashMap::new();
+    for symbol in symbols {
+        let info = listed
+            .get(symbol.to_uppercase().as_str())
+            .ok_or_else(|| SymbolError::Unknown(symbol.clone()))?;
+        if info.status != "TRADING" {
+            return Err(SymbolError::NotTrading {
+                symbol: symbol.clone(),
+                status: info.status.clone(),
+            });
+        }
+        metadata.insert(symbol.clone(), SymbolMetadata::new(symbol, info));
+    }
+    Ok(metadata)
+}
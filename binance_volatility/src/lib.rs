@@ -1,2 +1,5 @@
+pub mod book;
 pub mod client;
-pub mod math;
\ No newline at end of file
+pub mod math;
+pub mod venues;
+pub mod workers;
@@ -1,15 +1,24 @@
-mod client;
-mod math;
-
-use client::BinanceClient;
-use math::VolatilityCalculator;
+use binance_volatility::book::{BookPrice, BookSummary};
+use binance_volatility::client::kline::{KlineInterval, KlineMode};
+use binance_volatility::client::rest::{BINANCE_FUTURES_REST_URL, FUTURES_EXCHANGE_INFO_PATH};
+use binance_volatility::client::symbols::{validate_symbols, SymbolMetadata};
+use binance_volatility::client::{BinanceClient, MarkPriceEvent, QuoteEvent, StreamEvent, StreamKind, Subscription};
+use binance_volatility::math::{predicted_funding_rate, TimeWeightedAverage, VolatilityCalculator};
+use binance_volatility::venues::venue_source;
+use binance_volatility::workers::ShardedCalculators;
+use futures_util::StreamExt;
+use volatility_core::channel::{bounded, BackpressurePolicy, ChannelMetrics, EventReceiver, EventSender};
+use volatility_core::composite::{CompositeIndex, CompositeMethod, Exclusion};
+use volatility_core::event::{now_millis, MarketEvent};
+use volatility_core::source::PriceSource;
+use std::collections::HashMap;
+use std::time::UNIX_EPOCH;
 use std::sync::{Arc, Mutex};
 use std::sync::atomic::{AtomicBool, Ordering};
 use tokio::time::Duration;
-use tokio::sync::mpsc;
 use hyper::{Body, Request, Response, Server};
 use hyper::service::{make_service_fn, service_fn};
-use prometheus::{Encoder, TextEncoder, GaugeVec, Registry};
+use prometheus::{Encoder, TextEncoder, CounterVec, GaugeVec, Registry};
 use tracing::{info, debug, error};
 use tracing_subscriber::fmt;
 use tracing_appender::rolling;
@@ -17,9 +26,71 @@ use tracing_subscriber::util::SubscriberInitExt;
 use tracing_subscriber::prelude::__tracing_subscriber_SubscriberExt;
 
 
-const MAX_ROLLING_WINDOW_DURATION: u64 = u64::from_be(30);
+// Rolling window of every volatility calculator and average, in seconds:
+// thirty one-minute candles
+const MAX_ROLLING_WINDOW_DURATION: u64 = 30 * 60;
 const BINANCE_WS_URL: &str = "wss://stream.binance.com:9443/ws";
+const BINANCE_FUTURES_WS_URL: &str = "wss://fstream.binance.com/ws";
 const SYMBOL: &str = "ethusdc";
+// USDⓈ-M perpetuals streamed for mark price and funding, each paired with
+// the spot symbol its basis is measured against
+const PERPETUALS: &[(&str, &str)] = &[("ethusdc", SYMBOL)];
+// Spot stream whose live price the basis is measured against, opened for
+// every paired spot symbol
+const BASIS_SPOT_STREAM: StreamKind = StreamKind::BookTicker;
+// Binance spot trades feeding the composite index, as (symbol, spot symbol
+// whose composite index they feed). Trades rather than candle closes, which
+// would be stale most of the time
+const BINANCE_COMPOSITE: &[(&str, &str)] = &[("ETHUSDC", SYMBOL)];
+// Other venues streamed for the same asset, as (venue, venue-native symbol,
+// spot symbol whose composite index they feed)
+const VENUES: &[(&str, &str, &str)] = &[
+    ("coinbase", "ETH-USDC", SYMBOL),
+    ("kraken", "ETH/USDC", SYMBOL),
+    ("okx", "ETH-USDC", SYMBOL),
+    ("bybit", "ETHUSDC", SYMBOL),
+];
+// A venue without ticks for this long is reported down
+const VENUE_MAX_TICK_AGE_MS: u64 = 60_000;
+// How the composite index combines Binance spot and the other venues
+const COMPOSITE_METHOD: CompositeMethod = CompositeMethod::Median;
+// Venues older than this are left out of the composite
+const COMPOSITE_MAX_STALENESS_MS: u64 = 10_000;
+// Venues further than this fraction from the median are rejected as outliers
+const COMPOSITE_MAX_DEVIATION: f64 = 0.01;
+// No composite price is published with fewer contributing venues
+const COMPOSITE_MIN_VENUES: usize = 2;
+// How often symbols are re-validated against exchangeInfo
+const SYMBOL_REFRESH_SECS: u64 = 15 * 60;
+// Binance settles funding every 8 hours
+const FUNDING_INTERVAL_SECS: u64 = 8 * 60 * 60;
+// Spot symbols, the streams opened for each (trade streams give tick-level
+// prices), and whether their klines forward closed candles only or
+// deduplicated intra-candle updates. Each kline interval and trade stream of
+// a symbol is a volatility series of its own, labelled `interval`
+const SYMBOLS: &[(&str, &[StreamKind], KlineMode)] = &[
+    (SYMBOL, &[StreamKind::Kline(KlineInterval::OneMinute)], KlineMode::ClosedOnly),
+];
+// Seed kline windows from REST history on startup, covering the
+// calculators' window
+const BACKFILL: bool = true;
+const BACKFILL_WINDOW_SECS: u64 = MAX_ROLLING_WINDOW_DURATION;
+// Fetch candles or trades lost in a detected stream gap over REST
+const GAP_BACKFILL: bool = true;
+// Book price fed into the volatility calculation when `StreamKind::Depth` is streamed
+const BOOK_PRICE: Option<BookPrice> = None;
+// Events queued between the Binance streams and the calculation task, and
+// what happens when the queue is full
+const EVENT_CHANNEL_CAPACITY: usize = 10_000;
+const EVENT_CHANNEL_POLICY: BackpressurePolicy = BackpressurePolicy::Block;
+// Ticks queued for the composite index. Never conflated: the volume-weighted
+// method needs every trade's size, so a full queue drops its oldest ticks
+const COMPOSITE_CHANNEL_CAPACITY: usize = 1_000;
+const COMPOSITE_CHANNEL_POLICY: BackpressurePolicy = BackpressurePolicy::DropOldest;
+// Worker tasks the per-symbol volatility calculators are sharded over, and
+// the samples each worker queues before the dispatcher waits
+const CALC_WORKERS: usize = 4;
+const CALC_WORKER_QUEUE_CAPACITY: usize = 1_024;
 
 #[tokio::main]
 async fn main() {
@@ -40,16 +111,34 @@ async fn main() {
     eprintln!("Starting Binance Volatility Estimator. Press Ctrl+C to exit.");
 
     // // Initialize Prometheus metrics
-    let (volatility_gauge, registry) = init_metrics();
+    let (metrics, registry) = init_metrics();
+    let channel_metrics = ChannelMetrics::register(&registry).unwrap();
 
     // Channel for decoupling fetch and process
-    let (tx, rx) = mpsc::unbounded_channel();
+    let (tx, rx) = bounded(EVENT_CHANNEL_CAPACITY, EVENT_CHANNEL_POLICY);
+    // Binance spot and venue prices feeding the composite index
+    let (composite_tx, composite_rx) = bounded(COMPOSITE_CHANNEL_CAPACITY, COMPOSITE_CHANNEL_POLICY);
+    let channels = vec![("binance_events", rx.monitor()), ("composite", composite_rx.monitor())];
     println!("Channel for decoupling fetch and process created.");
+
+    // Perpetuals stream from the futures endpoints into the same channel
+    let futures_client = match BinanceClient::new(BINANCE_FUTURES_WS_URL, tx.clone()).await {
+        Ok(client) => client
+            .with_rest_url(BINANCE_FUTURES_REST_URL)
+            .with_exchange_info_path(FUTURES_EXCHANGE_INFO_PATH),
+        Err(e) => {
+            error!("Failed to create futures BinanceClient: {:?}", e);
+            return;
+        }
+    };
     
     // Create Infura client
     debug!("Creating BinanceClient...");
     let client = match BinanceClient::new(BINANCE_WS_URL, tx).await{
-        Ok(client) => client,
+        Ok(client) if BACKFILL => client
+            .with_backfill(BACKFILL_WINDOW_SECS)
+            .with_gap_backfill(GAP_BACKFILL),
+        Ok(client) => client.with_gap_backfill(GAP_BACKFILL),
         Err(e) => {
             error!("Failed to create BinanceClient: {:?}", e);
             return;
@@ -57,26 +146,78 @@ async fn main() {
     };
   
     // Symbols and shared state
-    let symbols: Vec<String> = vec![SYMBOL.to_string()];
-    let calculators = Arc::new(Mutex::new(
-        symbols.iter()
-            .map(|symbol| (symbol.clone(), VolatilityCalculator::new(MAX_ROLLING_WINDOW_DURATION)))
-            .collect(),
-    ));
+    let symbols: Vec<String> = SYMBOLS.iter().map(|(symbol, _, _)| symbol.to_string()).collect();
+    let perpetuals: Vec<String> = PERPETUALS.iter().map(|(perp, _)| perp.to_string()).collect();
+
+    // Refuse to stream symbols the exchange does not list as trading
+    let symbol_metadata = match client.validate_symbols(&symbols).await {
+        Ok(metadata) => Arc::new(Mutex::new(metadata)),
+        Err(e) => {
+            error!("Invalid spot symbol configuration: {}", e);
+            return;
+        }
+    };
+    if let Err(e) = futures_client.validate_symbols(&perpetuals).await {
+        error!("Invalid perpetual symbol configuration: {}", e);
+        return;
+    }
+    // Per-symbol calculators, sharded over worker tasks
+    let volatility_metrics = Arc::clone(&metrics);
+    let calculators = ShardedCalculators::spawn(
+        CALC_WORKERS,
+        MAX_ROLLING_WINDOW_DURATION,
+        CALC_WORKER_QUEUE_CAPACITY,
+        Arc::new(move |symbol: &str, interval: &str, volatility: Option<f64>| match volatility {
+            Some(volatility) => {
+                volatility_metrics.volatility_gauge
+                    .with_label_values(&[symbol, interval])
+                    .set(volatility);
+                info!("Volatility Gauge updated for {} {}: {:.6}", symbol, interval, volatility);
+            }
+            None => info!("{} {}: Not enough data for volatility calculation", symbol, interval),
+        }),
+    );
     let is_running = Arc::new(AtomicBool::new(true));
 
+    let mut subscriptions: Vec<Subscription> = SYMBOLS.iter()
+        .flat_map(|(symbol, streams, kline_mode)| {
+            streams.iter().map(move |kind| Subscription::new(symbol, *kind).with_kline_mode(*kline_mode))
+        })
+        .collect();
+    for (_, spot) in PERPETUALS {
+        if !subscriptions.iter().any(|s| s.symbol == *spot && s.kind == BASIS_SPOT_STREAM) {
+            subscriptions.push(Subscription::new(spot, BASIS_SPOT_STREAM));
+        }
+    }
+    let futures_subscriptions: Vec<Subscription> = perpetuals.iter()
+        .map(|perp| Subscription::new(perp, StreamKind::MarkPrice))
+        .collect();
+    let markets = vec![
+        ("spot", client.clone(), symbols.clone()),
+        ("futures", futures_client.clone(), perpetuals.clone()),
+    ];
+    let binance_source = client.clone();
+
     // Task 1: WebSocket stream 
     let websocket_task = tokio::spawn(start_websocket_task(
-        symbols.clone(),
-        client,
+        vec![(client, subscriptions), (futures_client, futures_subscriptions)],
         Arc::clone(&is_running),
     ));
 
     // Task 2: volatility calculation 
     let calc_task = tokio::spawn(start_volatility_calc_task(
         rx,
-        Arc::clone(&calculators),
-        Arc::clone(&volatility_gauge),
+        calculators,
+        Arc::clone(&metrics),
+        Arc::clone(&symbol_metadata),
+    ));
+
+    // Task 4: periodic symbol re-validation
+    let symbols_task = tokio::spawn(start_symbol_validation_task(
+        markets,
+        Arc::clone(&symbol_metadata),
+        Arc::clone(&metrics),
+        Arc::clone(&is_running),
     ));
 
 
@@ -86,77 +227,543 @@ async fn main() {
         Arc::clone(&is_running),
     ));
 
+    // Task 5: cross-venue price sources, Binance's own trades included
+    for (venue, symbol, asset) in VENUES {
+        let Some(source) = venue_source(venue) else {
+            error!("Unknown venue: {}", venue);
+            continue;
+        };
+        tokio::spawn(start_venue_task(venue, source, symbol, asset, Arc::clone(&metrics), composite_tx.clone()));
+    }
+    for (symbol, asset) in BINANCE_COMPOSITE {
+        let source = Box::new(binance_source.clone());
+        tokio::spawn(start_venue_task("binance", source, symbol, asset, Arc::clone(&metrics), composite_tx.clone()));
+    }
+    drop(composite_tx);
+
+    // Task 6: composite index across venues
+    let composite_task = tokio::spawn(start_composite_task(composite_rx, Arc::clone(&metrics)));
+
+    // Task 7: channel depth and drop metrics
+    let channels_task = tokio::spawn(channel_metrics.run(channels, Arc::clone(&is_running)));
+
     tokio::select! {
         _ = websocket_task => info!("WebSocket task exited."),
         _ = calc_task => info!("Calculation task exited."),
         _ = metrics_task => info!("Metrics task exited."),
+        _ = symbols_task => info!("Symbol validation task exited."),
+        _ = composite_task => info!("Composite index task exited."),
+        _ = channels_task => info!("Channel metrics task exited."),
         _ = handle_shutdown_signal(Arc::clone(&is_running)) => info!("Shutdown signal received."),
     }
     info!("All tasks completed or shutdown signal processed.");
 
 }
 
+// Prometheus metrics updated by the calculation task
+struct Metrics {
+    volatility_gauge: GaugeVec,
+    volume_counter: CounterVec,
+    mid_price_gauge: GaugeVec,
+    microprice_gauge: GaugeVec,
+    spread_gauge: GaugeVec,
+    book_depth_gauge: GaugeVec,
+    quoted_spread_gauge: GaugeVec,
+    quoted_spread_bps_gauge: GaugeVec,
+    twa_spread_bps_gauge: GaugeVec,
+    quote_mid_volatility_gauge: GaugeVec,
+    message_errors_counter: CounterVec,
+    stream_gaps_counter: CounterVec,
+    mark_price_gauge: GaugeVec,
+    index_price_gauge: GaugeVec,
+    mark_volatility_gauge: GaugeVec,
+    index_volatility_gauge: GaugeVec,
+    funding_rate_gauge: GaugeVec,
+    predicted_funding_gauge: GaugeVec,
+    basis_bps_gauge: GaugeVec,
+    basis_volatility_gauge: GaugeVec,
+    quoted_spread_ticks_gauge: GaugeVec,
+    book_spread_ticks_gauge: GaugeVec,
+    symbol_trading_gauge: GaugeVec,
+    symbol_increment_gauge: GaugeVec,
+    venue_price_gauge: GaugeVec,
+    venue_volatility_gauge: GaugeVec,
+    venue_up_gauge: GaugeVec,
+    composite_price_gauge: GaugeVec,
+    composite_volatility_gauge: GaugeVec,
+    composite_venues_gauge: GaugeVec,
+    composite_excluded_gauge: GaugeVec,
+}
+
+// Rolling state kept per symbol for `@bookTicker` quotes
+struct QuoteState {
+    mid_volatility: VolatilityCalculator,
+    spread_bps: TimeWeightedAverage,
+}
+
+impl QuoteState {
+    fn new() -> Self {
+        Self {
+            mid_volatility: VolatilityCalculator::new(MAX_ROLLING_WINDOW_DURATION),
+            spread_bps: TimeWeightedAverage::new(MAX_ROLLING_WINDOW_DURATION),
+        }
+    }
+}
+
+// Rolling state kept per perpetual for `@markPrice` updates
+struct PerpState {
+    mark_volatility: VolatilityCalculator,
+    index_volatility: VolatilityCalculator,
+    basis_volatility: VolatilityCalculator,
+    premium: TimeWeightedAverage, // Over one funding interval
+}
+
+impl PerpState {
+    fn new() -> Self {
+        Self {
+            mark_volatility: VolatilityCalculator::new(MAX_ROLLING_WINDOW_DURATION),
+            index_volatility: VolatilityCalculator::new(MAX_ROLLING_WINDOW_DURATION),
+            basis_volatility: VolatilityCalculator::new(MAX_ROLLING_WINDOW_DURATION),
+            premium: TimeWeightedAverage::new(FUNDING_INTERVAL_SECS),
+        }
+    }
+}
+
+impl Metrics {
+    fn record_mark_price(&self, mark: &MarkPriceEvent, state: &mut PerpState, spot_price: Option<f64>) {
+        let symbol = mark.symbol.as_str();
+        let timestamp = UNIX_EPOCH + Duration::from_millis(mark.event_time);
+        self.mark_price_gauge.with_label_values(&[symbol]).set(mark.mark_price);
+        self.index_price_gauge.with_label_values(&[symbol]).set(mark.index_price);
+        self.funding_rate_gauge.with_label_values(&[symbol]).set(mark.funding_rate);
+
+        state.premium.add_value_at(timestamp, mark.premium());
+        if let Some(premium) = state.premium.average_at(timestamp) {
+            self.predicted_funding_gauge.with_label_values(&[symbol]).set(predicted_funding_rate(premium));
+        }
+
+        state.mark_volatility.add_value_at(timestamp, mark.mark_price);
+        if let Some(volatility) = state.mark_volatility.calculate_volatility() {
+            self.mark_volatility_gauge.with_label_values(&[symbol]).set(volatility);
+        }
+        state.index_volatility.add_value_at(timestamp, mark.index_price);
+        if let Some(volatility) = state.index_volatility.calculate_volatility() {
+            self.index_volatility_gauge.with_label_values(&[symbol]).set(volatility);
+        }
+
+        // Basis needs a spot price for the paired symbol
+        let Some(spot_price) = spot_price else { return };
+        let basis_bps = mark.basis_bps(spot_price);
+        self.basis_bps_gauge.with_label_values(&[symbol]).set(basis_bps);
+        state.basis_volatility.add_value_at(timestamp, basis_bps);
+        if let Some(volatility) = state.basis_volatility.calculate_volatility() {
+            self.basis_volatility_gauge.with_label_values(&[symbol]).set(volatility);
+        }
+    }
+
+    fn record_quote(&self, quote: &QuoteEvent, state: &mut QuoteState, metadata: Option<&SymbolMetadata>) {
+        let symbol = quote.symbol.as_str();
+        self.quoted_spread_gauge.with_label_values(&[symbol]).set(quote.spread());
+        if let Some(ticks) = metadata.and_then(|m| m.in_ticks(quote.spread())) {
+            self.quoted_spread_ticks_gauge.with_label_values(&[symbol]).set(ticks);
+        }
+        self.quoted_spread_bps_gauge.with_label_values(&[symbol]).set(quote.spread_bps());
+
+        state.spread_bps.add_value(quote.spread_bps());
+        if let Some(average) = state.spread_bps.average() {
+            self.twa_spread_bps_gauge.with_label_values(&[symbol]).set(average);
+        }
+
+        state.mid_volatility.add_value(quote.mid_price());
+        if let Some(volatility) = state.mid_volatility.calculate_volatility() {
+            self.quote_mid_volatility_gauge.with_label_values(&[symbol]).set(volatility);
+        }
+    }
+
+    fn record_book(&self, book: &BookSummary, metadata: Option<&SymbolMetadata>) {
+        let symbol = book.symbol.as_str();
+        if let Some(ticks) = metadata.and_then(|m| m.in_ticks(book.spread)) {
+            self.book_spread_ticks_gauge.with_label_values(&[symbol]).set(ticks);
+        }
+        self.mid_price_gauge.with_label_values(&[symbol]).set(book.mid_price);
+        self.microprice_gauge.with_label_values(&[symbol]).set(book.microprice);
+        self.spread_gauge.with_label_values(&[symbol]).set(book.spread);
+        self.book_depth_gauge.with_label_values(&[symbol, "bid"]).set(book.bid_depth);
+        self.book_depth_gauge.with_label_values(&[symbol, "ask"]).set(book.ask_depth);
+    }
+
+    fn record_symbol(&self, market: &str, metadata: &SymbolMetadata) {
+        let symbol = metadata.symbol.as_str();
+        self.symbol_trading_gauge
+            .with_label_values(&[symbol, market, &metadata.base_asset, &metadata.quote_asset])
+            .set(1.0);
+        if let Some(tick_size) = metadata.tick_size {
+            self.symbol_increment_gauge.with_label_values(&[symbol, market, "tick"]).set(tick_size);
+        }
+        if let Some(step_size) = metadata.step_size {
+            self.symbol_increment_gauge.with_label_values(&[symbol, market, "step"]).set(step_size);
+        }
+    }
+}
+
+fn register_gauge(registry: &Registry, name: &str, help: &str, labels: &[&str]) -> GaugeVec {
+    let gauge = GaugeVec::new(prometheus::Opts::new(name, help), labels).unwrap();
+    registry.register(Box::new(gauge.clone())).unwrap();
+    gauge
+}
+
 // Initialize Prometheus metrics
-fn init_metrics() -> (Arc<GaugeVec>, Arc<Registry>) {
+fn init_metrics() -> (Arc<Metrics>, Arc<Registry>) {
     // Create a new Prometheus registry
     let registry = Arc::new(Registry::new());
 
     // Create a new GaugeVec for volatility metrics
     let volatility_gauge = GaugeVec::new(
-        prometheus::Opts::new("binance_volatility", "Volatility metrics for Binance symbols"),
-        &["symbol"],
+        prometheus::Opts::new("binance_volatility", "Volatility metrics for Binance symbols, per kline interval or tick stream"),
+        &["symbol", "interval"],
     ).unwrap();
 
     // Register the GaugeVec with the registry
     registry.register(Box::new(volatility_gauge.clone())).unwrap();
     info!("Volatility gauge registered successfully with Prometheus.");
 
-    // Wrap the GaugeVec in Arc for shared ownership and return
-    (Arc::new(volatility_gauge), registry)
+    // Traded base-asset volume from trade streams, split by aggressor side
+    let volume_counter = CounterVec::new(
+        prometheus::Opts::new("binance_traded_volume", "Traded volume for Binance symbols by aggressor side"),
+        &["symbol", "side"],
+    ).unwrap();
+    registry.register(Box::new(volume_counter.clone())).unwrap();
+
+    // Order book metrics from depth streams
+    let mid_price_gauge = register_gauge(&registry, "binance_mid_price", "Order book mid-price", &["symbol"]);
+    let microprice_gauge = register_gauge(&registry, "binance_microprice", "Order book size-weighted microprice", &["symbol"]);
+    let spread_gauge = register_gauge(&registry, "binance_book_spread", "Order book best ask minus best bid", &["symbol"]);
+    let book_depth_gauge = register_gauge(&registry, "binance_book_depth", "Quantity over the top N book levels", &["symbol", "side"]);
+
+    // Quote metrics from bookTicker streams
+    let quoted_spread_gauge = register_gauge(&registry, "binance_quoted_spread", "Best ask minus best bid from bookTicker", &["symbol"]);
+    let quoted_spread_bps_gauge = register_gauge(&registry, "binance_quoted_spread_bps", "Quoted spread in basis points of mid", &["symbol"]);
+    let twa_spread_bps_gauge = register_gauge(&registry, "binance_time_weighted_spread_bps", "Time-weighted average quoted spread over the window", &["symbol"]);
+    let quote_mid_volatility_gauge = register_gauge(&registry, "binance_quote_mid_volatility", "Volatility of the bookTicker mid-price", &["symbol"]);
+
+    // Stream messages that failed to decode, by error kind
+    let message_errors_counter = CounterVec::new(
+        prometheus::Opts::new("binance_message_errors_total", "Binance stream messages that could not be decoded"),
+        &["symbol", "kind"],
+    ).unwrap();
+    registry.register(Box::new(message_errors_counter.clone())).unwrap();
+
+    // Gaps detected in stream continuity, by stream
+    let stream_gaps_counter = CounterVec::new(
+        prometheus::Opts::new("stream_gaps_total", "Gaps detected in Binance stream sequences"),
+        &["symbol", "stream"],
+    ).unwrap();
+    registry.register(Box::new(stream_gaps_counter.clone())).unwrap();
+
+    // Perpetual futures metrics from markPrice streams
+    let mark_price_gauge = register_gauge(&registry, "binance_mark_price", "Perpetual mark price", &["symbol"]);
+    let index_price_gauge = register_gauge(&registry, "binance_index_price", "Perpetual index price", &["symbol"]);
+    let mark_volatility_gauge = register_gauge(&registry, "binance_mark_price_volatility", "Volatility of the perpetual mark price", &["symbol"]);
+    let index_volatility_gauge = register_gauge(&registry, "binance_index_price_volatility", "Volatility of the perpetual index price", &["symbol"]);
+    let funding_rate_gauge = register_gauge(&registry, "binance_funding_rate", "Funding rate applied at the next funding time", &["symbol"]);
+    let predicted_funding_gauge = register_gauge(&registry, "binance_predicted_funding_rate", "Funding rate predicted from the average mark premium", &["symbol"]);
+    let basis_bps_gauge = register_gauge(&registry, "binance_basis_bps", "Perpetual mark price minus spot price in basis points of spot", &["symbol"]);
+    let basis_volatility_gauge = register_gauge(&registry, "binance_basis_volatility", "Volatility of the perp-spot basis in basis points", &["symbol"]);
+
+    // Symbol metadata from exchangeInfo
+    let quoted_spread_ticks_gauge = register_gauge(&registry, "binance_quoted_spread_ticks", "Quoted spread in price ticks", &["symbol"]);
+    let book_spread_ticks_gauge = register_gauge(&registry, "binance_book_spread_ticks", "Order book spread in price ticks", &["symbol"]);
+    let symbol_trading_gauge = register_gauge(&registry, "binance_symbol_trading", "1 while the symbol is listed and TRADING, else 0", &["symbol", "market", "base_asset", "quote_asset"]);
+    let symbol_increment_gauge = register_gauge(&registry, "binance_symbol_increment", "Tick size and lot step size", &["symbol", "market", "kind"]);
+
+    // Other venues' trade prices
+    let venue_price_gauge = register_gauge(&registry, "venue_price", "Last trade price per venue", &["venue", "symbol"]);
+    let venue_volatility_gauge = register_gauge(&registry, "venue_volatility", "Volatility of trade prices per venue", &["venue", "symbol"]);
+    let venue_up_gauge = register_gauge(&registry, "venue_up", "1 while the venue is connected and ticking, else 0", &["venue"]);
+
+    // Composite index across venues
+    let composite_price_gauge = register_gauge(&registry, "composite_index_price", "Composite price across venues", &["symbol"]);
+    let composite_volatility_gauge = register_gauge(&registry, "composite_index_volatility", "Volatility of the composite price", &["symbol"]);
+    let composite_venues_gauge = register_gauge(&registry, "composite_index_venues", "Venues contributing to the composite price", &["symbol"]);
+    let composite_excluded_gauge = register_gauge(&registry, "composite_venue_excluded", "1 while the venue is excluded from the composite for the reason, else 0", &["symbol", "venue", "reason"]);
+
+    // Wrap the metrics in Arc for shared ownership and return
+    let metrics = Metrics {
+        volatility_gauge,
+        volume_counter,
+        mid_price_gauge,
+        microprice_gauge,
+        spread_gauge,
+        book_depth_gauge,
+        quoted_spread_gauge,
+        quoted_spread_bps_gauge,
+        twa_spread_bps_gauge,
+        quote_mid_volatility_gauge,
+        message_errors_counter,
+        stream_gaps_counter,
+        mark_price_gauge,
+        index_price_gauge,
+        mark_volatility_gauge,
+        index_volatility_gauge,
+        funding_rate_gauge,
+        predicted_funding_gauge,
+        basis_bps_gauge,
+        basis_volatility_gauge,
+        quoted_spread_ticks_gauge,
+        book_spread_ticks_gauge,
+        symbol_trading_gauge,
+        symbol_increment_gauge,
+        venue_price_gauge,
+        venue_volatility_gauge,
+        venue_up_gauge,
+        composite_price_gauge,
+        composite_volatility_gauge,
+        composite_venues_gauge,
+        composite_excluded_gauge,
+    };
+    (Arc::new(metrics), registry)
 }
 
 // WebSocket stream task
 async fn start_websocket_task(
-    symbols: Vec<String>,
-    client: BinanceClient,
+    clients: Vec<(BinanceClient, Vec<Subscription>)>,
     is_running: Arc<AtomicBool>,
 ) {
+    // Each stream reconnects on its own; keep the task alive until shutdown
+    for (client, subscriptions) in &clients {
+        client.start_streams(subscriptions.clone()).await;
+    }
     while is_running.load(Ordering::SeqCst) {
-        client.start_multi_symbol_stream(symbols.clone()).await;
-        tokio::time::sleep(Duration::from_secs(1)).await; // Retry on failure
+        tokio::time::sleep(Duration::from_secs(1)).await;
     }
 }
 
 
 // Volatility calculation task
 async fn start_volatility_calc_task(
-    mut receiver: mpsc::UnboundedReceiver<(String, f64)>,
-    calculators: Arc<Mutex<Vec<(String, VolatilityCalculator)>>>,
-    volatility_gauge: Arc<GaugeVec>,
+    mut receiver: EventReceiver<StreamEvent>,
+    mut calculators: ShardedCalculators,
+    metrics: Arc<Metrics>,
+    symbol_metadata: Arc<Mutex<HashMap<String, SymbolMetadata>>>,
 ) {
-    while let Some((symbol, price)) = receiver.recv().await {
-        let mut calculators_lock = match calculators.lock() {
-            Ok(lock) => lock,
-            Err(e) => {
-                error!("Failed to acquire lock: {}", e);
+    let mut quote_states: HashMap<String, QuoteState> = HashMap::new();
+    let mut perp_states: HashMap<String, PerpState> = HashMap::new();
+    // Latest live spot price per symbol (trade or quote mid, never a candle
+    // close), for the perp-spot basis
+    let mut spot_prices: HashMap<String, f64> = HashMap::new();
+
+    // Nothing here allocates per message: symbols are borrowed from the
+    // event, and calculators are addressed by the `SymbolId` interned for
+    // the symbol and its series
+    while let Some(event) = receiver.recv().await {
+        let price = match &event {
+            StreamEvent::Trade(trade) => {
+                // Buyer is maker => seller was the aggressor
+                let side = if trade.is_buyer_maker { "sell" } else { "buy" };
+                metrics.volume_counter
+                    .with_label_values(&[&trade.symbol, side])
+                    .inc_by(trade.quantity);
+                *entry_for(&mut spot_prices, &trade.symbol, || trade.price) = trade.price;
+                trade.price
+            }
+            StreamEvent::Book(book) => {
+                let metadata = symbol_metadata.lock().ok();
+                metrics.record_book(book, metadata.as_ref().and_then(|m| m.get(&book.symbol)));
+                *entry_for(&mut spot_prices, &book.symbol, || book.mid_price) = book.mid_price;
+                match BOOK_PRICE {
+                    Some(kind) => book.price(kind),
+                    None => continue,
+                }
+            }
+            StreamEvent::Quote(quote) => {
+                let mid_price = quote.mid_price();
+                *entry_for(&mut spot_prices, &quote.symbol, || mid_price) = mid_price;
+                let state = entry_for(&mut quote_states, &quote.symbol, QuoteState::new);
+                let metadata = symbol_metadata.lock().ok();
+                metrics.record_quote(quote, state, metadata.as_ref().and_then(|m| m.get(&quote.symbol)));
+                continue;
+            }
+            StreamEvent::MarkPrice(mark) => {
+                let spot_price = PERPETUALS.iter()
+                    .find(|(perp, _)| *perp == mark.symbol)
+                    .and_then(|(_, spot)| spot_prices.get(*spot).copied());
+                let state = entry_for(&mut perp_states, &mark.symbol, PerpState::new);
+                metrics.record_mark_price(mark, state, spot_price);
+                continue;
+            }
+            StreamEvent::Gap(gap) => {
+                metrics.stream_gaps_counter
+                    .with_label_values(&[&gap.symbol, gap.stream])
+                    .inc();
                 continue;
             }
+            StreamEvent::Error { symbol, error } => {
+                metrics.message_errors_counter
+                    .with_label_values(&[symbol, error.kind()])
+                    .inc();
+                continue;
+            }
+            StreamEvent::Kline(kline) => kline.close,
         };
+        // Each kline interval and tick stream of a symbol has its own window
+        let id = calculators.intern(event.symbol(), event.series().unwrap_or_default()).await;
+        // Windows run on exchange time so backfilled history lands in place
+        let timestamp = UNIX_EPOCH + Duration::from_millis(event.event_time().unwrap_or_else(now_millis));
 
-        if let Some((_, calculator)) = calculators_lock.iter_mut().find(|(s, _)| s == &symbol) {
-            calculator.add_value(price);
-            if let Some(volatility) = calculator.calculate_volatility() {
-                volatility_gauge
-                    .with_label_values(&[&symbol])
-                    .set(volatility);
-                info!("Volatility Gauge updated for {}: {:.6}", symbol, volatility);
-            } else {
-                info!("{}: Not enough data for volatility calculation", symbol);
+        // The symbol's worker updates the volatility gauge
+        calculators.add(id, timestamp, price).await;
+    }
+
+    calculators.shutdown().await;
+    info!("Volatility calculation task exiting.");
+}
+
+// The symbol's entry, allocating its key only the first time it is seen
+fn entry_for<'a, V>(map: &'a mut HashMap<String, V>, symbol: &str, new: impl FnOnce() -> V) -> &'a mut V {
+    if !map.contains_key(symbol) {
+        map.insert(symbol.to_string(), new());
+    }
+    map.get_mut(symbol).expect("inserted above")
+}
+
+// Symbol validation task: re-checks exchangeInfo so delistings and trading
+// halts show up while running
+async fn start_symbol_validation_task(
+    markets: Vec<(&'static str, BinanceClient, Vec<String>)>,
+    symbol_metadata: Arc<Mutex<HashMap<String, SymbolMetadata>>>,
+    metrics: Arc<Metrics>,
+    is_running: Arc<AtomicBool>,
+) {
+    // Assets of each symbol as last validated, to zero the right series
+    let mut last_seen: HashMap<(&str, String), SymbolMetadata> = HashMap::new();
+
+    while is_running.load(Ordering::SeqCst) {
+        for (market, client, symbols) in &markets {
+            let exchange_info = match client.exchange_info().await {
+                Ok(info) => info,
+                Err(e) => {
+                    error!("Symbol validation for {} skipped: {}", market, e);
+                    continue;
+                }
+            };
+
+            for symbol in symbols {
+                match validate_symbols(&exchange_info, std::slice::from_ref(symbol)) {
+                    Ok(mut validated) => {
+                        let Some(metadata) = validated.remove(symbol) else { continue };
+                        metrics.record_symbol(market, &metadata);
+                        if *market == "spot" {
+                            if let Ok(mut lock) = symbol_metadata.lock() {
+                                lock.insert(symbol.clone(), metadata.clone());
+                            }
+                        }
+                        last_seen.insert((*market, symbol.clone()), metadata);
+                    }
+                    Err(e) => {
+                        error!("Symbol validation failed for {} {}: {}", market, symbol, e);
+                        if let Some(metadata) = last_seen.get(&(*market, symbol.clone())) {
+                            metrics.symbol_trading_gauge
+                                .with_label_values(&[symbol, market, &metadata.base_asset, &metadata.quote_asset])
+                                .set(0.0);
+                        }
+                    }
+                }
             }
         }
+        tokio::time::sleep(Duration::from_secs(SYMBOL_REFRESH_SECS)).await;
     }
+}
 
-    info!("Volatility calculation task exiting.");
+// Venue task: streams one symbol from a venue into its own volatility
+// window and the composite index for `asset`
+async fn start_venue_task(
+    venue: &'static str,
+    mut source: Box<dyn PriceSource>,
+    symbol: &'static str,
+    asset: &'static str,
+    metrics: Arc<Metrics>,
+    composite: EventSender<MarketEvent>,
+) {
+    let mut retry_attempts = 0;
+    while let Err(e) = source.connect().await {
+        retry_attempts += 1;
+        error!("Failed to connect to {} (attempt {}): {}", venue, retry_attempts, e);
+        tokio::time::sleep(Duration::from_secs(retry_attempts.min(10))).await;
+    }
+    if let Err(e) = source.subscribe(&[symbol.to_string()]).await {
+        error!("Failed to subscribe to {} on {}: {}", symbol, venue, e);
+        return;
+    }
+    let mut ticks = match source.ticks() {
+        Ok(ticks) => ticks,
+        Err(e) => {
+            error!("No tick stream from {}: {}", venue, e);
+            return;
+        }
+    };
+
+    let mut calculator = VolatilityCalculator::new(MAX_ROLLING_WINDOW_DURATION);
+    let mut health_check = tokio::time::interval(Duration::from_secs(10));
+    loop {
+        tokio::select! {
+            tick = ticks.next() => {
+                let Some(tick) = tick else { break };
+                metrics.venue_price_gauge.with_label_values(&[venue, &tick.instrument]).set(tick.price);
+                calculator.add_value_at(UNIX_EPOCH + Duration::from_millis(tick.timestamp()), tick.price);
+                if let Some(volatility) = calculator.calculate_volatility() {
+                    metrics.venue_volatility_gauge.with_label_values(&[venue, &tick.instrument]).set(volatility);
+                }
+                let _ = composite.send(MarketEvent { instrument: asset.to_string(), ..tick }).await;
+            }
+            _ = health_check.tick() => {
+                let now_ms = now_millis();
+                let up = source.health().is_live(now_ms, VENUE_MAX_TICK_AGE_MS);
+                metrics.venue_up_gauge.with_label_values(&[venue]).set(if up { 1.0 } else { 0.0 });
+            }
+        }
+    }
+    info!("Tick stream from {} ended.", venue);
+}
+
+// Composite index task: combines the latest price from every venue per
+// asset and tracks the volatility of the result
+async fn start_composite_task(mut receiver: EventReceiver<MarketEvent>, metrics: Arc<Metrics>) {
+    let mut indices: HashMap<String, (CompositeIndex, VolatilityCalculator)> = HashMap::new();
+
+    while let Some(event) = receiver.recv().await {
+        let (index, calculator) = indices.entry(event.instrument.clone()).or_insert_with(|| {
+            let index = CompositeIndex::new(COMPOSITE_METHOD)
+                .with_max_staleness_ms(COMPOSITE_MAX_STALENESS_MS)
+                .with_max_deviation(COMPOSITE_MAX_DEVIATION)
+                .with_min_venues(COMPOSITE_MIN_VENUES);
+            (index, VolatilityCalculator::new(MAX_ROLLING_WINDOW_DURATION))
+        });
+        index.update(&event);
+
+        let now_ms = now_millis();
+        let Some(composite) = index.price(now_ms) else {
+            metrics.composite_venues_gauge.with_label_values(&[&event.instrument]).set(0.0);
+            continue;
+        };
+        metrics.composite_price_gauge.with_label_values(&[&event.instrument]).set(composite.price);
+        metrics.composite_venues_gauge.with_label_values(&[&event.instrument]).set(composite.venues.len() as f64);
+        for reason in [Exclusion::Stale, Exclusion::Outlier] {
+            for venue in &composite.venues {
+                metrics.composite_excluded_gauge.with_label_values(&[&event.instrument, venue, reason.as_str()]).set(0.0);
+            }
+            for (venue, excluded) in &composite.excluded {
+                let value = if *excluded == reason { 1.0 } else { 0.0 };
+                metrics.composite_excluded_gauge.with_label_values(&[&event.instrument, venue, reason.as_str()]).set(value);
+            }
+        }
+
+        calculator.add_value_at(UNIX_EPOCH + Duration::from_millis(composite.timestamp), composite.price);
+        if let Some(volatility) = calculator.calculate_volatility() {
+            metrics.composite_volatility_gauge.with_label_values(&[&event.instrument]).set(volatility);
+        }
+    }
+
+    info!("Composite index task exiting.");
 }
 
 // Prometheus metrics server task
@@ -1,5 +1,5 @@
 use std::collections::VecDeque;
-use std::time::{Duration, SystemTime};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use tracing::warn;
 
 pub struct VolatilityCalculator {
@@ -18,9 +18,13 @@ impl VolatilityCalculator {
 
     /// Add a new price value to the rolling window.
     pub fn add_value(&mut self, price: f64) {
-        let now = SystemTime::now();
+        self.add_value_at(SystemTime::now(), price);
+    }
 
-        // Add the new price with the current timestamp
+    /// Add a price observed at `now`, e.g. the event time of a backfilled candle.
+    /// Values must be added in time order.
+    pub fn add_value_at(&mut self, now: SystemTime, price: f64) {
+        // Add the new price with its timestamp
         self.window.push_back((now, price));
 
         // Remove prices outside the rolling window
@@ -52,3 +56,81 @@ impl VolatilityCalculator {
     }
 }
 
+
+/// Time-weighted average of a step series (e.g. the quoted spread) over a
+/// rolling window. Each value is weighted by how long it was in force.
+pub struct TimeWeightedAverage {
+    window: VecDeque<(SystemTime, f64)>, // Stores (timestamp, value)
+    max_duration: Duration,
+}
+
+impl TimeWeightedAverage {
+    /// Create a new time-weighted average with a rolling window duration in seconds.
+    pub fn new(max_duration_secs: u64) -> Self {
+        TimeWeightedAverage {
+            window: VecDeque::new(),
+            max_duration: Duration::from_secs(max_duration_secs),
+        }
+    }
+
+    /// Add a new value observed now.
+    pub fn add_value(&mut self, value: f64) {
+        self.add_value_at(SystemTime::now(), value);
+    }
+
+    /// Add a new value observed at `time`.
+    pub fn add_value_at(&mut self, time: SystemTime, value: f64) {
+        self.window.push_back((time, value));
+
+        // Keep the last value that started before the window: it is still in
+        // force at the window start
+        while self.window.len() > 1 {
+            let next_start = self.window[1].0;
+            match time.duration_since(next_start) {
+                Ok(age) if age > self.max_duration => {
+                    self.window.pop_front();
+                }
+                _ => break,
+            }
+        }
+    }
+
+    /// Average over the window ending at `now`.
+    pub fn average_at(&self, now: SystemTime) -> Option<f64> {
+        // A window reaching back past the epoch covers every value
+        let window_start = now.checked_sub(self.max_duration).unwrap_or(UNIX_EPOCH);
+        let mut weighted_sum = 0.0;
+        let mut total = 0.0;
+
+        for (i, (start, value)) in self.window.iter().enumerate() {
+            let end = self.window.get(i + 1).map(|(t, _)| *t).unwrap_or(now);
+            let start = (*start).max(window_start);
+            if let Ok(held) = end.duration_since(start) {
+                weighted_sum += value * held.as_secs_f64();
+                total += held.as_secs_f64();
+            }
+        }
+
+        if total > 0.0 {
+            Some(weighted_sum / total)
+        } else {
+            self.window.back().map(|(_, value)| *value)
+        }
+    }
+
+    /// Average over the window ending now.
+    pub fn average(&self) -> Option<f64> {
+        self.average_at(SystemTime::now())
+    }
+}
+
+// Binance's default interest rate per 8h funding interval, and the band the
+// interest-minus-premium term is clamped to
+const FUNDING_INTEREST_RATE: f64 = 0.0001;
+const FUNDING_CLAMP: f64 = 0.0005;
+
+/// Predicted funding rate from the average premium over the funding
+/// interval, using Binance's formula `P + clamp(I - P, -0.05%, 0.05%)`.
+pub fn predicted_funding_rate(average_premium: f64) -> f64 {
+    average_premium + (FUNDING_INTEREST_RATE - average_premium).clamp(-FUNDING_CLAMP, FUNDING_CLAMP)
+}
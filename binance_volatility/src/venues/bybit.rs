@@ -0,0 +1,73 @@
+use serde::Deserialize;
+use serde_json::json;
+use volatility_core::event::MarketEvent;
+
+use super::VenueProtocol;
+use crate::client::models::de_decimal;
+
+/// Bybit v5 spot `publicTrade` topic.
+pub struct Bybit;
+
+// Bybit spot accepts at most 10 topics per subscribe request
+const MAX_TOPICS_PER_REQUEST: usize = 10;
+
+#[derive(Deserialize)]
+struct Message {
+    topic: Option<String>,
+    success: Option<bool>,
+    ret_msg: Option<String>,
+    #[serde(default)]
+    data: Vec<Trade>,
+}
+
+#[derive(Deserialize)]
+struct Trade {
+    #[serde(rename = "T")]
+    time: u64,
+    #[serde(rename = "s")]
+    symbol: String,
+    #[serde(rename = "p", deserialize_with = "de_decimal")]
+    price: f64,
+    #[serde(rename = "v", deserialize_with = "de_decimal")]
+    volume: f64,
+}
+
+impl VenueProtocol for Bybit {
+    fn venue(&self) -> &'static str {
+        "bybit"
+    }
+
+    fn url(&self) -> &'static str {
+        "wss://stream.bybit.com/v5/public/spot"
+    }
+
+    fn subscribe_messages(&self, symbols: &[String]) -> Vec<String> {
+        symbols
+            .chunks(MAX_TOPICS_PER_REQUEST)
+            .map(|chunk| {
+                let topics: Vec<String> = chunk.iter().map(|symbol| format!("publicTrade.{}", symbol)).collect();
+                json!({"op": "subscribe", "args": topics}).to_string()
+            })
+            .collect()
+    }
+
+    fn parse(&self, text: &str) -> Result<Vec<MarketEvent>, String> {
+        let message: Message = serde_json::from_str(text).map_err(|e| e.to_string())?;
+        if message.success == Some(false) {
+            return Err(message.ret_msg.unwrap_or_else(|| text.to_string()));
+        }
+        if !message.topic.is_some_and(|topic| topic.starts_with("publicTrade.")) {
+            return Ok(Vec::new());
+        }
+
+        Ok(message
+            .data
+            .into_iter()
+            .map(|trade| MarketEvent::trade(self.venue(), &trade.symbol, trade.price, Some(trade.volume), Some(trade.time)))
+            .collect())
+    }
+
+    fn ping_message(&self) -> Option<String> {
+        Some(json!({"op": "ping"}).to_string())
+    }
+}
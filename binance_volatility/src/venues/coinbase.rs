@@ -0,0 +1,85 @@
+use serde::Deserialize;
+use serde_json::json;
+use volatility_core::event::MarketEvent;
+
+use super::{rfc3339_millis, VenueProtocol};
+use crate::client::models::de_decimal;
+
+/// Coinbase Advanced Trade `market_trades` channel.
+pub struct Coinbase;
+
+// Events are only decoded for `market_trades`; heartbeats and subscription
+// acknowledgements carry events of other shapes
+#[derive(Deserialize)]
+struct Envelope {
+    channel: String,
+    #[serde(default)]
+    events: serde_json::Value,
+}
+
+#[derive(Deserialize)]
+struct TradesEvent {
+    #[serde(rename = "type")]
+    kind: String,
+    #[serde(default)]
+    trades: Vec<Trade>,
+}
+
+#[derive(Deserialize)]
+struct Trade {
+    product_id: String,
+    #[serde(deserialize_with = "de_decimal")]
+    price: f64,
+    #[serde(deserialize_with = "de_decimal")]
+    size: f64,
+    time: String,
+}
+
+#[derive(Deserialize)]
+struct ErrorMessage {
+    #[serde(rename = "type")]
+    kind: String,
+    message: String,
+}
+
+impl VenueProtocol for Coinbase {
+    fn venue(&self) -> &'static str {
+        "coinbase"
+    }
+
+    fn url(&self) -> &'static str {
+        "wss://advanced-trade-ws.coinbase.com"
+    }
+
+    fn subscribe_messages(&self, symbols: &[String]) -> Vec<String> {
+        vec![
+            json!({"type": "subscribe", "product_ids": symbols, "channel": "market_trades"}).to_string(),
+            // Without heartbeats quiet products get disconnected
+            json!({"type": "subscribe", "channel": "heartbeats"}).to_string(),
+        ]
+    }
+
+    fn parse(&self, text: &str) -> Result<Vec<MarketEvent>, String> {
+        if let Ok(error) = serde_json::from_str::<ErrorMessage>(text) {
+            if error.kind == "error" {
+                return Err(error.message);
+            }
+        }
+        let envelope: Envelope = serde_json::from_str(text).map_err(|e| e.to_string())?;
+        if envelope.channel != "market_trades" {
+            return Ok(Vec::new());
+        }
+
+        let events: Vec<TradesEvent> = serde_json::from_value(envelope.events).map_err(|e| e.to_string())?;
+
+        let mut ticks = Vec::new();
+        // The snapshot replays recent history; only updates are live
+        for event in events.into_iter().filter(|event| event.kind == "update") {
+            for trade in event.trades {
+                let time = rfc3339_millis(&trade.time)?;
+                ticks.push(MarketEvent::trade(self.venue(), &trade.product_id, trade.price, Some(trade.size), Some(time)));
+            }
+        }
+        Ok(ticks)
+    }
+}
@@ -0,0 +1,71 @@
+use serde::Deserialize;
+use serde_json::json;
+use volatility_core::event::MarketEvent;
+
+use super::{rfc3339_millis, VenueProtocol};
+use crate::client::models::de_decimal;
+
+/// Kraken WebSocket v2 `trade` channel.
+pub struct Kraken;
+
+#[derive(Deserialize)]
+struct Message {
+    channel: Option<String>,
+    #[serde(rename = "type")]
+    kind: Option<String>,
+    #[serde(default)]
+    data: serde_json::Value, // Trades on the trade channel; status and other channels differ
+    success: Option<bool>,
+    error: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Trade {
+    symbol: String,
+    #[serde(deserialize_with = "de_decimal")]
+    price: f64,
+    #[serde(deserialize_with = "de_decimal")]
+    qty: f64,
+    timestamp: String,
+}
+
+impl VenueProtocol for Kraken {
+    fn venue(&self) -> &'static str {
+        "kraken"
+    }
+
+    fn url(&self) -> &'static str {
+        "wss://ws.kraken.com/v2"
+    }
+
+    fn subscribe_messages(&self, symbols: &[String]) -> Vec<String> {
+        vec![json!({
+            "method": "subscribe",
+            "params": {"channel": "trade", "symbol": symbols, "snapshot": false}
+        })
+        .to_string()]
+    }
+
+    fn parse(&self, text: &str) -> Result<Vec<MarketEvent>, String> {
+        let message: Message = serde_json::from_str(text).map_err(|e| e.to_string())?;
+        if message.success == Some(false) {
+            return Err(message.error.unwrap_or_else(|| text.to_string()));
+        }
+        if message.channel.as_deref() != Some("trade") || message.kind.as_deref() != Some("update") {
+            return Ok(Vec::new());
+        }
+
+        let trades: Vec<Trade> = serde_json::from_value(message.data).map_err(|e| e.to_string())?;
+        trades
+            .into_iter()
+            .map(|trade| {
+                let time = rfc3339_millis(&trade.timestamp)?;
+                Ok(MarketEvent::trade(self.venue(), &trade.symbol, trade.price, Some(trade.qty), Some(time)))
+            })
+            .collect()
+    }
+
+    fn ping_message(&self) -> Option<String> {
+        Some(json!({"method": "ping"}).to_string())
+    }
+}
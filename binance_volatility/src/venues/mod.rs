@@ -0,0 +1,224 @@
+use async_trait::async_trait;
+use futures_util::stream::BoxStream;
+use futures_util::SinkExt;
+use log::{error, info, warn};
+use std::sync::Arc;
+use time::format_description::well_known::Rfc3339;
+use time::OffsetDateTime;
+use tokio::time::{interval, sleep, Duration, MissedTickBehavior};
+use tokio_tungstenite::connect_async;
+use tokio_tungstenite::tungstenite::Message;
+use volatility_core::channel::EventSender;
+use volatility_core::event::MarketEvent;
+use volatility_core::source::{ConnectionState, Health, HealthTracker, PriceSource, SourceError, TickChannel};
+
+use crate::client::stream::{next_text, WsStream};
+
+pub mod bybit;
+pub mod coinbase;
+pub mod kraken;
+pub mod okx;
+
+pub use bybit::Bybit;
+pub use coinbase::Coinbase;
+pub use kraken::Kraken;
+pub use okx::Okx;
+
+// Application-level keepalive interval; OKX drops connections silent for 30s
+const PING_INTERVAL: Duration = Duration::from_secs(20);
+
+/// Wire format of one venue's public trade stream.
+pub trait VenueProtocol: Send + Sync + 'static {
+    fn venue(&self) -> &'static str;
+
+    /// Default public WebSocket endpoint.
+    fn url(&self) -> &'static str;
+
+    /// Messages that subscribe to trades for the venue-native symbols.
+    fn subscribe_messages(&self, symbols: &[String]) -> Vec<String>;
+
+    /// Decodes a text frame into ticks; control messages yield no ticks and
+    /// venue error payloads an `Err`.
+    fn parse(&self, text: &str) -> Result<Vec<MarketEvent>, String>;
+
+    /// Keepalive sent every 20 seconds, for venues that expect one.
+    fn ping_message(&self) -> Option<String> {
+        None
+    }
+}
+
+/// [`PriceSource`] over a venue's public WebSocket trade stream. Reconnects
+/// and resubscribes with backoff once subscribed.
+pub struct WsPriceSource<P: VenueProtocol> {
+    protocol: Arc<P>,
+    url: String,
+    stream: Option<WsStream>,
+    subscribed: bool,
+    ticks: TickChannel,
+    health: HealthTracker,
+}
+
+impl<P: VenueProtocol> WsPriceSource<P> {
+    pub fn new(protocol: P) -> Self {
+        let url = protocol.url().to_string();
+        Self {
+            protocol: Arc::new(protocol),
+            url,
+            stream: None,
+            subscribed: false,
+            ticks: TickChannel::new(),
+            health: HealthTracker::new(),
+        }
+    }
+
+    /// Overrides the WebSocket endpoint, e.g. for a sandbox or a local stand-in.
+    pub fn with_url(mut self, url: &str) -> Self {
+        self.url = url.to_string();
+        self
+    }
+}
+
+#[async_trait]
+impl<P: VenueProtocol> PriceSource for WsPriceSource<P> {
+    fn venue(&self) -> &str {
+        self.protocol.venue()
+    }
+
+    async fn connect(&mut self) -> Result<(), SourceError> {
+        let (stream, _) = connect_async(&self.url)
+            .await
+            .map_err(|e| SourceError::Connect(e.to_string()))?;
+        info!("Connected to {} at {}", self.protocol.venue(), self.url);
+        self.stream = Some(stream);
+        self.health.set_state(ConnectionState::Connected);
+        Ok(())
+    }
+
+    async fn subscribe(&mut self, symbols: &[String]) -> Result<(), SourceError> {
+        if self.subscribed {
+            return Err(SourceError::Subscribe("already subscribed".to_string()));
+        }
+        let stream = self.stream.take().ok_or(SourceError::NotConnected)?;
+        self.subscribed = true;
+
+        tokio::spawn(run_venue(
+            Arc::clone(&self.protocol),
+            self.url.clone(),
+            symbols.to_vec(),
+            stream,
+            self.ticks.sender(),
+            self.health.clone(),
+        ));
+        Ok(())
+    }
+
+    fn ticks(&mut self) -> Result<BoxStream<'static, MarketEvent>, SourceError> {
+        self.ticks.take_stream()
+    }
+
+    fn health(&self) -> Health {
+        self.health.snapshot()
+    }
+}
+
+/// Reads one venue connection after another, resubscribing on each.
+async fn run_venue<P: VenueProtocol>(
+    protocol: Arc<P>,
+    url: String,
+    symbols: Vec<String>,
+    stream: WsStream,
+    sender: EventSender<MarketEvent>,
+    health: HealthTracker,
+) {
+    let venue = protocol.venue();
+    let mut current = Some(stream);
+    let mut retry_attempts = 0;
+
+    loop {
+        let mut stream = match current.take() {
+            Some(stream) => stream,
+            None => match connect_async(&url).await {
+                Ok((stream, _)) => {
+                    info!("Reconnected to {} at {}", venue, url);
+                    stream
+                }
+                Err(e) => {
+                    retry_attempts += 1;
+                    error!("Failed to connect to {} (attempt {}): {}", venue, retry_attempts, e);
+                    health.record_reconnect(&e.to_string());
+                    sleep(Duration::from_secs(retry_attempts.min(10))).await;
+                    continue;
+                }
+            },
+        };
+
+        let mut subscribed = true;
+        for message in protocol.subscribe_messages(&symbols) {
+            if let Err(e) = stream.send(Message::text(message)).await {
+                error!("Failed to subscribe on {}: {}", venue, e);
+                subscribed = false;
+                break;
+            }
+        }
+
+        if subscribed {
+            health.set_state(ConnectionState::Connected);
+            retry_attempts = 0;
+
+            let mut ping = interval(PING_INTERVAL);
+            ping.set_missed_tick_behavior(MissedTickBehavior::Delay);
+            ping.tick().await; // The first tick completes immediately
+
+            loop {
+                tokio::select! {
+                    text = next_text(&mut stream, venue) => {
+                        let Some(text) = text else { break };
+                        match protocol.parse(&text) {
+                            Ok(ticks) => {
+                                for tick in ticks {
+                                    health.record_tick(tick.timestamp());
+                                    if sender.send(tick).await.is_err() {
+                                        // Nobody is consuming ticks any more
+                                        health.set_state(ConnectionState::Closed);
+                                        return;
+                                    }
+                                }
+                            }
+                            Err(e) => warn!("{} sent an error: {}", venue, e),
+                        }
+                    }
+                    _ = ping.tick() => {
+                        let Some(message) = protocol.ping_message() else { continue };
+                        if let Err(e) = stream.send(Message::text(message)).await {
+                            error!("Failed to ping {}: {}", venue, e);
+                            break;
+                        }
+                    }
+                }
+            }
+        }
+
+        warn!("{} connection closed. Reconnecting...", venue);
+        health.record_reconnect("connection closed");
+        retry_attempts += 1;
+        sleep(Duration::from_secs(retry_attempts.min(10))).await;
+    }
+}
+
+/// Builds the WebSocket source for a venue name, e.g. `kraken`.
+pub fn venue_source(venue: &str) -> Option<Box<dyn PriceSource>> {
+    let source: Box<dyn PriceSource> = match venue {
+        "coinbase" => Box::new(WsPriceSource::new(Coinbase)),
+        "kraken" => Box::new(WsPriceSource::new(Kraken)),
+        "okx" => Box::new(WsPriceSource::new(Okx)),
+        "bybit" => Box::new(WsPriceSource::new(Bybit)),
+        _ => return None,
+    };
+    Some(source)
+}
+
+/// Parses an RFC 3339 timestamp into milliseconds since epoch.
+pub(crate) fn rfc3339_millis(timestamp: &str) -> Result<u64, String> {
+    let time = OffsetDateTime::parse(timestamp, &Rfc3339).map_err(|e| e.to_string())?;
+    Ok((time.unix_timestamp_nanos() / 1_000_000) as u64)
+}
@@ -0,0 +1,68 @@
+use serde::Deserialize;
+use serde_json::json;
+use volatility_core::event::MarketEvent;
+
+use super::VenueProtocol;
+use crate::client::models::de_decimal;
+
+/// OKX v5 public `trades` channel.
+pub struct Okx;
+
+#[derive(Deserialize)]
+struct Message {
+    event: Option<String>,
+    msg: Option<String>,
+    #[serde(default)]
+    data: Vec<Trade>,
+}
+
+#[derive(Deserialize)]
+struct Trade {
+    #[serde(rename = "instId")]
+    inst_id: String,
+    #[serde(deserialize_with = "de_decimal")]
+    px: f64,
+    #[serde(deserialize_with = "de_decimal")]
+    sz: f64,
+    #[serde(deserialize_with = "de_decimal")]
+    ts: f64, // Milliseconds, sent as a string
+}
+
+impl VenueProtocol for Okx {
+    fn venue(&self) -> &'static str {
+        "okx"
+    }
+
+    fn url(&self) -> &'static str {
+        "wss://ws.okx.com:8443/ws/v5/public"
+    }
+
+    fn subscribe_messages(&self, symbols: &[String]) -> Vec<String> {
+        let args: Vec<_> = symbols
+            .iter()
+            .map(|symbol| json!({"channel": "trades", "instId": symbol}))
+            .collect();
+        vec![json!({"op": "subscribe", "args": args}).to_string()]
+    }
+
+    fn parse(&self, text: &str) -> Result<Vec<MarketEvent>, String> {
+        // Reply to our plain-text keepalive
+        if text == "pong" {
+            return Ok(Vec::new());
+        }
+        let message: Message = serde_json::from_str(text).map_err(|e| e.to_string())?;
+        if message.event.as_deref() == Some("error") {
+            return Err(message.msg.unwrap_or_else(|| text.to_string()));
+        }
+
+        Ok(message
+            .data
+            .into_iter()
+            .map(|trade| MarketEvent::trade(self.venue(), &trade.inst_id, trade.px, Some(trade.sz), Some(trade.ts as u64)))
+            .collect())
+    }
+
+    fn ping_message(&self) -> Option<String> {
+        Some("ping".to_string())
+    }
+}
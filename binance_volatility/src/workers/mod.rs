@@ -0,0 +1,169 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::SystemTime;
+use tokio::sync::mpsc::{self, Receiver, Sender};
+use tokio::task::JoinHandle;
+
+use crate::math::VolatilityCalculator;
+
+/// Compact id for one price series of a symbol, assigned in order of first
+/// use.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SymbolId(u32);
+
+impl SymbolId {
+    pub fn index(&self) -> usize {
+        self.0 as usize
+    }
+}
+
+/// Maps a symbol and series to a [`SymbolId`] so hot paths carry a `u32`
+/// instead of a `String`. The series tells apart the streams of one symbol,
+/// such as its `1m` and `1h` klines or its trades, which must not share a
+/// window.
+#[derive(Debug, Default)]
+pub struct SymbolInterner {
+    ids: HashMap<Arc<str>, Vec<(Arc<str>, SymbolId)>>, // By symbol, then series
+    names: Vec<(Arc<str>, Arc<str>)>,
+}
+
+impl SymbolInterner {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The series' id, assigning the next one if it is new.
+    pub fn intern(&mut self, symbol: &str, series: &str) -> SymbolId {
+        if let Some(id) = self.get(symbol, series) {
+            return id;
+        }
+        let id = SymbolId(self.names.len() as u32);
+        let (symbol, series): (Arc<str>, Arc<str>) = (Arc::from(symbol), Arc::from(series));
+        self.ids.entry(symbol.clone()).or_default().push((series.clone(), id));
+        self.names.push((symbol, series));
+        id
+    }
+
+    pub fn get(&self, symbol: &str, series: &str) -> Option<SymbolId> {
+        let series_ids = self.ids.get(symbol)?;
+        series_ids.iter().find(|(name, _)| &**name == series).map(|(_, id)| *id)
+    }
+
+    /// Symbol and series of `id`.
+    pub fn name(&self, id: SymbolId) -> Option<(&Arc<str>, &Arc<str>)> {
+        self.names.get(id.index()).map(|(symbol, series)| (symbol, series))
+    }
+
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+}
+
+/// Called by a worker after every sample with the symbol, the series and its
+/// volatility, `None` while the window is too short.
+pub type VolatilitySink = Arc<dyn Fn(&str, &str, Option<f64>) + Send + Sync>;
+
+// A worker's calculator for one series, with the labels it reports under
+struct Series {
+    symbol: Arc<str>,
+    series: Arc<str>,
+    calculator: VolatilityCalculator,
+}
+
+enum WorkerMessage {
+    Register { slot: usize, symbol: Arc<str>, series: Arc<str> },
+    Sample { slot: usize, timestamp: SystemTime, price: f64 },
+}
+
+/// Volatility calculators spread over `N` worker tasks. Series `id` lives on
+/// worker `id % N` in slot `id / N`, so each worker owns its calculators
+/// outright and a sample is routed without locks or string lookups.
+pub struct ShardedCalculators {
+    interner: SymbolInterner,
+    shards: Vec<Sender<WorkerMessage>>,
+    workers: Vec<JoinHandle<()>>,
+}
+
+impl ShardedCalculators {
+    /// Spawns `shards` workers with rolling windows of `window_secs` and
+    /// queues of `queue_capacity` samples each. Sending to a full queue waits.
+    pub fn spawn(shards: usize, window_secs: u64, queue_capacity: usize, sink: VolatilitySink) -> Self {
+        let (shards, workers) = (0..shards.max(1))
+            .map(|_| {
+                let (sender, receiver) = mpsc::channel(queue_capacity.max(1));
+                (sender, tokio::spawn(run_worker(receiver, window_secs, sink.clone())))
+            })
+            .unzip();
+        Self {
+            interner: SymbolInterner::new(),
+            shards,
+            workers,
+        }
+    }
+
+    pub fn shard_count(&self) -> usize {
+        self.shards.len()
+    }
+
+    /// Worker owning `symbol`'s `series`, once it has been seen.
+    pub fn shard_of(&self, symbol: &str, series: &str) -> Option<usize> {
+        self.interner.get(symbol, series).map(|id| id.index() % self.shards.len())
+    }
+
+    /// Id for `symbol`'s `series`, registering it with its worker on first use.
+    pub async fn intern(&mut self, symbol: &str, series: &str) -> SymbolId {
+        if let Some(id) = self.interner.get(symbol, series) {
+            return id;
+        }
+        let id = self.interner.intern(symbol, series);
+        let (shard, slot) = self.route(id);
+        let Some((symbol, series)) = self.interner.name(id) else { return id };
+        let (symbol, series) = (symbol.clone(), series.clone());
+        let _ = self.shards[shard].send(WorkerMessage::Register { slot, symbol, series }).await;
+        id
+    }
+
+    /// Queues a sample for an interned series.
+    pub async fn add(&self, id: SymbolId, timestamp: SystemTime, price: f64) {
+        let (shard, slot) = self.route(id);
+        let _ = self.shards[shard].send(WorkerMessage::Sample { slot, timestamp, price }).await;
+    }
+
+    /// Closes the queues and waits for the workers to drain them.
+    pub async fn shutdown(self) {
+        drop(self.shards);
+        for worker in self.workers {
+            let _ = worker.await;
+        }
+    }
+
+    fn route(&self, id: SymbolId) -> (usize, usize) {
+        let shards = self.shards.len();
+        (id.index() % shards, id.index() / shards)
+    }
+}
+
+async fn run_worker(mut receiver: Receiver<WorkerMessage>, window_secs: u64, sink: VolatilitySink) {
+    let mut calculators: Vec<Option<Series>> = Vec::new();
+
+    while let Some(message) = receiver.recv().await {
+        match message {
+            WorkerMessage::Register { slot, symbol, series } => {
+                if calculators.len() <= slot {
+                    calculators.resize_with(slot + 1, || None);
+                }
+                let calculator = VolatilityCalculator::new(window_secs);
+                calculators[slot] = Some(Series { symbol, series, calculator });
+            }
+            WorkerMessage::Sample { slot, timestamp, price } => {
+                let Some(Some(entry)) = calculators.get_mut(slot) else { continue };
+                entry.calculator.add_value_at(timestamp, price);
+                sink(&entry.symbol, &entry.series, entry.calculator.calculate_volatility());
+            }
+        }
+    }
+}
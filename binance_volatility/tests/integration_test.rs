@@ -0,0 +1,935 @@
+use binance_volatility::book::{BookError, BookSync, DepthSnapshot, DepthUpdate, UpdateOutcome};
+use binance_volatility::client::kline::{backfill_limit, KlineFilter, KlineInterval, KlineMode};
+use binance_volatility::client::models::{MessageError, RestKline};
+use binance_volatility::client::rest::RestClient;
+use binance_volatility::client::sequence::{Continuity, SequenceFilter};
+use binance_volatility::client::symbols::{validate_symbols, ExchangeInfo, SymbolError};
+use binance_volatility::client::{parse_event, BinanceClient, StreamEvent, StreamKind, Subscription};
+use binance_volatility::math::{predicted_funding_rate, TimeWeightedAverage, VolatilityCalculator};
+use binance_volatility::workers::{ShardedCalculators, SymbolInterner};
+use binance_volatility::venues::{venue_source, Bybit, Coinbase, Kraken, Okx, VenueProtocol, WsPriceSource};
+use volatility_core::channel::{bounded, BackpressurePolicy, Conflate};
+use volatility_core::event::{EventDetail, EventKind};
+use volatility_core::source::{ConnectionState, PriceSource, SourceError};
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Request, Response, Server};
+use futures_util::{SinkExt, StreamExt};
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use tokio::net::TcpListener;
+use tokio::sync::{broadcast, mpsc};
+use tokio_tungstenite::tungstenite::Message;
+
+#[test]
+fn test_parse_kline_close_price() {
+    let text = r#"{"e":"kline","E":1700000000000,"s":"ETHUSDC","k":{"t":1699999980000,"T":1700000039999,"s":"ETHUSDC","i":"1m","o":"2049.00","c":"2050.25","h":"2051.00","l":"2048.50","v":"12.5","x":false}}"#;
+    let kind = StreamKind::Kline(KlineInterval::OneMinute);
+    let event = parse_event(text, &Subscription::new("ethusdc", kind)).unwrap().unwrap();
+    // Its own volatility series, apart from the symbol's other intervals
+    assert_eq!(event.series(), Some("1m"));
+
+    match event {
+        StreamEvent::Kline(kline) => {
+            assert_eq!(kline.close, 2050.25);
+            assert_eq!(kline.interval, "1m");
+            assert_eq!(kline.open_time, 1699999980000);
+            assert!(!kline.is_closed);
+        }
+        other => panic!("Expected a kline, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_parse_trade() {
+    let text = r#"{"e":"trade","E":1700000000001,"s":"ETHUSDC","t":42,"p":"2050.10","q":"0.5","T":1700000000000,"m":true,"M":true}"#;
+    let event = parse_event(text, &Subscription::new("ethusdc", StreamKind::Trade)).unwrap().unwrap();
+    assert_eq!(event.series(), Some("trade"));
+
+    match event {
+        StreamEvent::Trade(trade) => {
+            assert_eq!(trade.price, 2050.10);
+            assert_eq!(trade.quantity, 0.5);
+            assert!(trade.is_buyer_maker);
+            assert_eq!((trade.trade_id, trade.first_trade_id, trade.last_trade_id), (42, 42, 42));
+            assert_eq!(trade.event_time, 1700000000001);
+        }
+        other => panic!("Expected a trade, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_parse_agg_trade_id_range() {
+    let text = r#"{"e":"aggTrade","E":1700000000001,"s":"ETHUSDC","a":7,"p":"2050.10","q":"1.25","f":100,"l":105,"T":1700000000000,"m":false,"M":true}"#;
+    let event = parse_event(text, &Subscription::new("ethusdc", StreamKind::AggTrade)).unwrap().unwrap();
+
+    match event {
+        StreamEvent::Trade(trade) => {
+            assert_eq!(trade.kind, StreamKind::AggTrade);
+            assert!(!trade.is_buyer_maker);
+            assert_eq!((trade.trade_id, trade.first_trade_id, trade.last_trade_id), (7, 100, 105));
+        }
+        other => panic!("Expected a trade, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_parse_control_response_yields_no_event() {
+    let subscription = Subscription::new("ethusdc", StreamKind::Trade);
+    assert_eq!(parse_event(r#"{"result":null,"id":1}"#, &subscription), Ok(None));
+}
+
+#[test]
+fn test_parse_error_payloads() {
+    let subscription = Subscription::new("invalidsymbol", StreamKind::Trade);
+
+    assert_eq!(
+        parse_event(r#"{"code":-1121,"msg":"Invalid symbol."}"#, &subscription),
+        Err(MessageError::Api { code: -1121, msg: "Invalid symbol.".to_string() })
+    );
+    assert_eq!(
+        parse_event(r#"{"error":{"code":2,"msg":"Invalid request"},"id":1}"#, &subscription),
+        Err(MessageError::Api { code: 2, msg: "Invalid request".to_string() })
+    );
+    assert_eq!(parse_event(r#"{"e":"trade","p":"1.0"}"#, &subscription).unwrap_err().kind(), "unknown");
+    assert_eq!(parse_event("not json", &subscription).unwrap_err().kind(), "malformed");
+}
+
+#[test]
+fn test_parse_decimal_rejects_garbage_price() {
+    let text = r#"{"e":"trade","E":1,"s":"ETHUSDC","t":1,"p":"abc","q":"0.5","T":1,"m":true,"M":true}"#;
+    let error = parse_event(text, &Subscription::new("ethusdc", StreamKind::Trade)).unwrap_err();
+    assert_eq!(error.kind(), "unknown");
+}
+
+fn depth_update(first: u64, last: u64, bids: Vec<(f64, f64)>, asks: Vec<(f64, f64)>) -> DepthUpdate {
+    DepthUpdate { event_time: 0, first_update_id: first, final_update_id: last, bids, asks }
+}
+
+fn depth_snapshot(last_update_id: u64) -> DepthSnapshot {
+    DepthSnapshot {
+        last_update_id,
+        bids: vec![(100.0, 1.0), (99.0, 2.0)],
+        asks: vec![(101.0, 3.0), (102.0, 4.0)],
+    }
+}
+
+#[test]
+fn test_parse_depth_update() {
+    let text = r#"{"e":"depthUpdate","E":123456789,"s":"BNBBTC","U":157,"u":160,"b":[["0.0024","10"]],"a":[["0.0026","100"]]}"#;
+    let update: DepthUpdate = serde_json::from_str(text).unwrap();
+
+    assert_eq!(update.event_time, 123456789);
+    assert_eq!((update.first_update_id, update.final_update_id), (157, 160));
+    assert_eq!(update.bids, vec![(0.0024, 10.0)]);
+    assert_eq!(update.asks, vec![(0.0026, 100.0)]);
+}
+
+#[test]
+fn test_book_sync_applies_buffered_updates_after_snapshot() {
+    let mut sync = BookSync::new();
+    assert_eq!(sync.on_update(depth_update(8, 10, vec![], vec![])), Ok(UpdateOutcome::Buffered));
+    assert_eq!(sync.on_update(depth_update(11, 12, vec![(100.0, 0.0), (100.5, 5.0)], vec![])), Ok(UpdateOutcome::Buffered));
+
+    // Snapshot at 10 covers the first diff; the second continues from it
+    sync.on_snapshot(&depth_snapshot(10)).unwrap();
+    assert!(sync.is_synced());
+    assert_eq!(sync.book().last_update_id(), 12);
+    assert_eq!(sync.book().best_bid(), Some((100.5, 5.0)));
+
+    assert_eq!(sync.on_update(depth_update(13, 13, vec![], vec![(101.0, 1.0)])), Ok(UpdateOutcome::Applied));
+    assert_eq!(sync.book().best_ask(), Some((101.0, 1.0)));
+}
+
+#[test]
+fn test_book_sync_rejects_stale_snapshot() {
+    let mut sync = BookSync::new();
+    sync.on_update(depth_update(20, 25, vec![], vec![])).unwrap();
+
+    assert_eq!(
+        sync.on_snapshot(&depth_snapshot(10)),
+        Err(BookError::StaleSnapshot { last_update_id: 10, first_buffered: 20 })
+    );
+    assert!(!sync.is_synced());
+}
+
+#[test]
+fn test_book_sync_detects_gap() {
+    let mut sync = BookSync::new();
+    sync.on_update(depth_update(10, 11, vec![], vec![])).unwrap();
+    sync.on_snapshot(&depth_snapshot(10)).unwrap();
+
+    assert_eq!(
+        sync.on_update(depth_update(15, 16, vec![], vec![])),
+        Err(BookError::Gap { expected: 12, got: 15 })
+    );
+}
+
+#[test]
+fn test_book_summary_mid_and_microprice() {
+    let mut sync = BookSync::new();
+    sync.on_update(depth_update(10, 10, vec![], vec![])).unwrap();
+    sync.on_snapshot(&depth_snapshot(10)).unwrap();
+
+    let summary = sync.book().summary("bnbbtc", 0, 1).unwrap();
+    assert_eq!(summary.mid_price, 100.5);
+    assert_eq!(summary.spread, 1.0);
+    // (100 * 3 + 101 * 1) / 4
+    assert_eq!(summary.microprice, 100.25);
+    assert_eq!((summary.bid_depth, summary.ask_depth), (1.0, 3.0));
+
+    let summary = sync.book().summary("bnbbtc", 0, 10).unwrap();
+    assert_eq!((summary.bid_depth, summary.ask_depth), (3.0, 7.0));
+}
+
+#[test]
+fn test_parse_book_ticker_spread() {
+    let text = r#"{"u":400900217,"s":"BNBUSDT","b":"99.50","B":"31.21","a":"100.50","A":"40.66"}"#;
+    let event = parse_event(text, &Subscription::new("bnbusdt", StreamKind::BookTicker)).unwrap().unwrap();
+
+    match event {
+        StreamEvent::Quote(quote) => {
+            assert_eq!(quote.update_id, 400900217);
+            assert_eq!(quote.mid_price(), 100.0);
+            assert_eq!(quote.spread(), 1.0);
+            assert_eq!(quote.spread_bps(), 100.0);
+        }
+        other => panic!("Expected a quote, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_time_weighted_average_weights_by_duration() {
+    let start = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000);
+    let mut average = TimeWeightedAverage::new(30);
+
+    // 2.0 for 10 seconds, then 5.0 for 20 seconds
+    average.add_value_at(start, 2.0);
+    average.add_value_at(start + Duration::from_secs(10), 5.0);
+
+    let result = average.average_at(start + Duration::from_secs(30)).unwrap();
+    assert!((result - 4.0).abs() < 1e-9);
+}
+
+#[test]
+fn test_time_weighted_average_clips_to_window() {
+    let start = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000);
+    let mut average = TimeWeightedAverage::new(10);
+
+    average.add_value_at(start, 1.0);
+    average.add_value_at(start + Duration::from_secs(100), 3.0);
+
+    // Window covers 95s..105s: 1.0 for 5 seconds, then 3.0 for 5 seconds
+    let result = average.average_at(start + Duration::from_secs(105)).unwrap();
+    assert!((result - (1.0 * 5.0 + 3.0 * 5.0) / 10.0).abs() < 1e-9);
+}
+
+#[test]
+fn test_time_weighted_average_window_longer_than_epoch_offset() {
+    let start = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000);
+    let mut average = TimeWeightedAverage::new(10_000);
+
+    // The window reaches back past the epoch, so it covers all 30 seconds
+    average.add_value_at(start, 2.0);
+    average.add_value_at(start + Duration::from_secs(10), 5.0);
+
+    let result = average.average_at(start + Duration::from_secs(30)).unwrap();
+    assert!((result - 4.0).abs() < 1e-9);
+}
+
+#[test]
+fn test_kline_interval_round_trip() {
+    for code in ["1s", "1m", "5m", "1h", "1d", "1M"] {
+        let interval: KlineInterval = code.parse().unwrap();
+        assert_eq!(interval.as_str(), code);
+        assert_eq!(StreamKind::Kline(interval).suffix(), format!("kline_{}", code));
+    }
+    assert!("7m".parse::<KlineInterval>().is_err());
+    assert_eq!(KlineInterval::FiveMinutes.millis(), 300_000);
+}
+
+#[test]
+fn test_kline_filter_closed_only() {
+    let mut filter = KlineFilter::new(KlineMode::ClosedOnly);
+
+    assert!(!filter.accept(0, 100.0, false));
+    assert!(!filter.accept(0, 101.0, false));
+    assert!(filter.accept(0, 101.5, true));
+    // A replayed final update for the same candle is dropped
+    assert!(!filter.accept(0, 101.5, true));
+    assert!(filter.accept(60_000, 102.0, true));
+}
+
+#[test]
+fn test_kline_filter_intra_candle_dedupe() {
+    let mut filter = KlineFilter::new(KlineMode::IntraCandle);
+
+    assert!(filter.accept(0, 100.0, false));
+    assert!(!filter.accept(0, 100.0, false)); // Unchanged close
+    assert!(filter.accept(0, 100.5, false));
+    assert!(filter.accept(0, 100.5, true)); // Final update always forwarded once
+    assert!(!filter.accept(0, 100.5, false)); // Late update for a closed candle
+    assert!(filter.accept(60_000, 100.5, false)); // New candle
+}
+
+// Serves a fixed JSON body for every request on an ephemeral local port
+async fn serve_json(body: &'static str) -> String {
+    let make_svc = make_service_fn(move |_conn| async move {
+        Ok::<_, Infallible>(service_fn(move |_req: Request<Body>| async move {
+            Ok::<_, Infallible>(Response::new(Body::from(body)))
+        }))
+    });
+    let server = Server::bind(&([127, 0, 0, 1], 0).into()).serve(make_svc);
+    let url = format!("http://{}", server.local_addr());
+    tokio::spawn(server);
+    url
+}
+
+const KLINES_BODY: &str = r#"[
+    [1700000000000,"100.0","101.0","99.0","100.5","10.0",1700000059999,"1000.0",42,"5.0","500.0","0"],
+    [1700000060000,"100.5","102.0","100.0","101.5","12.0",1700000119999,"1200.0",40,"6.0","600.0","0"],
+    [4102444800000,"101.5","101.5","101.5","101.5","0.0",4102444859999,"0.0",0,"0.0","0.0","0"]
+]"#;
+
+#[test]
+fn test_parse_rest_kline_row() {
+    let rows: Vec<RestKline> = serde_json::from_str(KLINES_BODY).unwrap();
+    let payload = rows[0].clone().into_payload("1m", 1700000060000);
+
+    assert_eq!((payload.open_time, payload.close_time), (1700000000000, 1700000059999));
+    assert_eq!((payload.open, payload.high, payload.low, payload.close), (100.0, 101.0, 99.0, 100.5));
+    assert!(payload.is_closed);
+    assert!(!rows[2].clone().into_payload("1m", 1700000060000).is_closed);
+}
+
+#[test]
+fn test_backfill_limit_covers_window() {
+    assert_eq!(backfill_limit(Duration::from_secs(600), KlineInterval::OneMinute), 11);
+    assert_eq!(backfill_limit(Duration::from_secs(30), KlineInterval::OneMinute), 1);
+    assert_eq!(backfill_limit(Duration::from_secs(30 * 60), KlineInterval::OneMinute), 31);
+    assert_eq!(backfill_limit(Duration::from_secs(u64::MAX / 2), KlineInterval::OneSecond), 1000);
+}
+
+#[tokio::test]
+async fn test_rest_klines_from_local_endpoint() {
+    let url = serve_json(KLINES_BODY).await;
+    let klines = RestClient::new(&url).klines("ethusdc", KlineInterval::OneMinute, 3).await.unwrap();
+
+    assert_eq!(klines.len(), 3);
+    assert_eq!(klines[1].close, 101.5);
+    assert_eq!(klines[1].interval, "1m");
+    assert!(klines[0].is_closed && klines[1].is_closed);
+    assert!(!klines[2].is_closed);
+}
+
+#[test]
+fn test_volatility_with_backfilled_timestamps() {
+    let start = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000);
+    let mut calculator = VolatilityCalculator::new(60);
+
+    // One price per second for 20 seconds, then one 2 minutes later
+    for i in 0..20 {
+        calculator.add_value_at(start + Duration::from_secs(i), 100.0 + i as f64);
+    }
+    assert!(calculator.calculate_volatility().is_some());
+
+    calculator.add_value_at(start + Duration::from_secs(200), 120.0);
+    assert_eq!(calculator.calculate_volatility(), None, "Old history should have left the window.");
+}
+
+#[test]
+fn test_sequence_filter_drops_replayed_ids() {
+    let mut filter = SequenceFilter::new();
+    assert!(filter.accept(10));
+    assert!(filter.accept(11));
+    assert!(!filter.accept(11));
+    assert!(!filter.accept(9));
+    assert!(filter.accept(15));
+}
+
+#[test]
+fn test_sequence_filter_reports_gaps() {
+    let mut filter = SequenceFilter::new();
+    assert_eq!(filter.observe(100, 104), Continuity::First); // aggTrade covering trades 100..=104
+    assert_eq!(filter.observe(105, 105), Continuity::Contiguous);
+    assert_eq!(filter.observe(103, 105), Continuity::Duplicate);
+    assert_eq!(
+        filter.observe(110, 112),
+        Continuity::Gap { first_missing: 106, last_missing: 109 }
+    );
+    assert_eq!(filter.last_id(), Some(112));
+
+    // An open candle 115 means candles through 114 should have closed
+    assert_eq!(filter.observe(115, 114), Continuity::Gap { first_missing: 113, last_missing: 114 });
+    assert_eq!(filter.observe(115, 114), Continuity::Duplicate);
+    assert_eq!(filter.observe(115, 115), Continuity::Contiguous);
+}
+
+fn trade_json(id: u64) -> String {
+    format!(
+        r#"{{"e":"trade","E":{},"s":"ETHUSDC","t":{},"p":"2050.00","q":"0.1","T":{},"m":false,"M":true}}"#,
+        1700000000000 + id,
+        id,
+        1700000000000 + id
+    )
+}
+
+// WebSocket server publishing one trade every 5ms to all connections; each
+// new connection first replays the previous three trades, like an overlap
+async fn serve_trades(connections: Arc<AtomicUsize>) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let (tx, _) = broadcast::channel::<u64>(1024);
+
+    let publisher = tx.clone();
+    tokio::spawn(async move {
+        for id in 1.. {
+            let _ = publisher.send(id);
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+    });
+
+    tokio::spawn(async move {
+        while let Ok((tcp, _)) = listener.accept().await {
+            connections.fetch_add(1, Ordering::SeqCst);
+            let mut trades = tx.subscribe();
+            tokio::spawn(async move {
+                let mut ws = tokio_tungstenite::accept_async(tcp).await.unwrap();
+                let first = trades.recv().await.unwrap();
+                for id in first.saturating_sub(3).max(1)..=first {
+                    if ws.send(Message::text(trade_json(id))).await.is_err() {
+                        return;
+                    }
+                }
+                while let Ok(id) = trades.recv().await {
+                    if ws.send(Message::text(trade_json(id))).await.is_err() {
+                        return;
+                    }
+                }
+            });
+        }
+    });
+
+    format!("ws://{}", addr)
+}
+
+#[tokio::test]
+async fn test_rollover_has_no_gaps_or_duplicates() {
+    let connections = Arc::new(AtomicUsize::new(0));
+    let base_url = serve_trades(connections.clone()).await;
+    let (sender, mut receiver) = mpsc::unbounded_channel();
+
+    let client = BinanceClient::new(&base_url, sender)
+        .await
+        .unwrap()
+        .with_max_connection_age(Duration::from_millis(300));
+    client.start_streams(vec![Subscription::new("ethusdc", StreamKind::Trade)]).await;
+
+    let mut ids = Vec::new();
+    let deadline = tokio::time::Instant::now() + Duration::from_millis(1500);
+    while let Ok(Some(event)) = tokio::time::timeout_at(deadline, receiver.recv()).await {
+        if let StreamEvent::Trade(trade) = event {
+            ids.push(trade.trade_id);
+        }
+    }
+
+    assert!(connections.load(Ordering::SeqCst) >= 3, "expected several rollovers");
+    assert!(ids.len() > 100);
+    for pair in ids.windows(2) {
+        assert_eq!(pair[1], pair[0] + 1, "gap or duplicate in {:?}", pair);
+    }
+}
+
+// Like `serve_trades`, but the first connection lags the feed by 40ms, so
+// each replacement sees new trades before the old connection does
+async fn serve_lagging_trades() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let (tx, _) = broadcast::channel::<u64>(1024);
+
+    let publisher = tx.clone();
+    tokio::spawn(async move {
+        for id in 1.. {
+            let _ = publisher.send(id);
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+    });
+
+    tokio::spawn(async move {
+        let mut lag = Duration::from_millis(40);
+        while let Ok((tcp, _)) = listener.accept().await {
+            let mut trades = tx.subscribe();
+            let (delayed_tx, mut delayed) = mpsc::unbounded_channel();
+            tokio::spawn(async move {
+                while let Ok(id) = trades.recv().await {
+                    if delayed_tx.send((tokio::time::Instant::now() + lag, id)).is_err() {
+                        return;
+                    }
+                }
+            });
+            lag = Duration::ZERO;
+            tokio::spawn(async move {
+                let mut ws = tokio_tungstenite::accept_async(tcp).await.unwrap();
+                while let Some((at, id)) = delayed.recv().await {
+                    tokio::time::sleep_until(at).await;
+                    if ws.send(Message::text(trade_json(id))).await.is_err() {
+                        return;
+                    }
+                }
+            });
+        }
+    });
+
+    format!("ws://{}", addr)
+}
+
+#[tokio::test]
+async fn test_rollover_waits_for_lagging_connection() {
+    let base_url = serve_lagging_trades().await;
+    let (sender, mut receiver) = mpsc::unbounded_channel();
+
+    // Rolls over once, with a 100ms overlap
+    let client = BinanceClient::new(&base_url, sender)
+        .await
+        .unwrap()
+        .with_max_connection_age(Duration::from_secs(1));
+    client.start_streams(vec![Subscription::new("ethusdc", StreamKind::Trade)]).await;
+
+    let mut ids = Vec::new();
+    let deadline = tokio::time::Instant::now() + Duration::from_millis(1600);
+    while let Ok(Some(event)) = tokio::time::timeout_at(deadline, receiver.recv()).await {
+        match event {
+            StreamEvent::Trade(trade) => ids.push(trade.trade_id),
+            StreamEvent::Gap(gap) => panic!("False gap at rollover: {}", gap),
+            other => panic!("Unexpected event {:?}", other),
+        }
+    }
+
+    assert!(ids.len() > 200);
+    for pair in ids.windows(2) {
+        assert_eq!(pair[1], pair[0] + 1, "gap or duplicate in {:?}", pair);
+    }
+}
+
+#[tokio::test]
+async fn test_server_ping_is_answered_with_pong() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let base_url = format!("ws://{}", listener.local_addr().unwrap());
+    let (pong_tx, mut pong_rx) = mpsc::unbounded_channel();
+
+    tokio::spawn(async move {
+        let (tcp, _) = listener.accept().await.unwrap();
+        let mut ws = tokio_tungstenite::accept_async(tcp).await.unwrap();
+        ws.send(Message::Ping(b"keepalive".to_vec().into())).await.unwrap();
+        while let Some(Ok(message)) = ws.next().await {
+            if let Message::Pong(payload) = message {
+                let _ = pong_tx.send(payload.to_vec());
+            }
+        }
+    });
+
+    let (sender, _receiver) = mpsc::unbounded_channel();
+    let client = BinanceClient::new(&base_url, sender).await.unwrap();
+    client.start_streams(vec![Subscription::new("ethusdc", StreamKind::Trade)]).await;
+
+    let payload = tokio::time::timeout(Duration::from_secs(5), pong_rx.recv()).await.unwrap();
+    assert_eq!(payload.unwrap(), b"keepalive".to_vec());
+}
+
+// WebSocket server that sends `messages` on each connection, then idles
+async fn serve_ws_messages(messages: Vec<String>) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    tokio::spawn(async move {
+        while let Ok((tcp, _)) = listener.accept().await {
+            let messages = messages.clone();
+            tokio::spawn(async move {
+                let mut ws = tokio_tungstenite::accept_async(tcp).await.unwrap();
+                for message in messages {
+                    ws.send(Message::text(message)).await.unwrap();
+                }
+                while ws.next().await.is_some() {}
+            });
+        }
+    });
+
+    format!("ws://{}", addr)
+}
+
+const HISTORICAL_TRADES_BODY: &str = r#"[
+    {"id":4,"price":"2050.04","qty":"0.1","quoteQty":"205.0","time":1700000000004,"isBuyerMaker":false,"isBestMatch":true},
+    {"id":5,"price":"2050.05","qty":"0.1","quoteQty":"205.0","time":1700000000005,"isBuyerMaker":true,"isBestMatch":true},
+    {"id":6,"price":"2050.06","qty":"0.1","quoteQty":"205.0","time":1700000000006,"isBuyerMaker":false,"isBestMatch":true},
+    {"id":7,"price":"2050.07","qty":"0.1","quoteQty":"205.0","time":1700000000007,"isBuyerMaker":false,"isBestMatch":true}
+]"#;
+
+#[tokio::test]
+async fn test_trade_gap_is_reported_and_backfilled() {
+    let messages = [1, 2, 3, 7, 8].iter().map(|id| trade_json(*id)).collect();
+    let base_url = serve_ws_messages(messages).await;
+    let rest_url = serve_json(HISTORICAL_TRADES_BODY).await;
+    let (sender, mut receiver) = mpsc::unbounded_channel();
+
+    let client = BinanceClient::new(&base_url, sender)
+        .await
+        .unwrap()
+        .with_rest_url(&rest_url)
+        .with_gap_backfill(true);
+    client.start_streams(vec![Subscription::new("ethusdc", StreamKind::Trade)]).await;
+
+    let mut trade_ids = Vec::new();
+    let mut gaps = Vec::new();
+    while trade_ids.len() < 8 {
+        let event = tokio::time::timeout(Duration::from_secs(5), receiver.recv()).await.unwrap().unwrap();
+        match event {
+            StreamEvent::Trade(trade) => trade_ids.push(trade.trade_id),
+            StreamEvent::Gap(gap) => {
+                gaps.push(gap);
+                assert_eq!(trade_ids, vec![1, 2, 3], "gap should be reported before the fill");
+            }
+            other => panic!("Unexpected event {:?}", other),
+        }
+    }
+
+    assert_eq!(trade_ids, vec![1, 2, 3, 4, 5, 6, 7, 8]);
+    assert_eq!(gaps.len(), 1);
+    assert_eq!((gaps[0].first_missing, gaps[0].last_missing, gaps[0].missing), (4, 6, 3));
+    assert_eq!(gaps[0].stream, "trade");
+}
+
+fn depth_json(first: u64, last: u64) -> String {
+    format!(
+        r#"{{"e":"depthUpdate","E":{},"s":"ETHUSDC","U":{},"u":{},"b":[["2050.00","1.0"]],"a":[["2050.10","1.0"]]}}"#,
+        1700000000000 + last,
+        first,
+        last
+    )
+}
+
+#[tokio::test]
+async fn test_stale_depth_snapshot_is_fetched_once_per_attempt() {
+    let messages = (0..20).map(|i| depth_json(100 + i, 100 + i)).collect();
+    let base_url = serve_ws_messages(messages).await;
+
+    // Every snapshot predates the first buffered diff
+    let requests = Arc::new(AtomicUsize::new(0));
+    let counter = requests.clone();
+    let make_svc = make_service_fn(move |_conn| {
+        let counter = counter.clone();
+        async move {
+            Ok::<_, Infallible>(service_fn(move |_req: Request<Body>| {
+                counter.fetch_add(1, Ordering::SeqCst);
+                async move {
+                    Ok::<_, Infallible>(Response::new(Body::from(r#"{"lastUpdateId":1,"bids":[],"asks":[]}"#)))
+                }
+            }))
+        }
+    });
+    let server = Server::bind(&([127, 0, 0, 1], 0).into()).serve(make_svc);
+    let rest_url = format!("http://{}", server.local_addr());
+    tokio::spawn(server);
+
+    let (sender, _receiver) = mpsc::unbounded_channel();
+    let client = BinanceClient::new(&base_url, sender).await.unwrap().with_rest_url(&rest_url);
+    client.start_streams(vec![Subscription::new("ethusdc", StreamKind::Depth)]).await;
+
+    tokio::time::sleep(Duration::from_millis(500)).await;
+    assert_eq!(requests.load(Ordering::SeqCst), 1, "20 buffered diffs should share one snapshot");
+}
+
+#[test]
+fn test_parse_mark_price() {
+    let text = r#"{"e":"markPriceUpdate","E":1700000000000,"s":"ETHUSDC","p":"2051.00","i":"2050.00","P":"2050.50","r":"0.00012","T":1700006400000}"#;
+    let event = parse_event(text, &Subscription::new("ethusdc", StreamKind::MarkPrice)).unwrap().unwrap();
+
+    match event {
+        StreamEvent::MarkPrice(mark) => {
+            assert_eq!((mark.mark_price, mark.index_price), (2051.0, 2050.0));
+            assert_eq!(mark.funding_rate, 0.00012);
+            assert_eq!(mark.next_funding_time, 1700006400000);
+            assert!((mark.premium() - 1.0 / 2050.0).abs() < 1e-12);
+            assert!((mark.basis_bps(2049.0) - 2.0 / 2049.0 * 10_000.0).abs() < 1e-9);
+        }
+        other => panic!("Expected a mark price, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_predicted_funding_rate_clamps_interest_term() {
+    // Small premium: interest-minus-premium lies within the band
+    assert!((predicted_funding_rate(0.00005) - 0.0001).abs() < 1e-12);
+    // Large premium: the clamp caps the pull back towards the interest rate
+    assert!((predicted_funding_rate(0.002) - 0.0015).abs() < 1e-12);
+    assert!((predicted_funding_rate(-0.002) - -0.0015).abs() < 1e-12);
+}
+
+const EXCHANGE_INFO_BODY: &str = r#"{"timezone":"UTC","serverTime":1700000000000,"symbols":[
+    {"symbol":"ETHUSDC","status":"TRADING","baseAsset":"ETH","quoteAsset":"USDC","filters":[
+        {"filterType":"PRICE_FILTER","minPrice":"0.01","maxPrice":"1000000.00","tickSize":"0.01"},
+        {"filterType":"LOT_SIZE","minQty":"0.0001","maxQty":"9000.0","stepSize":"0.0001"},
+        {"filterType":"NOTIONAL","minNotional":"5.0","applyMinToMarket":true,"maxNotional":"9000000.0","applyMaxToMarket":false,"avgPriceMins":5}
+    ]},
+    {"symbol":"LUNAUSDT","status":"BREAK","baseAsset":"LUNA","quoteAsset":"USDT","filters":[]}
+]}"#;
+
+#[test]
+fn test_validate_symbols_against_exchange_info() {
+    let info: ExchangeInfo = serde_json::from_str(EXCHANGE_INFO_BODY).unwrap();
+
+    let metadata = validate_symbols(&info, &["ethusdc".to_string()]).unwrap();
+    let eth = &metadata["ethusdc"];
+    assert_eq!((eth.base_asset.as_str(), eth.quote_asset.as_str()), ("ETH", "USDC"));
+    assert_eq!((eth.tick_size, eth.step_size), (Some(0.01), Some(0.0001)));
+    assert!((eth.in_ticks(0.05).unwrap() - 5.0).abs() < 1e-9);
+
+    assert_eq!(
+        validate_symbols(&info, &["invalidsymbol".to_string()]),
+        Err(SymbolError::Unknown("invalidsymbol".to_string()))
+    );
+    assert_eq!(
+        validate_symbols(&info, &["lunausdt".to_string()]),
+        Err(SymbolError::NotTrading { symbol: "lunausdt".to_string(), status: "BREAK".to_string() })
+    );
+}
+
+#[tokio::test]
+async fn test_client_validates_symbols_from_configured_endpoint() {
+    let rest_url = serve_json(EXCHANGE_INFO_BODY).await;
+    let (sender, _receiver) = mpsc::unbounded_channel();
+    let client = BinanceClient::new("ws://127.0.0.1:1", sender)
+        .await
+        .unwrap()
+        .with_rest_url(&rest_url)
+        .with_exchange_info_path("/fapi/v1/exchangeInfo");
+
+    assert!(client.validate_symbols(&["ethusdc".to_string()]).await.is_ok());
+    let error = client.validate_symbols(&["invalidsymbol".to_string()]).await.unwrap_err();
+    assert_eq!(error.to_string(), "unknown symbol 'invalidsymbol'");
+}
+
+#[test]
+fn test_stream_events_as_market_events() {
+    let text = r#"{"e":"kline","E":1700000000000,"s":"ETHUSDC","k":{"t":1699999980000,"T":1700000039999,"s":"ETHUSDC","i":"1m","o":"2049.00","c":"2050.25","h":"2051.00","l":"2048.50","v":"12.5","x":true}}"#;
+    let kind = StreamKind::Kline(KlineInterval::OneMinute);
+    let event = parse_event(text, &Subscription::new("ethusdc", kind)).unwrap().unwrap();
+    let bar = event.to_market_event(1700000000050).unwrap();
+
+    assert_eq!((bar.source.as_str(), bar.instrument.as_str(), bar.kind()), ("binance", "ethusdc", EventKind::Bar));
+    assert_eq!((bar.price, bar.size, bar.event_time, bar.receive_time), (2050.25, Some(12.5), Some(1700000000000), 1700000000050));
+    match bar.detail {
+        EventDetail::Bar { open, high, low, closed, .. } => assert_eq!((open, high, low, closed), (2049.0, 2051.0, 2048.5, true)),
+        other => panic!("Unexpected detail: {:?}", other),
+    }
+
+    let text = r#"{"e":"trade","E":1700000000001,"s":"ETHUSDC","t":42,"p":"2050.10","q":"0.5","T":1700000000000,"m":true}"#;
+    let event = parse_event(text, &Subscription::new("ethusdc", StreamKind::Trade)).unwrap().unwrap();
+    let trade = event.to_market_event(1700000000002).unwrap();
+    assert_eq!((trade.price, trade.size), (2050.10, Some(0.5)));
+    assert_eq!(trade.detail, EventDetail::Trade { trade_id: Some(42), is_buyer_maker: Some(true) });
+}
+
+#[tokio::test]
+async fn test_client_streams_into_bounded_channel() {
+    let base_url = serve_ws_messages(vec![trade_json(1), trade_json(2), trade_json(3)]).await;
+    let (sender, mut receiver) = bounded(16, BackpressurePolicy::Block);
+    let client = BinanceClient::new(&base_url, sender).await.unwrap();
+    client.start_streams(vec![Subscription::new("ethusdc", StreamKind::Trade)]).await;
+
+    for id in 1..=3 {
+        match tokio::time::timeout(Duration::from_secs(5), receiver.recv()).await.unwrap().unwrap() {
+            StreamEvent::Trade(trade) => assert_eq!(trade.trade_id, id),
+            other => panic!("Unexpected event: {:?}", other),
+        }
+    }
+    assert_eq!(receiver.monitor().stats().dropped, 0);
+}
+
+#[test]
+fn test_stream_event_conflation_keys() {
+    let kind = StreamKind::Trade;
+    let trade = parse_event(&trade_json(1), &Subscription::new("ethusdc", kind)).unwrap().unwrap();
+    assert_eq!(trade.conflation_key().as_deref(), Some("ethusdc:trade"));
+
+    let error = StreamEvent::Error { symbol: "ethusdc".to_string(), error: MessageError::Unknown("?".to_string()) };
+    assert_eq!(error.conflation_key(), None);
+}
+
+#[test]
+fn test_parse_coinbase_market_trades() {
+    let text = r#"{"channel":"market_trades","client_id":"","timestamp":"2024-01-01T00:00:01.5Z","sequence_num":3,"events":[
+        {"type":"snapshot","trades":[{"trade_id":"1","product_id":"ETH-USD","price":"2000.00","size":"1.0","side":"BUY","time":"2023-12-31T23:59:00Z"}]},
+        {"type":"update","trades":[{"trade_id":"2","product_id":"ETH-USD","price":"2050.10","size":"0.25","side":"SELL","time":"2024-01-01T00:00:01.234Z"}]}
+    ]}"#;
+    let ticks = Coinbase.parse(text).unwrap();
+
+    assert_eq!(ticks.len(), 1, "snapshot trades are history, not live ticks");
+    assert_eq!((ticks[0].instrument.as_str(), ticks[0].price, ticks[0].size), ("ETH-USD", 2050.10, Some(0.25)));
+    assert_eq!(ticks[0].timestamp(), 1704067201234);
+    assert!(Coinbase.parse(r#"{"channel":"heartbeats","events":[]}"#).unwrap().is_empty());
+    let heartbeat = r#"{"channel":"heartbeats","client_id":"","timestamp":"2024-01-01T00:00:02.1Z","sequence_num":4,"events":[
+        {"current_time":"2024-01-01 00:00:02.121961769 +0000 UTC m=+91717.525857105","heartbeat_counter":"3049"}
+    ]}"#;
+    assert!(Coinbase.parse(heartbeat).unwrap().is_empty());
+    let subscriptions = r#"{"channel":"subscriptions","client_id":"","timestamp":"2024-01-01T00:00:00.5Z","sequence_num":1,"events":[
+        {"subscriptions":{"market_trades":["ETH-USD"],"heartbeats":["heartbeats"]}}
+    ]}"#;
+    assert!(Coinbase.parse(subscriptions).unwrap().is_empty());
+    assert_eq!(Coinbase.parse(r#"{"type":"error","message":"bad product"}"#), Err("bad product".to_string()));
+}
+
+#[test]
+fn test_parse_kraken_trades() {
+    let text = r#"{"channel":"trade","type":"update","data":[{"symbol":"ETH/USD","side":"buy","price":2050.5,"qty":0.1,"ord_type":"market","trade_id":7,"timestamp":"2024-01-01T00:00:00.100000Z"}]}"#;
+    let ticks = Kraken.parse(text).unwrap();
+
+    assert_eq!((ticks[0].instrument.as_str(), ticks[0].price, ticks[0].timestamp()), ("ETH/USD", 2050.5, 1704067200100));
+    assert!(Kraken.parse(r#"{"channel":"heartbeat"}"#).unwrap().is_empty());
+    let status = r#"{"channel":"status","type":"update","data":[{"version":"2.0.0","system":"online","api_version":"v2","connection_id":12393906104898154338}]}"#;
+    assert!(Kraken.parse(status).unwrap().is_empty());
+    assert!(Kraken.parse(r#"{"method":"subscribe","success":false,"error":"Currency pair not supported"}"#).is_err());
+}
+
+#[test]
+fn test_parse_okx_trades() {
+    let text = r#"{"arg":{"channel":"trades","instId":"ETH-USDT"},"data":[{"instId":"ETH-USDT","tradeId":"130639474","px":"2050.9","sz":"0.12","side":"buy","ts":"1704067200000","count":"3"}]}"#;
+    let ticks = Okx.parse(text).unwrap();
+
+    assert_eq!((ticks[0].instrument.as_str(), ticks[0].price, ticks[0].timestamp()), ("ETH-USDT", 2050.9, 1704067200000));
+    assert!(Okx.parse("pong").unwrap().is_empty());
+    assert!(Okx.parse(r#"{"event":"subscribe","arg":{"channel":"trades","instId":"ETH-USDT"},"connId":"a4d3ae55"}"#).unwrap().is_empty());
+    assert!(Okx.parse(r#"{"event":"error","code":"60012","msg":"Invalid request"}"#).is_err());
+}
+
+#[test]
+fn test_parse_bybit_public_trades() {
+    let text = r#"{"topic":"publicTrade.ETHUSDT","type":"snapshot","ts":1704067200005,"data":[{"T":1704067200001,"s":"ETHUSDT","S":"Buy","v":"0.5","p":"2049.80","L":"PlusTick","i":"2290000000061579537","BT":false}]}"#;
+    let ticks = Bybit.parse(text).unwrap();
+
+    assert_eq!((ticks[0].instrument.as_str(), ticks[0].price, ticks[0].timestamp()), ("ETHUSDT", 2049.8, 1704067200001));
+    assert!(Bybit.parse(r#"{"success":true,"ret_msg":"pong","conn_id":"x","op":"ping"}"#).unwrap().is_empty());
+    assert!(Bybit.parse(r#"{"success":false,"ret_msg":"Invalid symbol","conn_id":"x","op":"subscribe"}"#).is_err());
+
+    let symbols: Vec<String> = (0..12).map(|i| format!("SYM{}USDT", i)).collect();
+    assert_eq!(Bybit.subscribe_messages(&symbols).len(), 2);
+}
+
+#[test]
+fn test_venue_source_names() {
+    for venue in ["coinbase", "kraken", "okx", "bybit"] {
+        assert_eq!(venue_source(venue).unwrap().venue(), venue);
+    }
+    assert!(venue_source("mtgox").is_none());
+}
+
+#[tokio::test]
+async fn test_ws_price_source_subscribes_and_streams_ticks() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("ws://{}", listener.local_addr().unwrap());
+
+    tokio::spawn(async move {
+        let (tcp, _) = listener.accept().await.unwrap();
+        let mut ws = tokio_tungstenite::accept_async(tcp).await.unwrap();
+        // Answer the subscription with a trade for the requested instrument
+        while let Some(Ok(Message::Text(request))) = ws.next().await {
+            let request: serde_json::Value = serde_json::from_str(&request).unwrap();
+            let inst_id = request["args"][0]["instId"].as_str().unwrap().to_string();
+            let trade = format!(
+                r#"{{"arg":{{"channel":"trades","instId":"{0}"}},"data":[{{"instId":"{0}","tradeId":"1","px":"2051.0","sz":"1","side":"buy","ts":"1704067200000"}}]}}"#,
+                inst_id
+            );
+            ws.send(Message::text(trade)).await.unwrap();
+        }
+    });
+
+    let mut source = WsPriceSource::new(Okx).with_url(&url);
+    assert_eq!(source.subscribe(&["ETH-USDC".to_string()]).await, Err(SourceError::NotConnected));
+    source.connect().await.unwrap();
+    source.subscribe(&["ETH-USDC".to_string()]).await.unwrap();
+
+    let mut ticks = source.ticks().unwrap();
+    let tick = tokio::time::timeout(Duration::from_secs(5), ticks.next()).await.unwrap().unwrap();
+    assert_eq!((tick.source.as_str(), tick.instrument.as_str(), tick.price), ("okx", "ETH-USDC", 2051.0));
+
+    let health = source.health();
+    assert_eq!((health.state, health.last_tick), (ConnectionState::Connected, Some(1704067200000)));
+    assert!(source.ticks().is_err());
+}
+
+#[tokio::test]
+async fn test_binance_client_as_price_source() {
+    let base_url = serve_ws_messages(vec![trade_json(1), trade_json(2)]).await;
+    let rest_url = serve_json(EXCHANGE_INFO_BODY).await;
+    let (sender, _receiver) = mpsc::unbounded_channel();
+    let mut client = BinanceClient::new(&base_url, sender).await.unwrap().with_rest_url(&rest_url);
+
+    let mut ticks = client.ticks().unwrap();
+    client.connect().await.unwrap();
+    client.subscribe(&["ETHUSDC".to_string()]).await.unwrap();
+
+    for id in 1..=2 {
+        let tick = tokio::time::timeout(Duration::from_secs(5), ticks.next()).await.unwrap().unwrap();
+        assert_eq!((tick.source.as_str(), tick.instrument.as_str()), ("binance", "ETHUSDC"));
+        assert_eq!(tick.timestamp(), 1700000000000 + id);
+    }
+}
+
+#[test]
+fn test_symbol_interner_assigns_stable_ids() {
+    let mut interner = SymbolInterner::new();
+    let eth = interner.intern("ethusdc", "1m");
+    let btc = interner.intern("btcusdc", "1m");
+
+    assert_eq!(interner.intern("ethusdc", "1m"), eth);
+    assert_eq!((eth.index(), btc.index()), (0, 1));
+    assert_eq!(interner.get("btcusdc", "1m"), Some(btc));
+    assert_eq!(interner.name(btc).map(|(symbol, series)| (&**symbol, &**series)), Some(("btcusdc", "1m")));
+    assert_eq!((interner.get("solusdc", "1m"), interner.len()), (None, 2));
+
+    // Other intervals and trade streams of a symbol are separate series
+    let eth_hourly = interner.intern("ethusdc", "1h");
+    let eth_trades = interner.intern("ethusdc", "trade");
+    assert_eq!((eth_hourly.index(), eth_trades.index()), (2, 3));
+    assert_eq!(interner.get("ethusdc", "aggTrade"), None);
+}
+
+#[tokio::test]
+async fn test_sharded_calculators_match_single_calculator() {
+    let results = Arc::new(std::sync::Mutex::new(HashMap::new()));
+    let sink_results = Arc::clone(&results);
+    let mut calculators = ShardedCalculators::spawn(
+        3,
+        60,
+        8,
+        Arc::new(move |symbol: &str, _: &str, volatility: Option<f64>| {
+            sink_results.lock().unwrap().insert(symbol.to_string(), volatility);
+        }),
+    );
+
+    let symbols: Vec<String> = (0..7).map(|i| format!("sym{}usdc", i)).collect();
+    let mut expected = HashMap::new();
+    let start = SystemTime::now();
+    for (i, symbol) in symbols.iter().enumerate() {
+        let id = calculators.intern(symbol, "1m").await;
+        let mut reference = VolatilityCalculator::new(60);
+        for step in 0..12u64 {
+            let timestamp = start + Duration::from_secs(step);
+            let price = 100.0 * (i + 1) as f64 + (step % 4) as f64;
+            calculators.add(id, timestamp, price).await;
+            reference.add_value_at(timestamp, price);
+        }
+        expected.insert(symbol.clone(), reference.calculate_volatility());
+    }
+
+    // Symbols are spread round-robin over the workers
+    assert_eq!(calculators.shard_of("sym0usdc", "1m"), Some(0));
+    assert_eq!(calculators.shard_of("sym4usdc", "1m"), Some(1));
+    assert_eq!(calculators.shard_of("sym4usdc", "1h"), None);
+    assert_eq!(calculators.shard_of("unknown", "1m"), None);
+
+    calculators.shutdown().await;
+    assert_eq!(*results.lock().unwrap(), expected);
+}
+
@@ -0,0 +1,138 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+use web3::types::Address;
+
+use super::events::{DecodeError, PoolEvent, PoolEventKind};
+
+/// Event counts and liquidity state of a pool, built from its logs.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PoolActivity {
+    pub events: HashMap<PoolEventKind, u64>,
+    pub decode_failures: HashMap<&'static str, u64>, // By event, "unknown" for unrecognized topics
+    pub liquidity: Option<u128>, // In-range liquidity, from the latest swap plus later mints and burns
+    pub tick: Option<i32>,       // Current tick as of the latest swap
+    pub net_liquidity: i128,     // Liquidity minted minus burned since startup
+    pub tick_mismatches: u64,    // Swaps whose tick disagrees with their sqrtPriceX96
+    pub retracted_swaps: u64,    // Swaps sent, then dropped by a reorg
+}
+
+impl PoolActivity {
+    /// Counts the event and applies mints and burns to the liquidity state.
+    pub fn apply(&mut self, event: &PoolEvent) {
+        *self.events.entry(event.kind()).or_default() += 1;
+        match event {
+            PoolEvent::Swap(swap) => {
+                self.liquidity = Some(swap.liquidity);
+                self.tick = Some(swap.tick);
+                if !swap.tick_consistent() {
+                    self.tick_mismatches += 1;
+                }
+            }
+            PoolEvent::Mint(mint) => {
+                self.net_liquidity += mint.amount as i128;
+                if self.in_range(mint.tick_lower, mint.tick_upper) {
+                    self.liquidity = self.liquidity.map(|l| l.saturating_add(mint.amount));
+                }
+            }
+            PoolEvent::Burn(burn) => {
+                self.net_liquidity -= burn.amount as i128;
+                if self.in_range(burn.tick_lower, burn.tick_upper) {
+                    self.liquidity = self.liquidity.map(|l| l.saturating_sub(burn.amount));
+                }
+            }
+            PoolEvent::Collect(_) | PoolEvent::Flash(_) => {}
+        }
+    }
+
+    pub fn record_failure(&mut self, error: &DecodeError) {
+        *self.decode_failures.entry(error.event()).or_default() += 1;
+    }
+
+    // A position is active when the current tick is in [lower, upper)
+    fn in_range(&self, tick_lower: i32, tick_upper: i32) -> bool {
+        self.tick.is_some_and(|tick| tick_lower <= tick && tick < tick_upper)
+    }
+}
+
+/// Activity of every pool on a subscription, plus what happened to the
+/// subscription itself, which the pools share.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SubscriptionActivity {
+    pub pools: HashMap<Address, PoolActivity>,
+    pub log_gaps: u64,        // Resubscriptions that found missed logs
+    pub backfilled_logs: u64, // Logs recovered with eth_getLogs
+    pub reorgs: u64,
+    pub reorg_depths: BTreeMap<u64, u64>, // Reorgs by depth in blocks
+    pub header_fetches: u64,  // Block headers requested after a cache miss
+}
+
+impl SubscriptionActivity {
+    /// The activity of the pool at `address`, empty until it logs something.
+    pub fn pool(&mut self, address: Address) -> &mut PoolActivity {
+        self.pools.entry(address).or_default()
+    }
+
+    pub fn record_reorg(&mut self, depth: u64) {
+        self.reorgs += 1;
+        *self.reorg_depths.entry(depth).or_default() += 1;
+    }
+
+    /// Counts a gap in the subscription filled with `logs` fetched logs.
+    pub fn record_gap(&mut self, logs: usize) {
+        self.log_gaps += 1;
+        self.backfilled_logs += logs as u64;
+    }
+}
+
+/// Shared, cheaply cloned [`SubscriptionActivity`] updated by the log
+/// subscription.
+#[derive(Debug, Clone, Default)]
+pub struct ActivityTracker {
+    inner: Arc<Mutex<SubscriptionActivity>>,
+}
+
+impl ActivityTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn snapshot(&self) -> SubscriptionActivity {
+        self.inner.lock().map(|activity| activity.clone()).unwrap_or_default()
+    }
+
+    pub fn record(&self, pool: Address, event: &PoolEvent) {
+        if let Ok(mut activity) = self.inner.lock() {
+            activity.pool(pool).apply(event);
+        }
+    }
+
+    pub fn record_failure(&self, pool: Address, error: &DecodeError) {
+        if let Ok(mut activity) = self.inner.lock() {
+            activity.pool(pool).record_failure(error);
+        }
+    }
+
+    pub fn record_gap(&self, logs: usize) {
+        if let Ok(mut activity) = self.inner.lock() {
+            activity.record_gap(logs);
+        }
+    }
+
+    pub fn record_reorg(&self, depth: u64) {
+        if let Ok(mut activity) = self.inner.lock() {
+            activity.record_reorg(depth);
+        }
+    }
+
+    pub fn record_retraction(&self, pool: Address) {
+        if let Ok(mut activity) = self.inner.lock() {
+            activity.pool(pool).retracted_swaps += 1;
+        }
+    }
+
+    pub fn record_header_fetch(&self) {
+        if let Ok(mut activity) = self.inner.lock() {
+            activity.header_fetches += 1;
+        }
+    }
+}
@@ -0,0 +1,65 @@
+/// Widest `eth_getLogs` block range requested by default.
+pub const DEFAULT_MAX_CHUNK_BLOCKS: u64 = 2_000;
+
+/// Slowest Ethereum block cadence since the merge: blocks are at least this
+/// far apart, so this many seconds per block over-covers a lookback.
+pub const BLOCK_TIME_SECS: u64 = 12;
+
+/// Splits a block range into `eth_getLogs` requests, halving the chunk when
+/// the provider rejects a request as too large and growing it back after
+/// each success.
+#[derive(Debug, Clone)]
+pub struct AdaptiveChunker {
+    size: u64,
+    max: u64,
+}
+
+impl AdaptiveChunker {
+    pub fn new(max: u64) -> Self {
+        let max = max.max(1);
+        Self { size: max, max }
+    }
+
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// Last block of the next request starting at `from`, capped at `to`.
+    pub fn end_for(&self, from: u64, to: u64) -> u64 {
+        from.saturating_add(self.size - 1).min(to)
+    }
+
+    /// Halves the chunk; `false` once it is a single block and cannot shrink.
+    pub fn shrink(&mut self) -> bool {
+        if self.size == 1 {
+            return false;
+        }
+        self.size = (self.size / 2).max(1);
+        true
+    }
+
+    pub fn grow(&mut self) {
+        self.size = self.size.saturating_mul(2).min(self.max);
+    }
+}
+
+impl Default for AdaptiveChunker {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_CHUNK_BLOCKS)
+    }
+}
+
+/// Whether a provider error means the range returned too many logs or spans
+/// too many blocks, as worded by Infura, Alchemy, QuickNode and others.
+pub fn is_too_many_results(message: &str) -> bool {
+    let message = message.to_lowercase();
+    ["more than", "too many", "exceed", "too wide", "limited to", "-32005"]
+        .iter()
+        .any(|pattern| message.contains(pattern))
+}
+
+/// First block to backfill so `lookback_secs` of history ending at `head`
+/// is covered.
+pub fn backfill_start(head: u64, lookback_secs: u64) -> u64 {
+    head.saturating_sub(lookback_secs.div_ceil(BLOCK_TIME_SECS))
+}
@@ -0,0 +1,45 @@
+use web3::types::Log;
+
+/// Position of a log in the chain, ordered by block then log index.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct LogCursor {
+    pub block: u64,
+    pub log_index: u64,
+}
+
+impl LogCursor {
+    /// The log's position, `None` for a pending log.
+    pub fn of(log: &Log) -> Option<Self> {
+        Some(Self {
+            block: log.block_number?.as_u64(),
+            log_index: log.log_index?.low_u64(),
+        })
+    }
+
+    /// The position just before this one, `None` before the first log.
+    pub fn previous(&self) -> Option<Self> {
+        match (self.block, self.log_index) {
+            (0, 0) => None,
+            (block, 0) => Some(Self {
+                block: block - 1,
+                log_index: u64::MAX,
+            }),
+            (block, log_index) => Some(Self {
+                block,
+                log_index: log_index - 1,
+            }),
+        }
+    }
+}
+
+/// Logs positioned strictly after `cursor`, or all positioned logs without
+/// one, in chain order.
+pub fn logs_after(cursor: Option<LogCursor>, logs: Vec<Log>) -> Vec<Log> {
+    let mut logs: Vec<(LogCursor, Log)> = logs
+        .into_iter()
+        .filter_map(|log| LogCursor::of(&log).map(|position| (position, log)))
+        .filter(|(position, _)| Some(*position) > cursor)
+        .collect();
+    logs.sort_by_key(|(position, _)| *position);
+    logs.into_iter().map(|(_, log)| log).collect()
+}
@@ -0,0 +1,273 @@
+use futures_util::StreamExt;
+use std::collections::HashSet;
+use std::convert::TryFrom;
+use tokio::time::{interval, Duration, MissedTickBehavior};
+use tracing::{info, warn};
+use web3::ethabi::{decode, ParamType, Token};
+use web3::signing::keccak256;
+use web3::types::{Address, FilterBuilder, Log, H256};
+
+use super::events::signed;
+use super::metadata::{calls, decode_word, MetadataCall};
+use super::InfuraClient;
+use crate::pool::PoolConfig;
+
+/// The Uniswap V3 factory on mainnet.
+pub const FACTORY_ADDRESS: &str = "0x1F98431c8aD98523631AE4a59f267346ea31F984";
+
+/// Block the factory was deployed in; no pool is older.
+pub const FACTORY_DEPLOY_BLOCK: u64 = 12_369_621;
+
+/// Widest `eth_getLogs` block range when scanning the factory by default.
+/// Pools are created rarely, so wide ranges stay under result limits.
+pub const DEFAULT_DISCOVERY_CHUNK_BLOCKS: u64 = 100_000;
+
+/// How often pools short of liquidity are checked again by default.
+pub const DEFAULT_RECHECK_INTERVAL: Duration = Duration::from_secs(300);
+
+/// A factory `PoolCreated` event.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PoolCreated {
+    pub token0: Address,
+    pub token1: Address,
+    pub fee: u32,
+    pub tick_spacing: i32,
+    pub pool: Address,
+}
+
+impl PoolCreated {
+    pub const SIGNATURE: &'static str = "PoolCreated(address,address,uint24,int24,address)";
+
+    pub fn topic0() -> H256 {
+        H256(keccak256(Self::SIGNATURE.as_bytes()))
+    }
+
+    /// Decodes a `PoolCreated` log: the tokens and fee are indexed topics,
+    /// the tick spacing and pool address are in the data.
+    pub fn decode(log: &Log) -> Option<Self> {
+        let [topic0, token0, token1, fee] = log.topics.as_slice() else { return None };
+        if *topic0 != Self::topic0() {
+            return None;
+        }
+        let data = decode(&[ParamType::Int(24), ParamType::Address], &log.data.0).ok()?;
+        let [Token::Int(tick_spacing), Token::Address(pool)] = data.as_slice() else { return None };
+        let fee = decode_word(fee.as_bytes())?;
+        if fee.bits() > 24 {
+            return None;
+        }
+        Some(Self {
+            token0: Address::from(*token0),
+            token1: Address::from(*token1),
+            fee: fee.low_u32(),
+            tick_spacing: i32::try_from(signed(*tick_spacing)).ok()?,
+            pool: *pool,
+        })
+    }
+}
+
+/// Which created pools discovery monitors. An empty allowlist or fee tier
+/// set allows any.
+#[derive(Debug, Clone, Default)]
+pub struct DiscoveryFilter {
+    pub tokens: HashSet<Address>, // Both of a pool's tokens must be listed
+    pub fee_tiers: HashSet<u32>,
+    pub min_liquidity: f64, // In-range liquidity a pool needs before it is monitored, in whole tokens
+}
+
+impl DiscoveryFilter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_tokens(mut self, tokens: impl IntoIterator<Item = Address>) -> Self {
+        self.tokens = tokens.into_iter().collect();
+        self
+    }
+
+    pub fn with_fee_tiers(mut self, fee_tiers: impl IntoIterator<Item = u32>) -> Self {
+        self.fee_tiers = fee_tiers.into_iter().collect();
+        self
+    }
+
+    /// Minimum in-range liquidity in whole tokens, as
+    /// [`PoolConfig::whole_token_liquidity`] measures it, so one floor
+    /// means the same depth whatever the tokens' decimals.
+    pub fn with_min_liquidity(mut self, liquidity: f64) -> Self {
+        self.min_liquidity = liquidity;
+        self
+    }
+
+    /// Whether a created pool's tokens and fee tier pass. Its liquidity is
+    /// checked separately, on chain.
+    pub fn accepts(&self, created: &PoolCreated) -> bool {
+        let listed = |token: &Address| self.tokens.is_empty() || self.tokens.contains(token);
+        listed(&created.token0)
+            && listed(&created.token1)
+            && (self.fee_tiers.is_empty() || self.fee_tiers.contains(&created.fee))
+    }
+
+    /// Whether `pool`'s raw in-range `liquidity` reaches the floor.
+    pub fn has_liquidity(&self, pool: &PoolConfig, liquidity: u128) -> bool {
+        pool.whole_token_liquidity(liquidity) >= self.min_liquidity
+    }
+}
+
+/// Discovery mode: finds pools in the factory's `PoolCreated` events and adds
+/// those passing a [`DiscoveryFilter`] to a client's watched pools. It keeps
+/// its place, so a run after the subscription ends resumes the scan.
+#[derive(Debug)]
+pub struct PoolDiscovery {
+    filter: DiscoveryFilter,
+    factory: Address,
+    next_block: u64, // First block not scanned yet
+    max_chunk_blocks: u64,
+    recheck: Duration,
+    pending: HashSet<Address>, // Matching pools short of liquidity
+}
+
+impl PoolDiscovery {
+    /// Discovers pools from the mainnet factory, scanning from its deployment.
+    pub fn new(filter: DiscoveryFilter) -> Self {
+        Self {
+            filter,
+            factory: FACTORY_ADDRESS.parse().unwrap(),
+            next_block: FACTORY_DEPLOY_BLOCK,
+            max_chunk_blocks: DEFAULT_DISCOVERY_CHUNK_BLOCKS,
+            recheck: DEFAULT_RECHECK_INTERVAL,
+            pending: HashSet::new(),
+        }
+    }
+
+    /// Scans another factory's events, starting at `from_block`.
+    pub fn with_factory(mut self, factory: Address, from_block: u64) -> Self {
+        self.factory = factory;
+        self.next_block = from_block;
+        self
+    }
+
+    pub fn with_max_chunk_blocks(mut self, blocks: u64) -> Self {
+        self.max_chunk_blocks = blocks;
+        self
+    }
+
+    /// How often pools that matched but lacked liquidity are checked again.
+    pub fn with_recheck(mut self, interval: Duration) -> Self {
+        self.recheck = interval;
+        self
+    }
+
+    /// Pools that match the filter but are short of liquidity so far.
+    pub fn pending(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Scans the factory from where the last run stopped up to the head,
+    /// then follows new pools until the subscription ends.
+    pub async fn run(&mut self, client: &InfuraClient) -> web3::Result<()> {
+        let filter = FilterBuilder::default()
+            .address(vec![self.factory])
+            .topics(Some(vec![PoolCreated::topic0()]), None, None, None);
+        // Subscribe before scanning, so no pool falls between the two
+        let mut created = client.web3().eth_subscribe().subscribe_logs(filter.clone().build()).await?;
+        let head = client.web3().eth().block_number().await?.as_u64();
+        if self.next_block <= head {
+            info!("Scanning factory blocks {}..={} for pools", self.next_block, head);
+            let logs = client
+                .filtered_logs_in_range(filter, self.max_chunk_blocks, self.next_block, head)
+                .await?;
+            self.consider(client, &logs).await?;
+            self.next_block = head + 1;
+        }
+
+        let mut recheck = interval(self.recheck);
+        recheck.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        recheck.tick().await; // The first tick completes at once
+        loop {
+            tokio::select! {
+                log = created.next() => match log {
+                    Some(Ok(log)) => {
+                        // Rescanned on the next run if it stops mid-block
+                        let block = log.block_number.map(|block| block.as_u64());
+                        self.consider(client, &[log]).await?;
+                        if let Some(block) = block {
+                            self.next_block = self.next_block.max(block);
+                        }
+                    }
+                    Some(Err(e)) => warn!("Error reading factory log: {:?}", e),
+                    None => return Ok(()),
+                },
+                _ = recheck.tick() => {
+                    let pending = self.pending.drain().collect();
+                    self.admit(client, pending).await?;
+                }
+            }
+        }
+    }
+
+    // The pools created in `logs` that match the filter and are not watched yet
+    async fn consider(&mut self, client: &InfuraClient, logs: &[Log]) -> web3::Result<()> {
+        let registry = client.registry();
+        let candidates = logs
+            .iter()
+            .filter(|log| log.removed != Some(true))
+            .filter_map(PoolCreated::decode)
+            .filter(|created| self.filter.accepts(created) && !registry.contains(&created.pool))
+            .map(|created| created.pool)
+            .collect();
+        self.admit(client, candidates).await
+    }
+
+    // Watches the candidates with enough liquidity and keeps the rest pending.
+    // Resolved first: the floor is in whole tokens, so it needs their decimals
+    async fn admit(&mut self, client: &InfuraClient, candidates: Vec<Address>) -> web3::Result<()> {
+        if candidates.is_empty() {
+            return Ok(());
+        }
+        let mut pools = client.resolve_pools(&candidates).await?;
+        if self.filter.min_liquidity > 0.0 {
+            let addresses: Vec<Address> = pools.iter().map(|pool| pool.address).collect();
+            let liquidity = client.pool_liquidity(&addresses).await?;
+            let mut ready = Vec::new();
+            for (pool, liquidity) in pools.into_iter().zip(liquidity) {
+                if liquidity.is_some_and(|liquidity| self.filter.has_liquidity(&pool, liquidity)) {
+                    ready.push(pool);
+                } else {
+                    self.pending.insert(pool.address);
+                }
+            }
+            pools = ready;
+        }
+        if pools.is_empty() {
+            return Ok(());
+        }
+
+        let registry = client.registry();
+        let added = registry.extend(pools);
+        if added > 0 {
+            info!(
+                "Discovered {} pools; monitoring {}, {} short of liquidity",
+                added,
+                registry.len(),
+                self.pending.len()
+            );
+        }
+        Ok(())
+    }
+}
+
+impl InfuraClient {
+    /// Each pool's current in-range liquidity, `None` where the call failed.
+    pub async fn pool_liquidity(&self, pools: &[Address]) -> web3::Result<Vec<Option<u128>>> {
+        let outputs = self.call_batch(calls(pools, &[MetadataCall::Liquidity])).await?;
+        Ok(outputs
+            .iter()
+            .map(|output| {
+                output
+                    .as_deref()
+                    .and_then(decode_word)
+                    .filter(|liquidity| liquidity.bits() <= 128)
+                    .map(|liquidity| liquidity.low_u128())
+            })
+            .collect())
+    }
+}
@@ -0,0 +1,324 @@
+use std::fmt;
+use web3::ethabi::{decode, ParamType, Token};
+use web3::signing::keccak256;
+use web3::types::{Address, Log, H256, U256 as Word};
+
+use crate::pool::tick_matches_sqrt_price;
+
+/// The Uniswap V3 pool events the client understands.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PoolEventKind {
+    Swap,
+    Mint,
+    Burn,
+    Collect,
+    Flash,
+}
+
+impl PoolEventKind {
+    pub const ALL: [PoolEventKind; 5] = [
+        PoolEventKind::Swap,
+        PoolEventKind::Mint,
+        PoolEventKind::Burn,
+        PoolEventKind::Collect,
+        PoolEventKind::Flash,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PoolEventKind::Swap => "swap",
+            PoolEventKind::Mint => "mint",
+            PoolEventKind::Burn => "burn",
+            PoolEventKind::Collect => "collect",
+            PoolEventKind::Flash => "flash",
+        }
+    }
+
+    /// Canonical Solidity signature, whose keccak256 hash is the log's topic0.
+    pub fn signature(&self) -> &'static str {
+        match self {
+            PoolEventKind::Swap => "Swap(address,address,int256,int256,uint160,uint128,int24)",
+            PoolEventKind::Mint => "Mint(address,address,int24,int24,uint128,uint256,uint256)",
+            PoolEventKind::Burn => "Burn(address,int24,int24,uint128,uint256,uint256)",
+            PoolEventKind::Collect => "Collect(address,address,int24,int24,uint128,uint128)",
+            PoolEventKind::Flash => "Flash(address,address,uint256,uint256,uint256,uint256)",
+        }
+    }
+
+    pub fn topic0(&self) -> H256 {
+        H256(keccak256(self.signature().as_bytes()))
+    }
+
+    pub fn from_topic0(topic: &H256) -> Option<Self> {
+        Self::ALL.iter().copied().find(|kind| kind.topic0() == *topic)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Swap {
+    pub sender: Address,
+    pub recipient: Address,
+    pub amount0: i128,
+    pub amount1: i128,
+    pub sqrt_price_x96: Word,
+    pub liquidity: u128, // In-range liquidity after the swap
+    pub tick: i32,
+}
+
+impl Swap {
+    /// Whether the reported tick agrees with `sqrt_price_x96`.
+    pub fn tick_consistent(&self) -> bool {
+        tick_matches_sqrt_price(self.tick, self.sqrt_price_x96)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mint {
+    pub sender: Address,
+    pub owner: Address,
+    pub tick_lower: i32,
+    pub tick_upper: i32,
+    pub amount: u128, // Liquidity added to the position
+    pub amount0: Word,
+    pub amount1: Word,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Burn {
+    pub owner: Address,
+    pub tick_lower: i32,
+    pub tick_upper: i32,
+    pub amount: u128, // Liquidity removed from the position
+    pub amount0: Word,
+    pub amount1: Word,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Collect {
+    pub owner: Address,
+    pub recipient: Address,
+    pub tick_lower: i32,
+    pub tick_upper: i32,
+    pub amount0: u128,
+    pub amount1: u128,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Flash {
+    pub sender: Address,
+    pub recipient: Address,
+    pub amount0: Word,
+    pub amount1: Word,
+    pub paid0: Word,
+    pub paid1: Word,
+}
+
+/// A decoded Uniswap V3 pool log.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PoolEvent {
+    Swap(Swap),
+    Mint(Mint),
+    Burn(Burn),
+    Collect(Collect),
+    Flash(Flash),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DecodeError {
+    /// The log has no topics (anonymous event).
+    MissingTopic,
+    /// topic0 is not a pool event signature.
+    UnknownTopic(H256),
+    /// Indexed topics or data do not match the event's ABI.
+    Malformed(PoolEventKind),
+}
+
+impl DecodeError {
+    /// Event the failure is attributed to, `"unknown"` when not recognized.
+    pub fn event(&self) -> &'static str {
+        match self {
+            DecodeError::Malformed(kind) => kind.as_str(),
+            DecodeError::MissingTopic | DecodeError::UnknownTopic(_) => "unknown",
+        }
+    }
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeError::MissingTopic => write!(f, "log has no topics"),
+            DecodeError::UnknownTopic(topic) => write!(f, "unknown event topic {:?}", topic),
+            DecodeError::Malformed(kind) => write!(f, "malformed {} log", kind.as_str()),
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+impl PoolEvent {
+    pub fn kind(&self) -> PoolEventKind {
+        match self {
+            PoolEvent::Swap(_) => PoolEventKind::Swap,
+            PoolEvent::Mint(_) => PoolEventKind::Mint,
+            PoolEvent::Burn(_) => PoolEventKind::Burn,
+            PoolEvent::Collect(_) => PoolEventKind::Collect,
+            PoolEvent::Flash(_) => PoolEventKind::Flash,
+        }
+    }
+
+    /// Decodes a pool log, picking the event by its topic0.
+    pub fn decode(log: &Log) -> Result<PoolEvent, DecodeError> {
+        let topic0 = log.topics.first().ok_or(DecodeError::MissingTopic)?;
+        let kind = PoolEventKind::from_topic0(topic0).ok_or(DecodeError::UnknownTopic(*topic0))?;
+        decode_kind(kind, log).ok_or(DecodeError::Malformed(kind))
+    }
+}
+
+fn decode_kind(kind: PoolEventKind, log: &Log) -> Option<PoolEvent> {
+    let topics = &log.topics[1..];
+    let data = &log.data.0;
+    let event = match kind {
+        PoolEventKind::Swap => {
+            let [sender, recipient] = topics else { return None };
+            let tokens = decode(
+                &[
+                    ParamType::Int(256),  // amount0
+                    ParamType::Int(256),  // amount1
+                    ParamType::Uint(160), // sqrtPriceX96
+                    ParamType::Uint(128), // liquidity
+                    ParamType::Int(24),   // tick
+                ],
+                data,
+            )
+            .ok()?;
+            let [Token::Int(amount0), Token::Int(amount1), Token::Uint(sqrt_price_x96), Token::Uint(liquidity), Token::Int(tick)] =
+                tokens.as_slice()
+            else {
+                return None;
+            };
+            PoolEvent::Swap(Swap {
+                sender: topic_address(sender),
+                recipient: topic_address(recipient),
+                amount0: signed(*amount0),
+                amount1: signed(*amount1),
+                sqrt_price_x96: *sqrt_price_x96,
+                liquidity: liquidity.low_u128(),
+                tick: signed(*tick) as i32,
+            })
+        }
+        PoolEventKind::Mint => {
+            let [owner, tick_lower, tick_upper] = topics else { return None };
+            let tokens = decode(
+                &[
+                    ParamType::Address,   // sender
+                    ParamType::Uint(128), // amount
+                    ParamType::Uint(256), // amount0
+                    ParamType::Uint(256), // amount1
+                ],
+                data,
+            )
+            .ok()?;
+            let [Token::Address(sender), Token::Uint(amount), Token::Uint(amount0), Token::Uint(amount1)] = tokens.as_slice() else {
+                return None;
+            };
+            PoolEvent::Mint(Mint {
+                sender: *sender,
+                owner: topic_address(owner),
+                tick_lower: topic_tick(tick_lower),
+                tick_upper: topic_tick(tick_upper),
+                amount: amount.low_u128(),
+                amount0: *amount0,
+                amount1: *amount1,
+            })
+        }
+        PoolEventKind::Burn => {
+            let [owner, tick_lower, tick_upper] = topics else { return None };
+            let tokens = decode(
+                &[
+                    ParamType::Uint(128), // amount
+                    ParamType::Uint(256), // amount0
+                    ParamType::Uint(256), // amount1
+                ],
+                data,
+            )
+            .ok()?;
+            let [Token::Uint(amount), Token::Uint(amount0), Token::Uint(amount1)] = tokens.as_slice() else {
+                return None;
+            };
+            PoolEvent::Burn(Burn {
+                owner: topic_address(owner),
+                tick_lower: topic_tick(tick_lower),
+                tick_upper: topic_tick(tick_upper),
+                amount: amount.low_u128(),
+                amount0: *amount0,
+                amount1: *amount1,
+            })
+        }
+        PoolEventKind::Collect => {
+            let [owner, tick_lower, tick_upper] = topics else { return None };
+            let tokens = decode(
+                &[
+                    ParamType::Address,   // recipient
+                    ParamType::Uint(128), // amount0
+                    ParamType::Uint(128), // amount1
+                ],
+                data,
+            )
+            .ok()?;
+            let [Token::Address(recipient), Token::Uint(amount0), Token::Uint(amount1)] = tokens.as_slice() else {
+                return None;
+            };
+            PoolEvent::Collect(Collect {
+                owner: topic_address(owner),
+                recipient: *recipient,
+                tick_lower: topic_tick(tick_lower),
+                tick_upper: topic_tick(tick_upper),
+                amount0: amount0.low_u128(),
+                amount1: amount1.low_u128(),
+            })
+        }
+        PoolEventKind::Flash => {
+            let [sender, recipient] = topics else { return None };
+            let tokens = decode(
+                &[
+                    ParamType::Uint(256), // amount0
+                    ParamType::Uint(256), // amount1
+                    ParamType::Uint(256), // paid0
+                    ParamType::Uint(256), // paid1
+                ],
+                data,
+            )
+            .ok()?;
+            let [Token::Uint(amount0), Token::Uint(amount1), Token::Uint(paid0), Token::Uint(paid1)] = tokens.as_slice() else {
+                return None;
+            };
+            PoolEvent::Flash(Flash {
+                sender: topic_address(sender),
+                recipient: topic_address(recipient),
+                amount0: *amount0,
+                amount1: *amount1,
+                paid0: *paid0,
+                paid1: *paid1,
+            })
+        }
+    };
+    Some(event)
+}
+
+// Indexed addresses are left-padded to 32 bytes
+fn topic_address(topic: &H256) -> Address {
+    Address::from_slice(&topic.as_bytes()[12..])
+}
+
+fn topic_tick(topic: &H256) -> i32 {
+    signed(Word::from_big_endian(topic.as_bytes())) as i32
+}
+
+// Two's-complement int256 word to i128; swap amounts and ticks fit
+pub(crate) fn signed(value: Word) -> i128 {
+    if value.bit(255) {
+        -((!value).low_u128() as i128) - 1
+    } else {
+        value.low_u128() as i128
+    }
+}
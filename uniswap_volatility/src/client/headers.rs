@@ -0,0 +1,78 @@
+use std::collections::{HashMap, VecDeque};
+use web3::types::H256;
+
+/// Headers kept by default, a few hours of mainnet blocks.
+pub const DEFAULT_HEADER_CACHE_SIZE: usize = 1_024;
+
+/// When and where a block was produced.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BlockTime {
+    pub number: u64,
+    pub timestamp: u64, // Seconds since the Unix epoch
+}
+
+impl BlockTime {
+    pub fn timestamp_millis(&self) -> u64 {
+        self.timestamp * 1_000
+    }
+}
+
+/// Recent block headers by hash, evicting the oldest inserted once full.
+/// Keyed by hash rather than number so an orphaned block never lends its
+/// time to its replacement.
+#[derive(Debug)]
+pub struct HeaderCache {
+    capacity: usize,
+    headers: HashMap<H256, BlockTime>,
+    order: VecDeque<H256>,
+}
+
+impl HeaderCache {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            headers: HashMap::new(),
+            order: VecDeque::new(),
+        }
+    }
+
+    pub fn get(&self, hash: &H256) -> Option<BlockTime> {
+        self.headers.get(hash).copied()
+    }
+
+    pub fn insert(&mut self, hash: H256, time: BlockTime) {
+        if self.headers.insert(hash, time).is_some() {
+            return;
+        }
+        self.order.push_back(hash);
+        while self.order.len() > self.capacity {
+            if let Some(oldest) = self.order.pop_front() {
+                self.headers.remove(&oldest);
+            }
+        }
+    }
+
+    /// The newest cached block numbered `number` or lower, whose time stands
+    /// in for a block whose header could not be fetched.
+    pub fn latest_at_or_before(&self, number: u64) -> Option<BlockTime> {
+        self.headers
+            .values()
+            .filter(|time| time.number <= number)
+            .max_by_key(|time| time.number)
+            .copied()
+    }
+
+    pub fn len(&self) -> usize {
+        self.headers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.headers.is_empty()
+    }
+}
+
+impl Default for HeaderCache {
+    fn default() -> Self {
+        Self::new(DEFAULT_HEADER_CACHE_SIZE)
+    }
+}
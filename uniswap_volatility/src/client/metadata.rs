@@ -0,0 +1,318 @@
+use futures_util::future::join_all;
+use std::collections::{HashMap, HashSet};
+use std::convert::TryFrom;
+use tracing::{info, warn};
+use web3::ethabi::{decode, encode, ParamType, Token};
+use web3::signing::keccak256;
+use web3::types::{Address, Bytes, CallRequest, U256 as Word};
+
+use super::events::signed;
+use super::InfuraClient;
+use crate::pool::{Orientation, PoolConfig, TokenInfo};
+
+/// Multicall3, deployed at the same address on mainnet and most EVM chains.
+pub const MULTICALL3_ADDRESS: &str = "0xcA11bde05977b3631167028862bE2a173976CA11";
+
+/// Most calls batched into one `aggregate3` request.
+pub const MAX_MULTICALL_BATCH: usize = 200;
+
+const AGGREGATE3: &str = "aggregate3((address,bool,bytes)[])";
+
+/// Read-only calls made to pools and their tokens. None takes arguments, so
+/// the selector is the whole calldata.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MetadataCall {
+    Token0,
+    Token1,
+    Fee,
+    TickSpacing,
+    Symbol,
+    Decimals,
+    Liquidity, // A pool's current in-range liquidity
+}
+
+impl MetadataCall {
+    /// Calls made to a pool, in the order [`PoolMetadata::decode`] expects.
+    pub const POOL: [MetadataCall; 4] = [
+        MetadataCall::Token0,
+        MetadataCall::Token1,
+        MetadataCall::Fee,
+        MetadataCall::TickSpacing,
+    ];
+    /// Calls made to a token, in the order [`decode_token`] expects.
+    pub const TOKEN: [MetadataCall; 2] = [MetadataCall::Symbol, MetadataCall::Decimals];
+
+    pub fn signature(&self) -> &'static str {
+        match self {
+            MetadataCall::Token0 => "token0()",
+            MetadataCall::Token1 => "token1()",
+            MetadataCall::Fee => "fee()",
+            MetadataCall::TickSpacing => "tickSpacing()",
+            MetadataCall::Symbol => "symbol()",
+            MetadataCall::Decimals => "decimals()",
+            MetadataCall::Liquidity => "liquidity()",
+        }
+    }
+
+    pub fn selector(&self) -> [u8; 4] {
+        selector(self.signature())
+    }
+}
+
+fn selector(signature: &str) -> [u8; 4] {
+    let hash = keccak256(signature.as_bytes());
+    [hash[0], hash[1], hash[2], hash[3]]
+}
+
+/// The immutable parameters of a pool, as read from its contract.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PoolMetadata {
+    pub token0: Address,
+    pub token1: Address,
+    pub fee: u32,
+    pub tick_spacing: i32,
+}
+
+impl PoolMetadata {
+    /// Decodes the outputs of [`MetadataCall::POOL`]. `None` if a call
+    /// reverted or returned something other than a V3 pool would.
+    pub fn decode(outputs: &[Option<Vec<u8>>]) -> Option<Self> {
+        let [token0, token1, fee, tick_spacing] = outputs else { return None };
+        let fee = decode_word(fee.as_deref()?)?;
+        if fee >= Word::from(1u32 << 24) {
+            return None;
+        }
+        Some(Self {
+            token0: decode_address(token0.as_deref()?)?,
+            token1: decode_address(token1.as_deref()?)?,
+            fee: fee.low_u32(),
+            tick_spacing: i32::try_from(signed(decode_word(tick_spacing.as_deref()?)?)).ok()?,
+        })
+    }
+
+    /// The pool at `address` priced with its tokens, the orientation
+    /// inferred from their symbols.
+    pub fn config(&self, address: Address, token0: TokenInfo, token1: TokenInfo) -> PoolConfig {
+        let orientation = Orientation::infer(&token0, &token1);
+        let mut pool = PoolConfig::new(address, self.fee, token0, token1, orientation);
+        pool.tick_spacing = Some(self.tick_spacing);
+        pool
+    }
+}
+
+/// Decodes the outputs of [`MetadataCall::TOKEN`].
+pub fn decode_token(outputs: &[Option<Vec<u8>>]) -> Option<TokenInfo> {
+    let [symbol, decimals] = outputs else { return None };
+    let decimals = decode_word(decimals.as_deref()?)?;
+    if decimals > Word::from(u8::MAX) {
+        return None;
+    }
+    Some(TokenInfo::new(&decode_symbol(symbol.as_deref()?)?, decimals.low_u32() as u8))
+}
+
+/// A token's `symbol()`: an ABI string, or the NUL-padded bytes32 that
+/// older tokens such as MKR return.
+pub fn decode_symbol(output: &[u8]) -> Option<String> {
+    if let Ok(tokens) = decode(&[ParamType::String], output) {
+        if let Some(Token::String(symbol)) = tokens.into_iter().next() {
+            return Some(symbol);
+        }
+    }
+    if output.len() != 32 {
+        return None;
+    }
+    let symbol: Vec<u8> = output.iter().copied().take_while(|byte| *byte != 0).collect();
+    String::from_utf8(symbol).ok().filter(|symbol| !symbol.is_empty())
+}
+
+/// Calldata for Multicall3's `aggregate3` over `(target, calldata)` pairs,
+/// letting each call fail without reverting the batch.
+pub fn encode_aggregate3(calls: &[(Address, Vec<u8>)]) -> Vec<u8> {
+    let calls = calls
+        .iter()
+        .map(|(target, data)| {
+            Token::Tuple(vec![Token::Address(*target), Token::Bool(true), Token::Bytes(data.clone())])
+        })
+        .collect();
+    let mut calldata = selector(AGGREGATE3).to_vec();
+    calldata.extend(encode(&[Token::Array(calls)]));
+    calldata
+}
+
+/// Each call's return data from an `aggregate3` result, `None` where the
+/// call reverted. `None` overall if the result is malformed.
+pub fn decode_aggregate3(output: &[u8]) -> Option<Vec<Option<Vec<u8>>>> {
+    let result = ParamType::Tuple(vec![ParamType::Bool, ParamType::Bytes]);
+    let tokens = decode(&[ParamType::Array(Box::new(result))], output).ok()?;
+    let Some(Token::Array(results)) = tokens.into_iter().next() else { return None };
+    results
+        .into_iter()
+        .map(|result| match result {
+            Token::Tuple(fields) => match fields.as_slice() {
+                [Token::Bool(true), Token::Bytes(data)] => Some(Some(data.clone())),
+                [Token::Bool(false), Token::Bytes(_)] => Some(None),
+                _ => None,
+            },
+            _ => None,
+        })
+        .collect()
+}
+
+pub(crate) fn decode_word(output: &[u8]) -> Option<Word> {
+    output.get(..32).map(Word::from_big_endian)
+}
+
+// An address is a word with 12 zero bytes in front
+fn decode_address(output: &[u8]) -> Option<Address> {
+    let word = output.get(..32)?;
+    word[..12]
+        .iter()
+        .all(|byte| *byte == 0)
+        .then(|| Address::from_slice(&word[12..]))
+}
+
+/// Pools and tokens already read from chain, so each is resolved once.
+#[derive(Debug, Default)]
+pub struct MetadataCache {
+    pools: HashMap<Address, PoolMetadata>,
+    tokens: HashMap<Address, TokenInfo>,
+    multicall: Option<bool>, // Whether Multicall3 is deployed, once checked
+}
+
+impl MetadataCache {
+    pub fn pool(&self, address: &Address) -> Option<PoolMetadata> {
+        self.pools.get(address).copied()
+    }
+
+    pub fn token(&self, address: &Address) -> Option<TokenInfo> {
+        self.tokens.get(address).cloned()
+    }
+
+    pub fn insert_pool(&mut self, address: Address, pool: PoolMetadata) {
+        self.pools.insert(address, pool);
+    }
+
+    pub fn insert_token(&mut self, address: Address, token: TokenInfo) {
+        self.tokens.insert(address, token);
+    }
+
+    /// The pool at `address`, once it and both its tokens are resolved.
+    pub fn config(&self, address: Address) -> Option<PoolConfig> {
+        let pool = self.pool(&address)?;
+        Some(pool.config(address, self.token(&pool.token0)?, self.token(&pool.token1)?))
+    }
+}
+
+impl InfuraClient {
+    /// Resolves the pools at `addresses` with `eth_call`: their tokens, fee
+    /// tier and tick spacing, then each token's symbol and decimals. Calls
+    /// are batched through Multicall3 where it is deployed, and results are
+    /// cached, so only pools and tokens not seen before are read. Addresses
+    /// that do not answer as a V3 pool are skipped with a warning.
+    pub async fn resolve_pools(&self, addresses: &[Address]) -> web3::Result<Vec<PoolConfig>> {
+        let mut seen = HashSet::new();
+        let missing: Vec<Address> = addresses
+            .iter()
+            .copied()
+            .filter(|address| seen.insert(*address) && self.cached(|cache| cache.pool(address)).is_none())
+            .collect();
+        let outputs = self.call_batch(calls(&missing, &MetadataCall::POOL)).await?;
+        for (address, outputs) in missing.iter().zip(outputs.chunks(MetadataCall::POOL.len())) {
+            match PoolMetadata::decode(outputs) {
+                Some(pool) => self.update_cache(|cache| cache.insert_pool(*address, pool)),
+                None => warn!("{:?} did not answer as a Uniswap V3 pool", address),
+            }
+        }
+
+        let mut seen = HashSet::new();
+        let tokens: Vec<Address> = addresses
+            .iter()
+            .filter_map(|address| self.cached(|cache| cache.pool(address)))
+            .flat_map(|pool| [pool.token0, pool.token1])
+            .filter(|token| seen.insert(*token) && self.cached(|cache| cache.token(token)).is_none())
+            .collect();
+        let outputs = self.call_batch(calls(&tokens, &MetadataCall::TOKEN)).await?;
+        for (address, outputs) in tokens.iter().zip(outputs.chunks(MetadataCall::TOKEN.len())) {
+            match decode_token(outputs) {
+                Some(token) => self.update_cache(|cache| cache.insert_token(*address, token)),
+                None => warn!("Token {:?} has no readable symbol or decimals", address),
+            }
+        }
+
+        let pools: Vec<PoolConfig> = addresses
+            .iter()
+            .filter_map(|address| self.cached(|cache| cache.config(*address)))
+            .collect();
+        info!("Resolved {} of {} pools", pools.len(), addresses.len());
+        Ok(pools)
+    }
+
+    // Runs `(target, calldata)` calls, returning each one's output or `None`
+    // where it reverted
+    pub(crate) async fn call_batch(&self, calls: Vec<(Address, Vec<u8>)>) -> web3::Result<Vec<Option<Vec<u8>>>> {
+        if calls.is_empty() {
+            return Ok(Vec::new());
+        }
+        if self.multicall_available().await? {
+            let multicall = MULTICALL3_ADDRESS.parse().unwrap();
+            let mut outputs = Vec::with_capacity(calls.len());
+            for batch in calls.chunks(MAX_MULTICALL_BATCH) {
+                let request = CallRequest::builder()
+                    .to(multicall)
+                    .data(Bytes(encode_aggregate3(batch)))
+                    .build();
+                let output = self.web3().eth().call(request, None).await?;
+                let results = decode_aggregate3(&output.0)
+                    .filter(|results| results.len() == batch.len())
+                    .ok_or_else(|| web3::Error::Decoder("malformed aggregate3 result".to_string()))?;
+                outputs.extend(results);
+            }
+            return Ok(outputs);
+        }
+
+        let requests = calls.into_iter().map(|(target, data)| {
+            let request = CallRequest::builder().to(target).data(Bytes(data)).build();
+            self.web3().eth().call(request, None)
+        });
+        join_all(requests)
+            .await
+            .into_iter()
+            .map(|result| match result {
+                Ok(output) => Ok(Some(output.0)),
+                Err(web3::Error::Rpc(_)) => Ok(None), // Reverted
+                Err(e) => Err(e),
+            })
+            .collect()
+    }
+
+    async fn multicall_available(&self) -> web3::Result<bool> {
+        if let Some(available) = self.cached(|cache| cache.multicall) {
+            return Ok(available);
+        }
+        let code = self.web3().eth().code(MULTICALL3_ADDRESS.parse().unwrap(), None).await?;
+        let available = !code.0.is_empty();
+        if !available {
+            info!("Multicall3 is not deployed; resolving pool metadata one call at a time");
+        }
+        self.update_cache(|cache| cache.multicall = Some(available));
+        Ok(available)
+    }
+
+    fn cached<T>(&self, read: impl FnOnce(&MetadataCache) -> Option<T>) -> Option<T> {
+        self.metadata.lock().ok().and_then(|cache| read(&cache))
+    }
+
+    fn update_cache(&self, update: impl FnOnce(&mut MetadataCache)) {
+        if let Ok(mut cache) = self.metadata.lock() {
+            update(&mut cache);
+        }
+    }
+}
+
+// Every call in `calls` to every target, grouped by target
+pub(crate) fn calls(targets: &[Address], calls: &[MetadataCall]) -> Vec<(Address, Vec<u8>)> {
+    targets
+        .iter()
+        .flat_map(|target| calls.iter().map(move |call| (*target, call.selector().to_vec())))
+        .collect()
+}
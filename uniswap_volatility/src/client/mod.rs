@@ -1,97 +1,552 @@
 use web3::transports::WebSocket;
-use web3::types::{Log, Address, FilterBuilder};
+use web3::types::{Log, BlockId, BlockNumber, FilterBuilder, H256};
 use web3::Web3;
-use web3::ethabi::{decode, ParamType, Token};
-use primitive_types::U256;
 use futures_util::StreamExt;
+use std::sync::{Arc, Mutex, RwLock};
 use tracing::{info, error, warn};
-use tokio::sync::mpsc::UnboundedSender;
+use volatility_core::channel::EventSender;
+use volatility_core::event::{now_millis, EventDetail, MarketEvent};
+use volatility_core::source::{HealthTracker, TickChannel};
+
+use crate::pool::PoolConfig;
+use backfill::DEFAULT_MAX_CHUNK_BLOCKS;
+
+pub mod activity;
+pub mod backfill;
+pub mod cursor;
+pub mod discovery;
+pub mod events;
+pub mod headers;
+pub mod metadata;
+pub mod registry;
+pub mod reorg;
+mod source;
+
+pub use activity::{ActivityTracker, PoolActivity, SubscriptionActivity};
+pub use backfill::{backfill_start, is_too_many_results, AdaptiveChunker};
+pub use cursor::{logs_after, LogCursor};
+pub use discovery::{DiscoveryFilter, PoolCreated, PoolDiscovery};
+pub use events::{DecodeError, PoolEvent, PoolEventKind, Swap};
+pub use headers::{BlockTime, HeaderCache};
+pub use metadata::{decode_aggregate3, encode_aggregate3, MetadataCache, MetadataCall, PoolMetadata};
+pub use registry::PoolRegistry;
+pub use reorg::ReorgBuffer;
 
 #[derive(Debug, Clone)]
 pub struct InfuraClient {
-    web3: Web3<WebSocket>,
-    pool_address: Address,
+    web3: Arc<RwLock<Web3<WebSocket>>>, // Replaced for every clone by `reconnect`
+    url: String,
+    pools: PoolRegistry, // Watched pools, sharing one log subscription
+    // Used when the client acts as a `PriceSource`
+    ticks: TickChannel,
+    health: HealthTracker,
+    activity: ActivityTracker,
+    cursor: Arc<Mutex<Option<LogCursor>>>, // Last processed log, shared by clones
+    reorg: Arc<Mutex<ReorgBuffer>>,
+    headers: Arc<Mutex<HeaderCache>>, // Block times, filled from newHeads
+    metadata: Arc<Mutex<MetadataCache>>, // Pools and tokens resolved with eth_call
+    backfill_secs: u64,               // History fetched before the first live log
+    max_chunk_blocks: u64,            // Widest eth_getLogs block range
 }
 
 impl InfuraClient {
-    /// Creates a new client connected to the given Infura WebSocket URL.
-    pub async fn new(infura_ws_url: &str, pool_address: &str) -> web3::Result<Self> {
+    /// Creates a new client connected to the given Infura WebSocket URL,
+    /// watching the default USDC/WETH pool until given others.
+    pub async fn new(infura_ws_url: &str) -> web3::Result<Self> {
         let ws = WebSocket::new(infura_ws_url).await?;
         let web3 = Web3::new(ws);
 
         Ok(Self {
-            web3,
-            pool_address: pool_address.parse().unwrap(),
-        })
-    }
-
-    /// Decodes the price from the Uniswap log event.
-    fn decode_price(&self, log: Log) -> Option<f64> {
-        let decoded_data = decode(
-            &[
-                ParamType::Int(256),  // amount0
-                ParamType::Int(256),  // amount1
-                ParamType::Uint(160), // sqrtPriceX96
-                ParamType::Uint(128), // liquidity
-                ParamType::Int(24),   // tick
-            ],
-            &log.data.0,
-        )
-        .ok()?;
-
-        let sqrt_price_x96 = match &decoded_data[2] {
-            Token::Uint(value) => U256::from(value.as_u128()),
-            _ => return None,
+            web3: Arc::new(RwLock::new(web3)),
+            url: infura_ws_url.to_string(),
+            pools: PoolRegistry::new(),
+            ticks: TickChannel::new(),
+            health: HealthTracker::new(),
+            activity: ActivityTracker::new(),
+            cursor: Arc::new(Mutex::new(None)),
+            reorg: Arc::new(Mutex::new(ReorgBuffer::new(0))),
+            headers: Arc::new(Mutex::new(HeaderCache::default())),
+            metadata: Arc::new(Mutex::new(MetadataCache::default())),
+            backfill_secs: 0,
+            max_chunk_blocks: DEFAULT_MAX_CHUNK_BLOCKS,
+        }
+        .with_pool(PoolConfig::default()))
+    }
+
+    /// Watches only `pool`.
+    pub fn with_pool(self, pool: PoolConfig) -> Self {
+        self.with_pools(vec![pool])
+    }
+
+    /// Watches `pools` over a single log subscription, replacing the pools
+    /// watched so far. Swaps are labelled with their pool's address.
+    pub fn with_pools(self, pools: impl IntoIterator<Item = PoolConfig>) -> Self {
+        self.pools.replace(pools);
+        self
+    }
+
+    /// Finality mode: holds each swap back until its block has
+    /// `confirmations` confirmations. 0 sends swaps as they arrive, relying
+    /// on retractions if their block is later orphaned.
+    pub fn with_confirmations(mut self, confirmations: u64) -> Self {
+        self.reorg = Arc::new(Mutex::new(ReorgBuffer::new(confirmations)));
+        self
+    }
+
+    /// On the first subscription, replays `lookback_secs` of swaps from
+    /// `eth_getLogs` before the live stream, so windows start full.
+    pub fn with_backfill(mut self, lookback_secs: u64) -> Self {
+        self.backfill_secs = lookback_secs;
+        self
+    }
+
+    /// Caps the block range of each `eth_getLogs` request, for providers
+    /// with range limits.
+    pub fn with_max_chunk_blocks(mut self, blocks: u64) -> Self {
+        self.max_chunk_blocks = blocks;
+        self
+    }
+
+    /// The watched pools, ordered by address.
+    pub fn pools(&self) -> Vec<PoolConfig> {
+        self.pools.list()
+    }
+
+    /// The shared set of watched pools; pools added to it are picked up by
+    /// the running log subscription.
+    pub fn registry(&self) -> PoolRegistry {
+        self.pools.clone()
+    }
+
+    /// Counts of decoded events and liquidity state of each pool, and the
+    /// subscription's gaps and reorgs.
+    pub fn activity(&self) -> ActivityTracker {
+        self.activity.clone()
+    }
+
+    /// Opens a new WebSocket to the node in place of the current one, for
+    /// this client and its clones. Subscriptions only end when their socket
+    /// closes, so this comes before resubscribing.
+    pub async fn reconnect(&self) -> web3::Result<()> {
+        let web3 = Web3::new(WebSocket::new(&self.url).await?);
+        if let Ok(mut current) = self.web3.write() {
+            *current = web3;
+        }
+        Ok(())
+    }
+
+    // The current connection
+    pub(crate) fn web3(&self) -> Web3<WebSocket> {
+        match self.web3.read() {
+            Ok(web3) => web3.clone(),
+            Err(poisoned) => poisoned.into_inner().clone(),
+        }
+    }
+
+    /// The latest log handed to the pipeline; a new subscription resumes after it.
+    pub fn last_processed(&self) -> Option<LogCursor> {
+        self.cursor.lock().ok().and_then(|cursor| *cursor)
+    }
+
+    /// Caches the time of every new block until the subscription ends, so
+    /// swaps are stamped without a header request each. Each block also
+    /// counts as a confirmation: swaps it makes final are sent to `sender`.
+    pub async fn watch_heads(&self, sender: &EventSender<MarketEvent>) -> web3::Result<()> {
+        let mut heads = self.web3().eth_subscribe().subscribe_new_heads().await?;
+        while let Some(head) = heads.next().await {
+            let head = head?;
+            let (Some(hash), Some(number)) = (head.hash, head.number) else { continue };
+            self.cache_header(
+                hash,
+                BlockTime {
+                    number: number.as_u64(),
+                    timestamp: head.timestamp.low_u64(),
+                },
+            );
+
+            // A quiet pool's swaps still reach their confirmations
+            let ready = self
+                .reorg
+                .lock()
+                .map(|mut buffer| buffer.advance_head(number.as_u64()))
+                .unwrap_or_default();
+            for swap in ready {
+                info!("New Price: {}", swap.price);
+                if sender.send(swap).await.is_err() {
+                    return Ok(());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Time of the block `hash`, from the cache or else fetched by hash.
+    pub async fn block_time(&self, hash: H256) -> Option<BlockTime> {
+        if let Some(time) = self.headers.lock().ok().and_then(|headers| headers.get(&hash)) {
+            return Some(time);
+        }
+        let block = match self.web3().eth().block(BlockId::Hash(hash)).await {
+            Ok(block) => block?,
+            Err(e) => {
+                warn!("Failed to fetch block {:?}: {:?}", hash, e);
+                return None;
+            }
+        };
+        self.activity.record_header_fetch();
+        let time = BlockTime {
+            number: block.number?.as_u64(),
+            timestamp: block.timestamp.low_u64(),
         };
+        self.cache_header(hash, time);
+        Some(time)
+    }
 
-        let price = (sqrt_price_x96.as_u128() as f64 / 2f64.powi(96)).powi(2);
-        let dollar_price = 10f64.powi(12) / price;
+    fn cache_header(&self, hash: H256, time: BlockTime) {
+        if let Ok(mut headers) = self.headers.lock() {
+            headers.insert(hash, time);
+        }
+    }
 
-        Some(dollar_price)
+    // Every watched pool's events, without a block range
+    fn log_filter(&self) -> FilterBuilder {
+        // Only the pool events we decode
+        let topics = PoolEventKind::ALL.iter().map(|kind| kind.topic0()).collect();
+        let addresses = self.pools().into_iter().map(|pool| pool.address).collect();
+        FilterBuilder::default()
+            .address(addresses)
+            .topics(Some(topics), None, None, None)
     }
 
-    /// Fetches prices from the WebSocket and sends them through a channel.
+    /// Fetches the pools' logs from the WebSocket, sends swaps through a
+    /// channel and feeds every decoded event to the activity tracker.
+    ///
+    /// The subscription stays open until it ends, the channel closes or
+    /// `max_logs_per_batch` swaps were sent. When pools are added to the
+    /// registry, it is replaced by one covering them. Once a log has been
+    /// processed, each new subscription fills the blocks missed in between
+    /// with `eth_getLogs`.
     pub async fn fetch_prices(
         &self,
-        sender: &UnboundedSender<f64>,
+        sender: &EventSender<MarketEvent>,
         max_logs_per_batch: usize,
     ) -> web3::Result<usize> {
-        let filter = FilterBuilder::default()
-            .address(vec![self.pool_address])
-            .topics(None, None, None, None)
-            .build();
-
-        let mut logs = self.web3.eth_subscribe().subscribe_logs(filter).await?;
-        info!("Listening for price updates...");
-
-        let mut processed_count = 0; // Count of logs processed in this batch
-
-        while let Some(log) = logs.next().await {
-            match log {
-                Ok(log) => {
-                    if let Some(price) = self.decode_price(log) {
-                        info!("New Price: {}", price);
-
-                        // Send the price to the channel
-                        if let Err(e) = sender.send(price) {
-                            warn!("Failed to send price: {:?}", e);
-                            break; // Stop processing if the channel is closed
-                        }
-
-                        processed_count += 1;
-
-                        // Exit after processing a batch
-                        if processed_count >= max_logs_per_batch {
-                            break;
-                        }
+        let mut processed_count = 0; // Count of swaps processed in this batch
+        let mut replaced = false; // The last subscription was replaced, not dropped
+
+        loop {
+            // Changes from here on are not in the filter below
+            let mut pools_changed = self.pools.watch();
+            // Subscribe before looking back, so no log falls between the two
+            let mut logs = self.web3().eth_subscribe().subscribe_logs(self.log_filter().build()).await?;
+            info!("Listening for price updates from {} pools...", self.pools.len());
+
+            for log in self.missed_logs(!replaced).await? {
+                match self.process_log(log, sender).await {
+                    LogOutcome::Priced(sent) => processed_count += sent,
+                    LogOutcome::ChannelClosed => return Ok(processed_count),
+                    LogOutcome::Skipped => {}
+                }
+            }
+
+            loop {
+                let log = tokio::select! {
+                    log = logs.next() => log,
+                    _ = pools_changed.changed() => break,
+                };
+                let log = match log {
+                    Some(Ok(log)) => log,
+                    Some(Err(e)) => {
+                        error!("Error reading log: {:?}", e);
+                        continue;
                     }
+                    None => return Ok(processed_count),
+                };
+                match self.process_log(log, sender).await {
+                    LogOutcome::Priced(sent) => processed_count += sent,
+                    LogOutcome::ChannelClosed => return Ok(processed_count), // Stop processing if the channel is closed
+                    LogOutcome::Skipped => continue,
+                }
+
+                // Exit after processing a batch
+                if processed_count >= max_logs_per_batch {
+                    return Ok(processed_count);
+                }
+            }
+            info!("Watched pools changed; resubscribing to their logs");
+            replaced = true;
+        }
+    }
+
+    // Logs emitted after the cursor up to the chain head. Before any log was
+    // processed, the configured history instead, to seed the windows. Only
+    // logs missed while a `dropped` subscription was down count as a gap;
+    // replacing a subscription for new pools is routine.
+    async fn missed_logs(&self, dropped: bool) -> web3::Result<Vec<Log>> {
+        let cursor = self.last_processed();
+        if cursor.is_none() && self.backfill_secs == 0 {
+            return Ok(Vec::new());
+        }
+        let head = self.web3().eth().block_number().await?.as_u64();
+
+        let Some(cursor) = cursor else {
+            let from = backfill_start(head, self.backfill_secs);
+            info!(
+                "Backfilling {} seconds of pool history from blocks {}..={}",
+                self.backfill_secs, from, head
+            );
+            let history = logs_after(None, self.logs_in_range(from, head).await?);
+            info!("Backfilled {} pool logs", history.len());
+            return Ok(history);
+        };
+        if head < cursor.block {
+            return Ok(Vec::new());
+        }
+
+        let missed = logs_after(Some(cursor), self.logs_in_range(cursor.block, head).await?);
+        if !missed.is_empty() && dropped {
+            warn!(
+                "Backfilling {} pool logs missed in blocks {}..={}",
+                missed.len(),
+                cursor.block,
+                head
+            );
+            self.activity.record_gap(missed.len());
+        }
+        Ok(missed)
+    }
+
+    /// The pools' logs in blocks `from..=to`, fetched in chunks that shrink
+    /// whenever the provider rejects a request as too large.
+    pub async fn logs_in_range(&self, from: u64, to: u64) -> web3::Result<Vec<Log>> {
+        self.filtered_logs_in_range(self.log_filter(), self.max_chunk_blocks, from, to).await
+    }
+
+    // Logs matching `filter` in blocks `from..=to`, at most `max_chunk_blocks` per request
+    async fn filtered_logs_in_range(
+        &self,
+        filter: FilterBuilder,
+        max_chunk_blocks: u64,
+        from: u64,
+        to: u64,
+    ) -> web3::Result<Vec<Log>> {
+        let mut chunker = AdaptiveChunker::new(max_chunk_blocks);
+        let mut logs = Vec::new();
+        let mut start = from;
+        while start <= to {
+            let end = chunker.end_for(start, to);
+            let request = filter
+                .clone()
+                .from_block(BlockNumber::Number(start.into()))
+                .to_block(BlockNumber::Number(end.into()))
+                .build();
+            match self.web3().eth().logs(request).await {
+                Ok(chunk) => {
+                    logs.extend(chunk);
+                    chunker.grow();
+                    start = end + 1;
                 }
-                Err(e) => {
-                    error!("Error reading log: {:?}", e);
+                Err(e) if is_too_many_results(&e.to_string()) && chunker.shrink() => {
+                    warn!(
+                        "eth_getLogs over blocks {}..={} rejected ({}); retrying {} blocks at a time",
+                        start,
+                        end,
+                        e,
+                        chunker.size()
+                    );
                 }
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(logs)
+    }
+
+    // Decodes, tracks and prices one log, advancing the cursor once it is handled
+    async fn process_log(&self, log: Log, sender: &EventSender<MarketEvent>) -> LogOutcome {
+        let position = LogCursor::of(&log);
+        if log.removed == Some(true) {
+            return self.retract(&log, position, sender).await;
+        }
+        // Already handled before a reconnect
+        if let (Some(position), Some(cursor)) = (position, self.last_processed()) {
+            if position <= cursor {
+                return LogOutcome::Skipped;
             }
         }
+        // The first log after a run of removed ones is on the new chain
+        if let Some(depth) = self.reorg.lock().ok().and_then(|mut buffer| buffer.end_reorg()) {
+            warn!("Chain reorganization {} blocks deep", depth);
+            self.activity.record_reorg(depth);
+        }
+
+        // Route by emitting pool; the filter only matches watched ones
+        let Some(pool) = self.pools.get(&log.address) else {
+            warn!("Skipping log from unwatched address {:?}", log.address);
+            self.advance(position);
+            return LogOutcome::Skipped;
+        };
+        let event = match PoolEvent::decode(&log) {
+            Ok(event) => event,
+            Err(e) => {
+                warn!("Skipping log from pool {:?}: {}", pool.address, e);
+                self.activity.record_failure(pool.address, &e);
+                self.advance(position);
+                return LogOutcome::Skipped;
+            }
+        };
+        self.activity.record(pool.address, &event);
+
+        // Liquidity events stop at the tracker; only swaps are priced
+        let PoolEvent::Swap(swap) = event else {
+            self.advance(position);
+            return LogOutcome::Skipped;
+        };
+        if !swap.tick_consistent() {
+            warn!(
+                "Swap tick {} disagrees with sqrtPriceX96 {} in pool {:?}",
+                swap.tick, swap.sqrt_price_x96, pool.address
+            );
+        }
+        let Some(mut swap) = swap_event(&swap, &pool.instrument(), &pool, position) else {
+            self.advance(position);
+            return LogOutcome::Skipped;
+        };
+        // Chain time, so a burst of backfilled swaps keeps its spacing. The
+        // windows run on chain time alone: without its header, a swap takes
+        // the newest known earlier block's time, and with none it is dropped
+        let time = match log.block_hash {
+            Some(hash) => self.block_time(hash).await,
+            None => None,
+        };
+        let time = time.or_else(|| {
+            let block = position?.block;
+            self.headers.lock().ok()?.latest_at_or_before(block)
+        });
+        let Some(time) = time else {
+            warn!("Dropping swap in pool {:?}: no block time is known for it", pool.address);
+            self.advance(position);
+            return LogOutcome::Skipped;
+        };
+        swap.event_time = Some(time.timestamp_millis());
+
+        // Buffered for retraction, and held back until final in finality mode
+        let ready = match (log.block_hash, position) {
+            (Some(hash), Some(position)) => self
+                .reorg
+                .lock()
+                .map(|mut buffer| buffer.push(hash, position.block, position.log_index, swap))
+                .unwrap_or_default(),
+            _ => vec![swap],
+        };
 
-        Ok(processed_count)
+        let mut sent = 0;
+        for swap in ready {
+            info!("New Price: {}", swap.price);
+
+            // Send the swap to the channel
+            if let Err(e) = sender.send(swap).await {
+                warn!("Failed to send price: {:?}", e);
+                return LogOutcome::ChannelClosed;
+            }
+            sent += 1;
+        }
+        self.advance(position);
+        if sent == 0 {
+            LogOutcome::Skipped
+        } else {
+            LogOutcome::Priced(sent)
+        }
+    }
+
+    // A log dropped by a reorg: retracts its swap if it was already sent
+    async fn retract(
+        &self,
+        log: &Log,
+        position: Option<LogCursor>,
+        sender: &EventSender<MarketEvent>,
+    ) -> LogOutcome {
+        let (Some(hash), Some(position)) = (log.block_hash, position) else {
+            return LogOutcome::Skipped;
+        };
+        // The new chain may reuse the position
+        self.rewind(position);
+
+        let retraction = self
+            .reorg
+            .lock()
+            .ok()
+            .and_then(|mut buffer| buffer.remove(hash, position.block, position.log_index));
+        let Some(retraction) = retraction else { return LogOutcome::Skipped };
+        warn!(
+            "Retracting swap at block {} log {} after a reorg",
+            position.block, position.log_index
+        );
+        self.activity.record_retraction(log.address);
+        if sender.send(retraction).await.is_err() {
+            return LogOutcome::ChannelClosed;
+        }
+        LogOutcome::Skipped
+    }
+
+    fn advance(&self, position: Option<LogCursor>) {
+        let Some(position) = position else { return };
+        if let Ok(mut cursor) = self.cursor.lock() {
+            if Some(position) > *cursor {
+                *cursor = Some(position);
+            }
+        }
     }
-}
\ No newline at end of file
+
+    // Moves the cursor back to just before `position`
+    fn rewind(&self, position: LogCursor) {
+        if let Ok(mut cursor) = self.cursor.lock() {
+            if Some(position) <= *cursor {
+                *cursor = position.previous();
+            }
+        }
+    }
+}
+
+// What happened to a log handed to `process_log`
+enum LogOutcome {
+    Skipped,
+    Priced(usize), // Swaps sent, more than one when confirmations release a backlog
+    ChannelClosed,
+}
+
+/// Decodes a Uniswap V3 `Swap` log into a market event for `instrument`,
+/// priced in `pool`'s base token. `None` for any other log.
+pub fn decode_swap(log: &Log, instrument: &str, pool: &PoolConfig) -> Option<MarketEvent> {
+    match PoolEvent::decode(log) {
+        Ok(PoolEvent::Swap(swap)) => swap_event(&swap, instrument, pool, LogCursor::of(log)),
+        _ => None,
+    }
+}
+
+/// A decoded swap as a market event for `instrument`, tagged with the log's
+/// position so it can be retracted after a reorg.
+pub fn swap_event(
+    swap: &Swap,
+    instrument: &str,
+    pool: &PoolConfig,
+    position: Option<LogCursor>,
+) -> Option<MarketEvent> {
+    let price = pool.price(swap.sqrt_price_x96)?;
+
+    Some(MarketEvent {
+        source: "uniswap".to_string(),
+        instrument: instrument.to_string(),
+        event_time: None,
+        receive_time: now_millis(),
+        price,
+        size: None,
+        detail: EventDetail::Swap {
+            amount0: swap.amount0,
+            amount1: swap.amount1,
+            liquidity: swap.liquidity,
+            tick: swap.tick,
+            block_number: position.map(|position| position.block),
+            log_index: position.map(|position| position.log_index),
+            removed: false,
+        },
+    })
+}
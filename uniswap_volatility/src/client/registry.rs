@@ -0,0 +1,91 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tokio::sync::watch;
+use web3::types::Address;
+
+use crate::pool::PoolConfig;
+
+/// The pools a client watches, shared by its clones so pools found while
+/// running reach the log subscription. Every change bumps a version that
+/// [`PoolRegistry::watch`] receivers see.
+#[derive(Debug, Clone)]
+pub struct PoolRegistry {
+    pools: Arc<Mutex<HashMap<Address, PoolConfig>>>,
+    version: Arc<watch::Sender<u64>>,
+}
+
+impl PoolRegistry {
+    pub fn new() -> Self {
+        Self {
+            pools: Arc::new(Mutex::new(HashMap::new())),
+            version: Arc::new(watch::channel(0).0),
+        }
+    }
+
+    pub fn get(&self, address: &Address) -> Option<PoolConfig> {
+        self.pools.lock().ok().and_then(|pools| pools.get(address).cloned())
+    }
+
+    pub fn contains(&self, address: &Address) -> bool {
+        self.pools.lock().map(|pools| pools.contains_key(address)).unwrap_or(false)
+    }
+
+    /// Every pool, ordered by address.
+    pub fn list(&self) -> Vec<PoolConfig> {
+        let mut pools: Vec<PoolConfig> = self
+            .pools
+            .lock()
+            .map(|pools| pools.values().cloned().collect())
+            .unwrap_or_default();
+        pools.sort_by_key(|pool| pool.address);
+        pools
+    }
+
+    pub fn len(&self) -> usize {
+        self.pools.lock().map(|pools| pools.len()).unwrap_or(0)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Adds the pools not watched yet and returns how many that was.
+    pub fn extend(&self, pools: impl IntoIterator<Item = PoolConfig>) -> usize {
+        let added = match self.pools.lock() {
+            Ok(mut watched) => pools
+                .into_iter()
+                .filter(|pool| {
+                    let new = !watched.contains_key(&pool.address);
+                    if new {
+                        watched.insert(pool.address, pool.clone());
+                    }
+                    new
+                })
+                .count(),
+            Err(_) => 0,
+        };
+        if added > 0 {
+            self.version.send_modify(|version| *version += 1);
+        }
+        added
+    }
+
+    /// Watches exactly `pools`.
+    pub fn replace(&self, pools: impl IntoIterator<Item = PoolConfig>) {
+        if let Ok(mut watched) = self.pools.lock() {
+            *watched = pools.into_iter().map(|pool| (pool.address, pool)).collect();
+        }
+        self.version.send_modify(|version| *version += 1);
+    }
+
+    /// A receiver that sees every change made after this call.
+    pub fn watch(&self) -> watch::Receiver<u64> {
+        self.version.subscribe()
+    }
+}
+
+impl Default for PoolRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
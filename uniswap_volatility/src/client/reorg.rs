@@ -0,0 +1,148 @@
+use volatility_core::event::{EventDetail, MarketEvent};
+use web3::types::H256;
+
+/// Blocks of swaps kept for retraction behind the newest block seen.
+pub const DEFAULT_REORG_BUFFER_BLOCKS: u64 = 64;
+
+#[derive(Debug)]
+struct Buffered {
+    log_index: u64,
+    event: MarketEvent,
+    emitted: bool,
+}
+
+#[derive(Debug)]
+struct BufferedBlock {
+    hash: H256,
+    number: u64,
+    swaps: Vec<Buffered>,
+}
+
+/// Recent swaps keyed by block hash, so swaps from blocks orphaned by a
+/// reorg can be retracted, and held back until final in finality mode.
+#[derive(Debug)]
+pub struct ReorgBuffer {
+    depth: u64,         // Blocks kept behind the head
+    confirmations: u64, // Blocks a swap waits for before it is emitted; 0 or 1 emits at once
+    head: Option<u64>,  // Newest block seen
+    blocks: Vec<BufferedBlock>,
+    reorg_start: Option<u64>, // Lowest orphaned block of the reorg in progress
+}
+
+impl ReorgBuffer {
+    pub fn new(confirmations: u64) -> Self {
+        Self {
+            depth: DEFAULT_REORG_BUFFER_BLOCKS.max(confirmations),
+            confirmations,
+            head: None,
+            blocks: Vec::new(),
+            reorg_start: None,
+        }
+    }
+
+    pub fn confirmations(&self) -> u64 {
+        self.confirmations
+    }
+
+    /// Buffers a swap from block `number` with hash `hash` and returns the
+    /// swaps now confirmed enough to emit, oldest first.
+    pub fn push(&mut self, hash: H256, number: u64, log_index: u64, event: MarketEvent) -> Vec<MarketEvent> {
+        self.raise_head(number);
+        let swap = Buffered {
+            log_index,
+            event,
+            emitted: false,
+        };
+        match self.blocks.iter_mut().find(|block| block.hash == hash) {
+            Some(block) => block.swaps.push(swap),
+            None => {
+                let index = self.blocks.partition_point(|block| block.number <= number);
+                self.blocks.insert(
+                    index,
+                    BufferedBlock {
+                        hash,
+                        number,
+                        swaps: vec![swap],
+                    },
+                );
+            }
+        }
+
+        let ready = self.release();
+        self.prune();
+        ready
+    }
+
+    /// Records a new chain head `number`, whether or not it has swaps, and
+    /// returns the swaps it confirms, oldest first.
+    pub fn advance_head(&mut self, number: u64) -> Vec<MarketEvent> {
+        self.raise_head(number);
+        let ready = self.release();
+        self.prune();
+        ready
+    }
+
+    /// Drops the swap a removed log refers to. Returns a retraction if it was
+    /// already emitted; a swap still waiting for confirmations just vanishes.
+    pub fn remove(&mut self, hash: H256, number: u64, log_index: u64) -> Option<MarketEvent> {
+        self.reorg_start = Some(self.reorg_start.map_or(number, |start| start.min(number)));
+
+        let block = self.blocks.iter_mut().find(|block| block.hash == hash)?;
+        let index = block.swaps.iter().position(|swap| swap.log_index == log_index)?;
+        let swap = block.swaps.remove(index);
+        if !swap.emitted {
+            return None;
+        }
+        let mut event = swap.event;
+        if let EventDetail::Swap { removed, .. } = &mut event.detail {
+            *removed = true;
+        }
+        Some(event)
+    }
+
+    /// Ends the reorg in progress, if any, returning its depth: how many
+    /// blocks back from the head it reached.
+    pub fn end_reorg(&mut self) -> Option<u64> {
+        let start = self.reorg_start.take()?;
+        let depth = self.head.map_or(1, |head| head.saturating_sub(start) + 1);
+        // The head was orphaned too; the replacement chain sets a new one
+        self.head = Some(start.saturating_sub(1));
+        Some(depth)
+    }
+
+    /// Swaps buffered but not yet emitted.
+    pub fn pending(&self) -> usize {
+        self.blocks
+            .iter()
+            .flat_map(|block| &block.swaps)
+            .filter(|swap| !swap.emitted)
+            .count()
+    }
+
+    fn raise_head(&mut self, number: u64) {
+        self.head = Some(self.head.map_or(number, |head| head.max(number)));
+    }
+
+    // Marks and returns swaps with enough confirmations
+    fn release(&mut self) -> Vec<MarketEvent> {
+        let Some(head) = self.head else { return Vec::new() };
+        let confirmations = self.confirmations;
+        self.blocks
+            .iter_mut()
+            .filter(|block| head.saturating_sub(block.number) + 1 >= confirmations)
+            .flat_map(|block| block.swaps.iter_mut())
+            .filter(|swap| !swap.emitted)
+            .map(|swap| {
+                swap.emitted = true;
+                swap.event.clone()
+            })
+            .collect()
+    }
+
+    fn prune(&mut self) {
+        let Some(head) = self.head else { return };
+        let depth = self.depth;
+        self.blocks
+            .retain(|block| !block.swaps.is_empty() && head.saturating_sub(block.number) < depth);
+    }
+}
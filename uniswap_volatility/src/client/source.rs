@@ -0,0 +1,117 @@
+use async_trait::async_trait;
+use futures_util::stream::BoxStream;
+use tokio::time::{sleep, Duration};
+use tracing::{error, warn};
+use volatility_core::channel::{bounded, BackpressurePolicy};
+use volatility_core::event::MarketEvent;
+use volatility_core::source::{
+    ConnectionState, Health, PriceSource, SourceError, DEFAULT_TICK_CAPACITY,
+};
+
+use web3::types::Address;
+
+use super::InfuraClient;
+
+// Delay before resubscribing after the log subscription ends
+const RESUBSCRIBE_DELAY: Duration = Duration::from_secs(10);
+
+/// The client's pools as a venue-neutral price source. Symbols are pool
+/// addresses, which must be among the configured pools; ticks are labelled
+/// with the address of the pool that swapped. Every pool in the registry is
+/// streamed, including those added by discovery before or after subscribing.
+#[async_trait]
+impl PriceSource for InfuraClient {
+    fn venue(&self) -> &str {
+        "uniswap"
+    }
+
+    /// The WebSocket is opened by `new`; this checks the node answers.
+    async fn connect(&mut self) -> Result<(), SourceError> {
+        self.web3()
+            .eth()
+            .block_number()
+            .await
+            .map_err(|e| SourceError::Connect(e.to_string()))?;
+        self.health.set_state(ConnectionState::Connected);
+        Ok(())
+    }
+
+    async fn subscribe(&mut self, symbols: &[String]) -> Result<(), SourceError> {
+        if self.health.snapshot().state == ConnectionState::Idle {
+            return Err(SourceError::NotConnected);
+        }
+        if symbols.is_empty() {
+            return Err(SourceError::Subscribe("no pools to subscribe to".to_string()));
+        }
+        // Only checked: discovery may already have added pools to the shared registry
+        for symbol in symbols {
+            symbol
+                .parse::<Address>()
+                .ok()
+                .and_then(|address| self.pools.get(&address))
+                .ok_or_else(|| SourceError::Subscribe(format!("{} is not a configured pool", symbol)))?;
+        }
+
+        let (price_sender, mut prices) = bounded(DEFAULT_TICK_CAPACITY, BackpressurePolicy::Block);
+        let head_sender = price_sender.clone(); // Heads release confirmed swaps, and stop once prices are no longer read
+        let client = self.clone();
+        tokio::spawn(async move {
+            while !price_sender.is_closed() {
+                match client.fetch_prices(&price_sender, usize::MAX).await {
+                    Ok(_) => client.health.record_reconnect("log subscription ended"),
+                    Err(e) => {
+                        error!("Error fetching prices: {:?}", e);
+                        client.health.record_reconnect(&e.to_string());
+                    }
+                }
+                warn!(
+                    "Resubscribing to pool logs in {} seconds...",
+                    RESUBSCRIBE_DELAY.as_secs()
+                );
+                sleep(RESUBSCRIBE_DELAY).await;
+                // A subscription only ends with its socket
+                if let Err(e) = client.reconnect().await {
+                    error!("Failed to reconnect to the node: {:?}", e);
+                    continue;
+                }
+                client.health.set_state(ConnectionState::Connected);
+            }
+        });
+
+        // Block times and confirmations for the swaps, kept up while the price loop runs
+        let client = self.clone();
+        tokio::spawn(async move {
+            while !head_sender.is_closed() {
+                if let Err(e) = client.watch_heads(&head_sender).await {
+                    error!("Error watching new heads: {:?}", e);
+                }
+                warn!(
+                    "Resubscribing to new heads in {} seconds...",
+                    RESUBSCRIBE_DELAY.as_secs()
+                );
+                sleep(RESUBSCRIBE_DELAY).await;
+            }
+        });
+
+        let ticks = self.ticks.sender();
+        let health = self.health.clone();
+        tokio::spawn(async move {
+            while let Some(tick) = prices.recv().await {
+                health.record_tick(tick.timestamp());
+                if ticks.send(tick).await.is_err() {
+                    health.set_state(ConnectionState::Closed);
+                    return;
+                }
+            }
+        });
+        Ok(())
+    }
+
+    fn ticks(&mut self) -> Result<BoxStream<'static, MarketEvent>, SourceError> {
+        self.ticks.take_stream()
+    }
+
+    fn health(&self) -> Health {
+        self.health.snapshot()
+    }
+}
@@ -1,24 +1,62 @@
-mod client;
-mod math;
-
-use client::InfuraClient;
-use math::VolatilityCalculator;
+use uniswap_volatility::client::{ActivityTracker, DiscoveryFilter, InfuraClient, PoolActivity, PoolDiscovery, PoolRegistry};
+use uniswap_volatility::math::{TickVolatilityCalculator, VolatilityCalculator};
+use uniswap_volatility::pool::PoolConfig;
+use volatility_core::channel::{bounded, BackpressurePolicy, ChannelMetrics, EventReceiver, EventSender};
+use volatility_core::event::{EventDetail, MarketEvent};
+use volatility_core::source::PriceSource;
+use futures_util::StreamExt;
+use std::collections::HashMap;
 use std::sync::Arc;
 use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::UNIX_EPOCH;
 use tokio::time:: Duration;
-use tokio::sync::mpsc;
 use tracing::{info, warn, error};
 use tracing_subscriber::fmt;
 use tracing_appender::rolling;
 use tracing_subscriber::prelude::*;
 use hyper::{Body, Response, Server};
 use hyper::service::{make_service_fn, service_fn};
-use prometheus::{Encoder, TextEncoder, GaugeVec, Registry};
+use prometheus::{Encoder, TextEncoder, Counter, CounterVec, Gauge, GaugeVec, Histogram, HistogramOpts, Registry};
+use web3::types::Address;
 
 const MAX_ROLLING_WINDOW_DURATION: u64 = 600_000;
 const INFURA_WS_URL: &str = "wss://mainnet.infura.io/ws/v3/943fabd894044ec88ccae8613bf6b0b4";
-const POOL_ADDRESS: &str = "0x88e6A0c2dDD26FEEb64F039a2c41296FcB3f5640";
-const SYMBOL: &str = "ethusdc";
+// Pools watched over one log subscription. Tokens, decimals and fee tiers
+// are read from chain at startup: USDC/WETH 0.05%, WBTC/WETH 0.3% and
+// USDC/USDT 0.01%
+const POOL_ADDRESSES: &[&str] = &[
+    "0x88e6A0c2dDD26FEEb64F039a2c41296FcB3f5640",
+    "0xCBCdF9626bC03E24f779434178A73a0B4bad62eD",
+    "0x3416cF6C708Da44DB2624D63ea0AAef7113527C6",
+];
+// Discovery mode: also watch pools the factory creates whose tokens are both
+// on the allowlist (USDC, USDT, DAI, WETH, WBTC), whose fee tier is listed
+// and whose in-range liquidity reaches the minimum. Pools short of liquidity
+// are checked again every DISCOVERY_RECHECK_SECS
+const DISCOVERY_ENABLED: bool = true;
+const DISCOVERY_TOKENS: &[&str] = &[
+    "0xA0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48",
+    "0xdAC17F958D2ee523a2206206994597C13D831ec7",
+    "0x6B175474E89094C44Da98b954EedeAC495271d0F",
+    "0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2",
+    "0x2260FAC5E5542a773Aa44fBCfeDf7C193bc2C599",
+];
+const DISCOVERY_FEE_TIERS: &[u32] = &[100, 500, 3_000, 10_000];
+// In-range liquidity in whole tokens, sqrt(x * y) of the virtual reserves:
+// 100 WETH against 1M USDC in range is 10_000
+const DISCOVERY_MIN_LIQUIDITY: f64 = 10_000.0;
+const DISCOVERY_RECHECK_SECS: u64 = 300;
+// Finality mode: blocks a swap waits for before it is priced. 0 prices swaps
+// at once and retracts them if a reorg drops their block
+const CONFIRMATIONS: u64 = 0;
+// History replayed from eth_getLogs at startup, covering the rolling window,
+// and the widest block range per request
+const BACKFILL_SECS: u64 = MAX_ROLLING_WINDOW_DURATION / 1_000;
+const MAX_LOG_CHUNK_BLOCKS: u64 = 2_000;
+// Swaps queued between the pool subscription and the calculator, and what
+// happens when the queue is full
+const SWAP_CHANNEL_CAPACITY: usize = 10_000;
+const SWAP_CHANNEL_POLICY: BackpressurePolicy = BackpressurePolicy::Block;
 
 #[tokio::main]
 async fn main() {
@@ -39,46 +77,98 @@ async fn main() {
     eprintln!("Starting Uniswap Volatility Estimator. Press Ctrl+C to exit.");
 
     // Initialize Prometheus metrics
-    let (volatility_gauge, registry) = init_metrics();
+    let (volatility_gauge, tick_volatility_gauge, registry) = init_metrics();
+    let channel_metrics = ChannelMetrics::register(&registry).unwrap();
+    let pool_metrics = init_pool_metrics(&registry);
     
     // Shared state
-    let calculator = Arc::new(tokio::sync::Mutex::new(VolatilityCalculator::new(MAX_ROLLING_WINDOW_DURATION)));
     let is_running = Arc::new(AtomicBool::new(true));
 
     // Channel for decoupling fetch and process
-    let (tx, rx) = mpsc::unbounded_channel();
+    let (tx, rx) = bounded(SWAP_CHANNEL_CAPACITY, SWAP_CHANNEL_POLICY);
+    let channels = vec![("swaps", rx.monitor())];
     // Create Infura client
-    let client = match InfuraClient::new(INFURA_WS_URL, POOL_ADDRESS).await {
-        Ok(client) => client,
+    let client = match InfuraClient::new(INFURA_WS_URL).await {
+        Ok(client) => client
+            .with_confirmations(CONFIRMATIONS)
+            .with_backfill(BACKFILL_SECS)
+            .with_max_chunk_blocks(MAX_LOG_CHUNK_BLOCKS),
         Err(e) => {
             error!("Failed to create InfuraClient: {:?}", e);
             return;
         }
     };
+    // Resolve each pool's tokens and fee tier for pricing and labels
+    let addresses: Vec<Address> = POOL_ADDRESSES.iter().map(|address| address.parse().unwrap()).collect();
+    let pools = match client.resolve_pools(&addresses).await {
+        Ok(pools) if !pools.is_empty() => pools,
+        Ok(_) => {
+            error!("None of the configured pools could be resolved");
+            return;
+        }
+        Err(e) => {
+            error!("Failed to resolve pool metadata: {:?}", e);
+            return;
+        }
+    };
+    for pool in &pools {
+        info!(
+            "Watching {}/{} ({} bps) at {}",
+            pool.base().symbol,
+            pool.quote().symbol,
+            pool.fee as f64 / 100.0,
+            pool.instrument()
+        );
+    }
+    let client = client.with_pools(pools.clone());
+    let activity = client.activity();
+    let watched_pools = client.registry();
+    let discovery_client = client.clone();
 
     // Task 1: Fetch prices
     let fetch_task = tokio::spawn(fetch_prices_task(
         Arc::clone(&is_running),
         client,
         tx,
+        pools.iter().map(PoolConfig::instrument).collect(),
     ));
 
-    // Task 2: Process prices and calculate volatility
+    // Task 2: Process prices and calculate volatility per pool
     let process_task = tokio::spawn(process_prices_task(
         rx,
-        Arc::clone(&calculator),
+        watched_pools.clone(),
         Arc::clone(&volatility_gauge),
+        Arc::clone(&tick_volatility_gauge),
     ));
 
     // Task 3: Start Prometheus metrics server
     let metrics_task = tokio::spawn(metrics_server_task(
         Arc::clone(&registry),
+        watched_pools.clone(),
+    ));
+
+    // Task 4: Channel depth and drop metrics
+    let channel_task = tokio::spawn(channel_metrics.run(channels, Arc::clone(&is_running)));
+
+    // Task 5: Pool event counts and liquidity
+    let pool_task = tokio::spawn(pool_metrics_task(
+        activity,
+        watched_pools.clone(),
+        pool_metrics,
+        Arc::clone(&is_running),
     ));
 
+    // Task 6: Discover pools from the factory
+    if DISCOVERY_ENABLED {
+        tokio::spawn(discovery_task(discovery_client, Arc::clone(&is_running)));
+    }
+
     tokio::select! {
         _ = fetch_task => info!("WebSocket task exited."),
         _ = process_task => info!("Calculation task exited."),
         _ = metrics_task => info!("Metrics task exited."),
+        _ = channel_task => info!("Channel metrics task exited."),
+        _ = pool_task => info!("Pool metrics task exited."),
         _ = handle_shutdown_signal(Arc::clone(&is_running)) => info!("Shutdown signal received."),
     }
     info!("All tasks completed or shutdown signal processed.");
@@ -86,43 +176,268 @@ async fn main() {
 }
 
 
+// Metric labels identifying a pool
+const POOL_LABELS: [&str; 4] = ["pool", "token0", "token1", "fee"];
+
+struct PoolLabels {
+    pool: String,
+    token0: String,
+    token1: String,
+    fee: String,
+}
+
+impl PoolLabels {
+    fn new(pool: &PoolConfig) -> Self {
+        Self {
+            pool: pool.instrument(),
+            token0: pool.token0.symbol.clone(),
+            token1: pool.token1.symbol.clone(),
+            fee: pool.fee.to_string(),
+        }
+    }
+
+    fn values(&self) -> [&str; 4] {
+        [&self.pool, &self.token0, &self.token1, &self.fee]
+    }
+
+    // The pool's labels followed by an event type
+    fn with_event<'a>(&'a self, event: &'a str) -> [&'a str; 5] {
+        [&self.pool, &self.token0, &self.token1, &self.fee, event]
+    }
+}
+
 // Initialize Prometheus metrics
-fn init_metrics() -> (Arc<GaugeVec>, Arc<Registry>) {
+fn init_metrics() -> (Arc<GaugeVec>, Arc<GaugeVec>, Arc<Registry>) {
     // Create a new Prometheus registry
     let registry = Arc::new(Registry::new());
 
     // Create a new GaugeVec for volatility metrics
     let volatility_gauge = GaugeVec::new(
-        prometheus::Opts::new("uniswap_volatility", "Volatility for UniV3 ETHUSDC"),
-        &["symbol"],
+        prometheus::Opts::new("uniswap_volatility", "Volatility of UniV3 pool prices"),
+        &POOL_LABELS,
+    ).unwrap();
+
+    // Volatility of log returns between post-swap ticks
+    let tick_volatility_gauge = GaugeVec::new(
+        prometheus::Opts::new("uniswap_tick_volatility", "Log-return volatility from UniV3 pool swap ticks"),
+        &POOL_LABELS,
     ).unwrap();
 
-    // Register the GaugeVec with the registry
+    // Register the GaugeVecs with the registry
     registry.register(Box::new(volatility_gauge.clone())).unwrap();
-    info!("Volatility gauge registered successfully with Prometheus.");
+    registry.register(Box::new(tick_volatility_gauge.clone())).unwrap();
+    info!("Volatility gauges registered successfully with Prometheus.");
 
-    // Wrap the GaugeVec in Arc for shared ownership and return
-    (Arc::new(volatility_gauge), registry)
+    // Wrap the GaugeVecs in Arc for shared ownership and return
+    (Arc::new(volatility_gauge), Arc::new(tick_volatility_gauge), registry)
 }
 
 
-async fn fetch_prices_task(
+// Decoded pool events, decode failures and liquidity per pool, and the
+// subscription's gaps, reorgs and header fetches
+struct PoolMetrics {
+    events_counter: CounterVec,
+    decode_failures_counter: CounterVec,
+    liquidity_gauge: GaugeVec,
+    net_liquidity_gauge: GaugeVec,
+    tick_mismatch_counter: CounterVec,
+    retracted_swaps_counter: CounterVec,
+    log_gaps_counter: Counter,
+    backfilled_logs_counter: Counter,
+    reorgs_counter: Counter,
+    reorg_depth_histogram: Histogram,
+    header_fetches_counter: Counter,
+    monitored_pools_gauge: Gauge,
+}
+
+fn init_pool_metrics(registry: &Registry) -> PoolMetrics {
+    let event_labels = [POOL_LABELS[0], POOL_LABELS[1], POOL_LABELS[2], POOL_LABELS[3], "event"];
+    let events_counter = CounterVec::new(
+        prometheus::Opts::new("pool_events_total", "Pool logs decoded, by event type"),
+        &event_labels,
+    ).unwrap();
+    let decode_failures_counter = CounterVec::new(
+        prometheus::Opts::new("pool_decode_failures_total", "Pool logs that failed to decode, by event type"),
+        &event_labels,
+    ).unwrap();
+    let liquidity_gauge = GaugeVec::new(
+        prometheus::Opts::new("pool_liquidity", "In-range liquidity of the pool"),
+        &POOL_LABELS,
+    ).unwrap();
+    let net_liquidity_gauge = GaugeVec::new(
+        prometheus::Opts::new("pool_net_liquidity", "Liquidity minted minus burned since startup"),
+        &POOL_LABELS,
+    ).unwrap();
+    let tick_mismatch_counter = CounterVec::new(
+        prometheus::Opts::new("pool_tick_mismatches_total", "Swaps whose tick disagrees with their sqrtPriceX96"),
+        &POOL_LABELS,
+    ).unwrap();
+    let retracted_swaps_counter = CounterVec::new(
+        prometheus::Opts::new("swaps_retracted_total", "Priced swaps retracted because a reorg dropped their block"),
+        &POOL_LABELS,
+    ).unwrap();
+    let log_gaps_counter = Counter::new(
+        "pool_log_gaps_total",
+        "Resubscriptions that found pool logs missed while disconnected",
+    ).unwrap();
+    let backfilled_logs_counter = Counter::new(
+        "pool_backfilled_logs_total",
+        "Missed pool logs recovered with eth_getLogs",
+    ).unwrap();
+    let reorgs_counter = Counter::new(
+        "reorgs_total",
+        "Chain reorganizations that removed pool logs",
+    ).unwrap();
+    let reorg_depth_histogram = Histogram::with_opts(
+        HistogramOpts::new("reorg_depth_blocks", "Blocks orphaned by each chain reorganization")
+            .buckets(vec![1.0, 2.0, 3.0, 4.0, 6.0, 8.0, 16.0, 32.0, 64.0]),
+    ).unwrap();
+    let header_fetches_counter = Counter::new(
+        "pool_header_fetches_total",
+        "Block headers fetched because newHeads had not cached them",
+    ).unwrap();
+    registry.register(Box::new(events_counter.clone())).unwrap();
+    registry.register(Box::new(decode_failures_counter.clone())).unwrap();
+    registry.register(Box::new(liquidity_gauge.clone())).unwrap();
+    registry.register(Box::new(net_liquidity_gauge.clone())).unwrap();
+    registry.register(Box::new(tick_mismatch_counter.clone())).unwrap();
+    registry.register(Box::new(retracted_swaps_counter.clone())).unwrap();
+    registry.register(Box::new(log_gaps_counter.clone())).unwrap();
+    registry.register(Box::new(backfilled_logs_counter.clone())).unwrap();
+    registry.register(Box::new(reorgs_counter.clone())).unwrap();
+    registry.register(Box::new(reorg_depth_histogram.clone())).unwrap();
+    let monitored_pools_gauge = Gauge::new(
+        "monitored_pools",
+        "Pools watched, configured or discovered",
+    ).unwrap();
+    registry.register(Box::new(header_fetches_counter.clone())).unwrap();
+    registry.register(Box::new(monitored_pools_gauge.clone())).unwrap();
+
+    PoolMetrics {
+        events_counter,
+        decode_failures_counter,
+        liquidity_gauge,
+        net_liquidity_gauge,
+        tick_mismatch_counter,
+        retracted_swaps_counter,
+        log_gaps_counter,
+        backfilled_logs_counter,
+        reorgs_counter,
+        reorg_depth_histogram,
+        header_fetches_counter,
+        monitored_pools_gauge,
+    }
+}
+
+async fn pool_metrics_task(
+    activity: ActivityTracker,
+    pools: PoolRegistry,
+    metrics: PoolMetrics,
     is_running: Arc<AtomicBool>,
-    client: InfuraClient,
-    sender: mpsc::UnboundedSender<f64>,
 ) {
-    info!("Price fetching task started.");
+    // Counters are cumulative; remember what was already published
+    let mut published: HashMap<Address, PoolActivity> = HashMap::new();
+    let (mut log_gaps, mut backfilled_logs, mut reorgs, mut header_fetches) = (0, 0, 0, 0);
+    let mut reorg_depths: HashMap<u64, u64> = HashMap::new();
 
     while is_running.load(Ordering::SeqCst) {
-        match client.fetch_prices(&sender, 10).await {
-            Ok(batch_count) => {
-                info!("Fetched {} logs in this batch.", batch_count);
+        let snapshot = activity.snapshot();
+        metrics.monitored_pools_gauge.set(pools.len() as f64);
+        for (address, pool) in &snapshot.pools {
+            let Some(labels) = pools.get(address).map(|pool| PoolLabels::new(&pool)) else { continue };
+            let previous = published.insert(*address, pool.clone()).unwrap_or_default();
+            for (kind, count) in &pool.events {
+                let before = previous.events.get(kind).copied().unwrap_or(0);
+                metrics.events_counter
+                    .with_label_values(&labels.with_event(kind.as_str()))
+                    .inc_by((count - before) as f64);
             }
-            Err(e) => {
-                error!("Error fetching prices: {:?}", e);
-                warn!("Retrying in 10 seconds...");
-                tokio::time::sleep(Duration::from_secs(10)).await;
+            for (event, count) in &pool.decode_failures {
+                let before = previous.decode_failures.get(event).copied().unwrap_or(0);
+                metrics.decode_failures_counter
+                    .with_label_values(&labels.with_event(event))
+                    .inc_by((count - before) as f64);
             }
+            if let Some(liquidity) = pool.liquidity {
+                metrics.liquidity_gauge.with_label_values(&labels.values()).set(liquidity as f64);
+            }
+            metrics.net_liquidity_gauge.with_label_values(&labels.values()).set(pool.net_liquidity as f64);
+            metrics.tick_mismatch_counter
+                .with_label_values(&labels.values())
+                .inc_by((pool.tick_mismatches - previous.tick_mismatches) as f64);
+            metrics.retracted_swaps_counter
+                .with_label_values(&labels.values())
+                .inc_by((pool.retracted_swaps - previous.retracted_swaps) as f64);
+        }
+
+        metrics.log_gaps_counter.inc_by((snapshot.log_gaps - log_gaps) as f64);
+        metrics.backfilled_logs_counter.inc_by((snapshot.backfilled_logs - backfilled_logs) as f64);
+        metrics.reorgs_counter.inc_by((snapshot.reorgs - reorgs) as f64);
+        for (depth, count) in &snapshot.reorg_depths {
+            let before = reorg_depths.insert(*depth, *count).unwrap_or(0);
+            for _ in before..*count {
+                metrics.reorg_depth_histogram.observe(*depth as f64);
+            }
+        }
+        metrics.header_fetches_counter.inc_by((snapshot.header_fetches - header_fetches) as f64);
+        log_gaps = snapshot.log_gaps;
+        backfilled_logs = snapshot.backfilled_logs;
+        reorgs = snapshot.reorgs;
+        header_fetches = snapshot.header_fetches;
+
+        tokio::time::sleep(Duration::from_secs(1)).await;
+    }
+}
+
+async fn discovery_task(client: InfuraClient, is_running: Arc<AtomicBool>) {
+    let filter = DiscoveryFilter::new()
+        .with_tokens(DISCOVERY_TOKENS.iter().map(|token| token.parse().unwrap()))
+        .with_fee_tiers(DISCOVERY_FEE_TIERS.iter().copied())
+        .with_min_liquidity(DISCOVERY_MIN_LIQUIDITY);
+    let mut discovery = PoolDiscovery::new(filter).with_recheck(Duration::from_secs(DISCOVERY_RECHECK_SECS));
+
+    while is_running.load(Ordering::SeqCst) {
+        match discovery.run(&client).await {
+            Ok(()) => warn!("Factory subscription ended"),
+            Err(e) => error!("Pool discovery failed: {:?}", e),
+        }
+        warn!("Resuming pool discovery in 10 seconds...");
+        tokio::time::sleep(Duration::from_secs(10)).await;
+    }
+}
+
+async fn fetch_prices_task(
+    is_running: Arc<AtomicBool>,
+    mut source: impl PriceSource,
+    sender: EventSender<MarketEvent>,
+    pools: Vec<String>,
+) {
+    info!("Price fetching task started.");
+
+    while let Err(e) = source.connect().await {
+        error!("Failed to connect to {}: {}", source.venue(), e);
+        warn!("Retrying in 10 seconds...");
+        tokio::time::sleep(Duration::from_secs(10)).await;
+    }
+    if let Err(e) = source.subscribe(&pools).await {
+        error!("Failed to subscribe to {} pools: {}", pools.len(), e);
+        return;
+    }
+    let mut ticks = match source.ticks() {
+        Ok(ticks) => ticks,
+        Err(e) => {
+            error!("No tick stream from {}: {}", source.venue(), e);
+            return;
+        }
+    };
+
+    while is_running.load(Ordering::SeqCst) {
+        let tick = match ticks.next().await {
+            Some(tick) => tick,
+            None => break,
+        };
+        if sender.send(tick).await.is_err() {
+            break;
         }
     }
 
@@ -130,37 +445,110 @@ async fn fetch_prices_task(
 }
 
 async fn process_prices_task(
-    mut receiver: mpsc::UnboundedReceiver<f64>,
-    calculator: Arc<tokio::sync::Mutex<VolatilityCalculator>>,
+    mut receiver: EventReceiver<MarketEvent>,
+    pools: PoolRegistry,
     volatility_gauge: Arc<GaugeVec>,
+    tick_volatility_gauge: Arc<GaugeVec>,
 ) {
-    while let Some(price) = receiver.recv().await {
+    // Rolling windows and labels of each pool, by instrument, created on its
+    // first swap so discovered pools get theirs too
+    let mut calculators: HashMap<String, (VolatilityCalculator, TickVolatilityCalculator, PoolLabels)> = HashMap::new();
+
+    while let Some(event) = receiver.recv().await {
+        if !calculators.contains_key(&event.instrument) {
+            let Some(pool) = event.instrument.parse().ok().and_then(|address| pools.get(&address)) else {
+                warn!("Skipping swap from unknown pool {}", event.instrument);
+                continue;
+            };
+            calculators.insert(
+                event.instrument.clone(),
+                (
+                    VolatilityCalculator::new(MAX_ROLLING_WINDOW_DURATION),
+                    TickVolatilityCalculator::new(MAX_ROLLING_WINDOW_DURATION),
+                    PoolLabels::new(&pool),
+                ),
+            );
+        }
+        let Some((calc, tick_calc, labels)) = calculators.get_mut(&event.instrument) else { continue };
 
-        let mut calc = calculator.lock().await;
-        calc.add_value(price); // Add price to the rolling window
+        // Swaps carry their log position, so one dropped by a reorg can be retracted
+        let (key, removed) = match event.detail {
+            EventDetail::Swap { block_number: Some(block), log_index: Some(index), removed, .. } => {
+                (Some((block, index)), removed)
+            }
+            EventDetail::Swap { removed, .. } => (None, removed),
+            _ => (None, false),
+        };
+
+        // Windows run on chain time; the client stamps every swap it sends
+        let Some(event_time) = event.event_time else {
+            warn!("Skipping swap without a block time from pool {}", event.instrument);
+            continue;
+        };
+        let timestamp = UNIX_EPOCH + Duration::from_millis(event_time);
+
+        if let EventDetail::Swap { tick, .. } = event.detail {
+            match (key, removed) {
+                (Some(key), true) => {
+                    tick_calc.remove_tick(key);
+                }
+                (Some(key), false) => tick_calc.add_keyed_tick(key, timestamp, tick),
+                (None, false) => tick_calc.add_tick_at(timestamp, tick),
+                (None, true) => {}
+            }
+            if let Some(volatility) = tick_calc.calculate_volatility() {
+                tick_volatility_gauge
+                    .with_label_values(&labels.values())
+                    .set(volatility);
+            }
+        }
+
+        match (key, removed) {
+            (Some(key), true) => {
+                if calc.remove_value(key) {
+                    info!("Retracted swap at block {} log {} in pool {}", key.0, key.1, event.instrument);
+                }
+            }
+            (Some(key), false) => calc.add_keyed_value(key, timestamp, event.price), // Add price to the rolling window
+            (None, false) => calc.add_value_at(timestamp, event.price),
+            (None, true) => {}
+        }
 
         // Calculate and update volatility
         if let Some(volatility) = calc.calculate_volatility() {
             volatility_gauge
-                .with_label_values(&[SYMBOL])
+                .with_label_values(&labels.values())
                 .set(volatility); 
-            info!("Volatility Gauge updated for {}: {:.6}", SYMBOL, volatility);
+            info!("Volatility Gauge updated for {}: {:.6}", event.instrument, volatility);
         } else {
-                info!("{}: Not enough data for volatility calculation", SYMBOL);
+                info!("{}: Not enough data for volatility calculation", event.instrument);
         }
         
     }
     info!("Volatility calculation task exiting.");
 }
 
-async fn metrics_server_task(registry: Arc<Registry>) {
+async fn metrics_server_task(registry: Arc<Registry>, pools: PoolRegistry) {
     let addr = ([127, 0, 0, 1], 8081).into();
     let make_svc = make_service_fn(move |_conn| {
         let registry = Arc::clone(&registry);
+        let pools = pools.clone();
         async move {
-            Ok::<_, hyper::Error>(service_fn(move |_req| {
+            Ok::<_, hyper::Error>(service_fn(move |req| {
                 let registry = Arc::clone(&registry);
+                let pools = pools.clone();
                 async move {
+                    // The watched pools, configured or discovered, as JSON
+                    if req.uri().path() == "/pools" {
+                        let body = serde_json::to_vec(&pools.list()).unwrap_or_default();
+                        return Ok::<_, hyper::Error>(
+                            Response::builder()
+                                .header("Content-Type", "application/json")
+                                .body(Body::from(body))
+                                .unwrap(),
+                        );
+                    }
+
                     let encoder = TextEncoder::new();
                     let mut buffer = Vec::new();
 
@@ -187,7 +575,7 @@ async fn metrics_server_task(registry: Arc<Registry>) {
         }
     });
 
-    info!("Starting Prometheus metrics server at http://127.0.0.1:8081");
+    info!("Starting Prometheus metrics server at http://127.0.0.1:8081, pools listed at /pools");
     let server = Server::bind(&addr).serve(make_svc);
 
     // Await the server and handle errors
@@ -2,8 +2,15 @@ use std::collections::VecDeque;
 use std::time::{Duration, SystemTime};
 use tracing::{warn, debug};
 
+pub mod tick;
+
+pub use tick::TickVolatilityCalculator;
+
+/// Identifies a sample so it can be retracted, e.g. (block number, log index).
+pub type SampleKey = (u64, u64);
+
 pub struct VolatilityCalculator {
-    window: VecDeque<(SystemTime, f64)>, // Stores (timestamp, price)
+    window: VecDeque<(SystemTime, f64, Option<SampleKey>)>, // Stores (timestamp, price, key)
     max_duration: Duration,              // Maximum window size in time
 }
 
@@ -18,23 +25,48 @@ impl VolatilityCalculator {
 
     /// Add a new price value to the rolling window.
     pub fn add_value(&mut self, price: f64) {
+        self.push(SystemTime::now(), price, None);
+    }
+
+    /// Add a price observed at `timestamp`, e.g. its block time.
+    pub fn add_value_at(&mut self, timestamp: SystemTime, price: f64) {
+        self.push(timestamp, price, None);
+    }
+
+    /// Add a price observed at `timestamp`, e.g. its block time, that may
+    /// later be retracted with [`Self::remove_value`].
+    pub fn add_keyed_value(&mut self, key: SampleKey, timestamp: SystemTime, price: f64) {
+        self.push(timestamp, price, Some(key));
+    }
+
+    /// Remove the price added under `key`, if it is still in the window.
+    pub fn remove_value(&mut self, key: SampleKey) -> bool {
+        match self.window.iter().position(|(_, _, k)| *k == Some(key)) {
+            Some(index) => self.window.remove(index).is_some(),
+            None => false,
+        }
+    }
+
+    // Inserts in timestamp order, so a late price never evicts newer ones;
+    // one already older than the window is rejected
+    fn push(&mut self, timestamp: SystemTime, price: f64, key: Option<SampleKey>) {
         debug!("Adding price: {}", price);
-        let now = SystemTime::now();
 
-        // Add the new price with the current timestamp
-        self.window.push_back((now, price));
+        let newest = self.window.back().map_or(timestamp, |(newest, _, _)| (*newest).max(timestamp));
+        if newest.duration_since(timestamp).is_ok_and(|age| age > self.max_duration) {
+            warn!("Rejecting a price older than the window: {:?}", timestamp);
+            return;
+        }
+        let index = self.window.partition_point(|(t, _, _)| *t <= timestamp);
+        self.window.insert(index, (timestamp, price, key));
 
         // Remove prices outside the rolling window
-        while let Some((timestamp, _)) = self.window.front() {
-            if let Ok(duration) = now.duration_since(*timestamp) {
-                if duration > self.max_duration {
+        while let Some((oldest, _, _)) = self.window.front() {
+            match newest.duration_since(*oldest) {
+                Ok(age) if age > self.max_duration => {
                     self.window.pop_front(); // Remove the oldest value
-                } else {
-                    break;
                 }
-            } else {
-                warn!("Encountered a timestamp in the future: {:?}", timestamp);
-                self.window.pop_front();
+                _ => break,
             }
         }
     }
@@ -45,7 +77,7 @@ impl VolatilityCalculator {
             return None; // Not enough data points for calculation
         }
         debug!("Rolling window contains: {:?}", self.window);
-        let prices: Vec<f64> = self.window.iter().map(|(_, price)| *price).collect();
+        let prices: Vec<f64> = self.window.iter().map(|(_, price, _)| *price).collect();
         debug!("Prices: {:?}", prices);
         let mean: f64 = prices.iter().sum::<f64>() / prices.len() as f64;
         let variance: f64 = prices.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / prices.len() as f64;
@@ -0,0 +1,91 @@
+use std::collections::VecDeque;
+use std::time::{Duration, SystemTime};
+use tracing::{debug, warn};
+
+use super::SampleKey;
+
+/// ln(1.0001): a one-tick move is exactly this log-price return.
+pub const LN_TICK_BASE: f64 = 9.999_500_033_329_732e-5;
+
+/// Realized volatility from a pool's post-swap ticks.
+///
+/// A tick is `floor(log_1.0001(price))`, so the difference of two ticks is a
+/// log return measured in units of ln(1.0001). Returns are computed on the
+/// integer ticks and scaled once, with no price reconstruction. Token
+/// decimals and price orientation only shift or negate ticks, so they do not
+/// change the result.
+pub struct TickVolatilityCalculator {
+    window: VecDeque<(SystemTime, i32, Option<SampleKey>)>, // Stores (timestamp, tick, key)
+    max_duration: Duration,              // Maximum window size in time
+}
+
+impl TickVolatilityCalculator {
+    /// Create a new calculator with a rolling window duration in milliseconds.
+    pub fn new(max_duration_millis: u64) -> Self {
+        TickVolatilityCalculator {
+            window: VecDeque::new(),
+            max_duration: Duration::from_millis(max_duration_millis),
+        }
+    }
+
+    /// Add a tick observed now to the rolling window.
+    pub fn add_tick(&mut self, tick: i32) {
+        self.add_tick_at(SystemTime::now(), tick);
+    }
+
+    /// Add a tick observed at `timestamp`, expiring ticks older than the window.
+    pub fn add_tick_at(&mut self, timestamp: SystemTime, tick: i32) {
+        self.push(timestamp, tick, None);
+    }
+
+    /// Add a tick observed at `timestamp` that may later be retracted with
+    /// [`Self::remove_tick`].
+    pub fn add_keyed_tick(&mut self, key: SampleKey, timestamp: SystemTime, tick: i32) {
+        self.push(timestamp, tick, Some(key));
+    }
+
+    /// Remove the tick added under `key`, if it is still in the window.
+    pub fn remove_tick(&mut self, key: SampleKey) -> bool {
+        match self.window.iter().position(|(_, _, k)| *k == Some(key)) {
+            Some(index) => self.window.remove(index).is_some(),
+            None => false,
+        }
+    }
+
+    // Inserts in timestamp order, so returns are taken between neighbours in
+    // time and a late tick never evicts newer ones; one already older than
+    // the window is rejected
+    fn push(&mut self, timestamp: SystemTime, tick: i32, key: Option<SampleKey>) {
+        debug!("Adding tick: {}", tick);
+
+        let newest = self.window.back().map_or(timestamp, |(newest, _, _)| (*newest).max(timestamp));
+        if newest.duration_since(timestamp).is_ok_and(|age| age > self.max_duration) {
+            warn!("Rejecting a tick older than the window: {:?}", timestamp);
+            return;
+        }
+        let index = self.window.partition_point(|(t, _, _)| *t <= timestamp);
+        self.window.insert(index, (timestamp, tick, key));
+
+        while let Some((oldest, _, _)) = self.window.front() {
+            match newest.duration_since(*oldest) {
+                Ok(age) if age > self.max_duration => {
+                    self.window.pop_front();
+                }
+                _ => break,
+            }
+        }
+    }
+
+    /// Standard deviation of the log returns between consecutive ticks.
+    pub fn calculate_volatility(&self) -> Option<f64> {
+        if self.window.len() < 5 {
+            return None; // Not enough data points for calculation
+        }
+        let ticks: Vec<i64> = self.window.iter().map(|(_, tick, _)| *tick as i64).collect();
+        let moves: Vec<f64> = ticks.windows(2).map(|pair| (pair[1] - pair[0]) as f64).collect();
+        let mean = moves.iter().sum::<f64>() / moves.len() as f64;
+        let variance = moves.iter().map(|m| (m - mean).powi(2)).sum::<f64>() / moves.len() as f64;
+
+        Some(variance.sqrt() * LN_TICK_BASE)
+    }
+}
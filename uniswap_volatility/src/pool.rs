@@ -0,0 +1,205 @@
+use primitive_types::{U256, U512};
+use serde::Serialize;
+use web3::types::Address;
+
+use crate::math::tick::LN_TICK_BASE;
+
+/// One side of a pool.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TokenInfo {
+    pub symbol: String,
+    pub decimals: u8,
+}
+
+impl TokenInfo {
+    pub fn new(symbol: &str, decimals: u8) -> Self {
+        Self {
+            symbol: symbol.to_string(),
+            decimals,
+        }
+    }
+}
+
+/// Which token a pool's price is quoted for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum Orientation {
+    /// Price of token0 in units of token1, Uniswap's native direction.
+    Token0Base,
+    /// Price of token1 in units of token0.
+    Token1Base,
+}
+
+/// Tokens prices are usually quoted in, most preferred first.
+pub const QUOTE_TOKENS: &[&str] = &["USDC", "USDT", "DAI", "WETH", "WBTC"];
+
+impl Orientation {
+    /// Prices the token that is not a quote token, or the less preferred of
+    /// two, so USDC/WETH gives WETH in USDC. Token0 is priced when neither is
+    /// a quote token.
+    pub fn infer(token0: &TokenInfo, token1: &TokenInfo) -> Self {
+        let rank = |token: &TokenInfo| {
+            QUOTE_TOKENS
+                .iter()
+                .position(|symbol| symbol.eq_ignore_ascii_case(&token.symbol))
+                .unwrap_or(QUOTE_TOKENS.len())
+        };
+        if rank(token0) < rank(token1) {
+            Orientation::Token1Base
+        } else {
+            Orientation::Token0Base
+        }
+    }
+}
+
+/// A pool to watch: its address, fee tier, and the token metadata needed to
+/// turn its `sqrtPriceX96` into a price.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PoolConfig {
+    pub address: Address,
+    pub fee: u32, // Fee tier in hundredths of a basis point, 500 for 0.05%
+    pub tick_spacing: Option<i32>, // Known once resolved on chain
+    pub token0: TokenInfo,
+    pub token1: TokenInfo,
+    pub orientation: Orientation,
+}
+
+impl PoolConfig {
+    pub fn new(
+        address: Address,
+        fee: u32,
+        token0: TokenInfo,
+        token1: TokenInfo,
+        orientation: Orientation,
+    ) -> Self {
+        Self {
+            address,
+            fee,
+            tick_spacing: None,
+            token0,
+            token1,
+            orientation,
+        }
+    }
+
+    /// The instrument the pool's swaps are labelled with: its full address
+    /// in lowercase hex.
+    pub fn instrument(&self) -> String {
+        format!("{:?}", self.address)
+    }
+
+    /// The token being priced.
+    pub fn base(&self) -> &TokenInfo {
+        match self.orientation {
+            Orientation::Token0Base => &self.token0,
+            Orientation::Token1Base => &self.token1,
+        }
+    }
+
+    /// The token the price is expressed in.
+    pub fn quote(&self) -> &TokenInfo {
+        match self.orientation {
+            Orientation::Token0Base => &self.token1,
+            Orientation::Token1Base => &self.token0,
+        }
+    }
+
+    /// Price of the base token in whole quote tokens at `sqrt_price_x96`.
+    pub fn price(&self, sqrt_price_x96: U256) -> Option<f64> {
+        sqrt_price_x96_to_price(
+            sqrt_price_x96,
+            self.token0.decimals,
+            self.token1.decimals,
+            self.orientation,
+        )
+    }
+
+    /// In-range liquidity `L` in whole tokens. `L` is sqrt(x * y) of the
+    /// virtual reserves in raw units, so it scales with 10^((decimals0 +
+    /// decimals1) / 2); removing that makes pools of any decimals comparable.
+    pub fn whole_token_liquidity(&self, liquidity: u128) -> f64 {
+        let decimals = f64::from(self.token0.decimals) + f64::from(self.token1.decimals);
+        liquidity as f64 / 10f64.powf(decimals / 2.0)
+    }
+}
+
+/// The mainnet USDC/WETH 0.05% pool, priced in USDC per WETH.
+impl Default for PoolConfig {
+    fn default() -> Self {
+        let mut pool = Self::new(
+            "0x88e6A0c2dDD26FEEb64F039a2c41296FcB3f5640".parse().unwrap(),
+            500,
+            TokenInfo::new("USDC", 6),
+            TokenInfo::new("WETH", 18),
+            Orientation::Token1Base,
+        );
+        pool.tick_spacing = Some(10);
+        pool
+    }
+}
+
+// Keeps sqrtPriceX96^2 · 10^difference, shifted for the division, inside 512 bits
+const MAX_DECIMALS_DIFFERENCE: usize = 38;
+
+/// Converts a `sqrtPriceX96` into a price in whole tokens. `None` for a zero
+/// price, or tokens whose decimals differ by more than 38.
+///
+/// `sqrtPriceX96^2 / 2^192` is token1 per token0 in raw units. The ratio and
+/// the decimal adjustment stay integers in a U512, and only the final
+/// quotient is rounded to `f64`.
+pub fn sqrt_price_x96_to_price(
+    sqrt_price_x96: U256,
+    token0_decimals: u8,
+    token1_decimals: u8,
+    orientation: Orientation,
+) -> Option<f64> {
+    let exponent = (token0_decimals as i32 - token1_decimals as i32).unsigned_abs() as usize;
+    if sqrt_price_x96.is_zero() || exponent > MAX_DECIMALS_DIFFERENCE {
+        return None;
+    }
+    let sqrt_price = U512::from(sqrt_price_x96);
+    let mut num = sqrt_price * sqrt_price; // token1 per token0, raw, scaled by 2^192
+    let mut den = U512::one() << 192;
+
+    // Whole tokens: multiply by 10^(decimals0 - decimals1)
+    let scale = U512::exp10(exponent);
+    if token0_decimals >= token1_decimals {
+        num *= scale;
+    } else {
+        den *= scale;
+    }
+
+    match orientation {
+        Orientation::Token0Base => Some(ratio_to_f64(num, den)),
+        Orientation::Token1Base => Some(ratio_to_f64(den, num)),
+    }
+}
+
+// num / den rounded once: shift so the integer quotient keeps ~64 significant bits
+fn ratio_to_f64(num: U512, den: U512) -> f64 {
+    let shift = 64 + den.bits() as i32 - num.bits() as i32;
+    let quotient = if shift >= 0 {
+        (num << shift as usize) / den
+    } else {
+        num / (den << (-shift) as usize)
+    };
+    quotient.low_u128() as f64 * 2f64.powi(-shift)
+}
+
+/// The tick `sqrtPriceX96` falls in: `floor(log_1.0001(sqrtPriceX96^2 / 2^192))`.
+pub fn sqrt_price_x96_to_tick(sqrt_price_x96: U256) -> Option<i32> {
+    if sqrt_price_x96.is_zero() {
+        return None;
+    }
+    // ln of the 160-bit value from its top 64 bits, then remove the 2^96 scale
+    let shift = sqrt_price_x96.bits().saturating_sub(64);
+    let ln_sqrt_price = ((sqrt_price_x96 >> shift).low_u64() as f64).ln()
+        + (shift as f64 - 96.0) * std::f64::consts::LN_2;
+    Some((2.0 * ln_sqrt_price / LN_TICK_BASE).floor() as i32)
+}
+
+/// Whether a swap's `tick` agrees with its `sqrtPriceX96`. A swap that ends
+/// exactly on a tick boundary while moving down reports the tick below, so
+/// one tick of slack is allowed.
+pub fn tick_matches_sqrt_price(tick: i32, sqrt_price_x96: U256) -> bool {
+    sqrt_price_x96_to_tick(sqrt_price_x96).is_some_and(|expected| (expected - tick).abs() <= 1)
+}
@@ -0,0 +1,804 @@
+use futures_util::{SinkExt, StreamExt};
+use serde_json::{json, Value};
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+use tokio::net::TcpListener;
+use tokio_tungstenite::tungstenite::Message;
+use uniswap_volatility::client::{
+    backfill_start, decode_aggregate3, decode_swap, encode_aggregate3, is_too_many_results, logs_after, swap_event,
+    ActivityTracker, AdaptiveChunker, BlockTime, DecodeError, DiscoveryFilter, HeaderCache, InfuraClient, LogCursor, MetadataCache,
+    MetadataCall, PoolActivity, PoolCreated, PoolEvent, PoolEventKind, PoolMetadata, PoolRegistry, ReorgBuffer,
+    SubscriptionActivity, Swap,
+};
+use uniswap_volatility::client::metadata::{decode_symbol, decode_token};
+use uniswap_volatility::math::{TickVolatilityCalculator, VolatilityCalculator};
+use uniswap_volatility::pool::{
+    sqrt_price_x96_to_price, sqrt_price_x96_to_tick, tick_matches_sqrt_price, Orientation, PoolConfig, TokenInfo,
+};
+use volatility_core::channel::{bounded, BackpressurePolicy, EventReceiver};
+use volatility_core::event::{EventDetail, EventKind, MarketEvent};
+use volatility_core::source::PriceSource;
+use web3::ethabi::{encode, Token};
+use web3::types::{Address, Block, Bytes, Log, H2048, H256, U256};
+
+fn log(topics: Vec<H256>, data: Vec<u8>) -> Log {
+    Log {
+        address: Default::default(),
+        topics,
+        data: Bytes(data),
+        block_hash: None,
+        block_number: None,
+        transaction_hash: None,
+        transaction_index: None,
+        log_index: None,
+        transaction_log_index: None,
+        log_type: None,
+        removed: None,
+    }
+}
+
+// An indexed address or int24 as a topic
+fn address_topic(byte: u8) -> H256 {
+    H256::from(Address::repeat_byte(byte))
+}
+
+fn tick_topic(tick: i32) -> H256 {
+    let word = if tick < 0 { negative(tick.unsigned_abs() as u128) } else { U256::from(tick) };
+    let mut topic = H256::zero();
+    word.to_big_endian(topic.as_bytes_mut());
+    topic
+}
+
+fn swap_topics() -> Vec<H256> {
+    vec![PoolEventKind::Swap.topic0(), address_topic(1), address_topic(2)]
+}
+
+// Two's-complement encoding of a negative int256
+fn negative(value: u128) -> U256 {
+    !U256::from(value) + U256::one()
+}
+
+#[test]
+fn test_decode_swap_into_market_event() {
+    // 1 WETH bought for 2000 USDC at sqrtPriceX96 for ~2000 USDC/WETH
+    let sqrt_price_x96 = U256::from_dec_str("1771595571142957166518320255467520").unwrap();
+    let data = encode(&[
+        Token::Int(U256::from(2_000_000_000u64)),
+        Token::Int(negative(1_000_000_000_000_000_000)),
+        Token::Uint(sqrt_price_x96),
+        Token::Uint(U256::from(5_000_000_000_000_000_000u128)),
+        Token::Int(negative(200_311)),
+    ]);
+    let swap = decode_swap(&log(swap_topics(), data.clone()), "ethusdc", &PoolConfig::default()).unwrap();
+    assert_eq!((swap.source.as_str(), swap.instrument.as_str(), swap.kind()), ("uniswap", "ethusdc", EventKind::Swap));
+    assert!((swap.price - 2000.0).abs() < 0.01, "price was {}", swap.price);
+    assert_eq!(swap.event_time, None);
+    assert_eq!(
+        swap.detail,
+        EventDetail::Swap {
+            amount0: 2_000_000_000,
+            amount1: -1_000_000_000_000_000_000,
+            liquidity: 5_000_000_000_000_000_000,
+            tick: -200_311,
+            block_number: None,
+            log_index: None,
+            removed: false,
+        }
+    );
+
+    assert!(decode_swap(&log(swap_topics(), vec![]), "ethusdc", &PoolConfig::default()).is_none());
+    // Swap data under another event's topic is not priced
+    let mut topics = swap_topics();
+    topics[0] = PoolEventKind::Collect.topic0();
+    assert!(decode_swap(&log(topics, data), "ethusdc", &PoolConfig::default()).is_none());
+}
+
+fn assert_close(actual: Option<f64>, expected: f64) {
+    let actual = actual.unwrap();
+    assert!(((actual - expected) / expected).abs() < 1e-12, "expected {}, got {}", expected, actual);
+}
+
+#[test]
+fn test_sqrt_price_decoding_by_decimals_and_orientation() {
+    // WBTC (8 decimals) / WETH (18 decimals) at 15.5 WETH per WBTC
+    let wbtc_weth = U256::from_dec_str("31192158774040239845801218050570798").unwrap();
+    let pool = PoolConfig::new(
+        Address::zero(),
+        3_000,
+        TokenInfo::new("WBTC", 8),
+        TokenInfo::new("WETH", 18),
+        Orientation::Token0Base,
+    );
+    assert_eq!((pool.base().symbol.as_str(), pool.quote().symbol.as_str()), ("WBTC", "WETH"));
+    assert_close(pool.price(wbtc_weth), 15.5);
+    assert_close(sqrt_price_x96_to_price(wbtc_weth, 8, 18, Orientation::Token1Base), 1.0 / 15.5);
+
+    // USDC / USDT, both 6 decimals
+    let stable = U256::from_dec_str("79232123823359799118286999567").unwrap();
+    assert_close(sqrt_price_x96_to_price(stable, 6, 6, Orientation::Token0Base), 1.0001);
+
+    // The extremes of the uint160 range decode without overflow
+    let max_sqrt_ratio = U256::from_dec_str("1461446703485210103287273052203988822378723970342").unwrap();
+    let max = sqrt_price_x96_to_price(max_sqrt_ratio, 0, 36, Orientation::Token1Base).unwrap();
+    assert!(max.is_finite() && max > 0.0);
+    let min = sqrt_price_x96_to_price(U256::from(4_295_128_739u64), 36, 0, Orientation::Token1Base).unwrap();
+    assert!(min.is_finite() && min > 0.0);
+
+    assert_eq!(sqrt_price_x96_to_price(U256::zero(), 6, 18, Orientation::Token1Base), None);
+    assert_eq!(sqrt_price_x96_to_price(wbtc_weth, 0, 40, Orientation::Token0Base), None);
+}
+
+#[test]
+fn test_pool_events_dispatch_on_topic0() {
+    // keccak256 of the Swap signature, as emitted by every V3 pool
+    assert_eq!(
+        format!("{:?}", PoolEventKind::Swap.topic0()),
+        "0xc42079f94a6350d7e6235f29174924f928cc2ac818eb64fed8004e115fbcca67"
+    );
+
+    let mint = log(
+        vec![PoolEventKind::Mint.topic0(), address_topic(3), tick_topic(-200_400), tick_topic(-200_200)],
+        encode(&[
+            Token::Address(Address::repeat_byte(4)),
+            Token::Uint(U256::from(1_000u64)),
+            Token::Uint(U256::from(5u64)),
+            Token::Uint(U256::from(6u64)),
+        ]),
+    );
+    let burn = log(
+        vec![PoolEventKind::Burn.topic0(), address_topic(3), tick_topic(-200_400), tick_topic(-200_200)],
+        encode(&[Token::Uint(U256::from(400u64)), Token::Uint(U256::from(2u64)), Token::Uint(U256::from(3u64))]),
+    );
+    let swap = log(
+        swap_topics(),
+        encode(&[
+            Token::Int(U256::from(1u64)),
+            Token::Int(negative(1)),
+            Token::Uint(U256::from_dec_str("1771595571142957166518320255467520").unwrap()),
+            Token::Uint(U256::from(10_000u64)),
+            Token::Int(negative(200_311)),
+        ]),
+    );
+
+    let PoolEvent::Mint(decoded) = PoolEvent::decode(&mint).unwrap() else { panic!("expected a mint") };
+    assert_eq!((decoded.sender, decoded.owner), (Address::repeat_byte(4), Address::repeat_byte(3)));
+    assert_eq!((decoded.tick_lower, decoded.tick_upper, decoded.amount), (-200_400, -200_200, 1_000));
+    assert_eq!(PoolEvent::decode(&burn).unwrap().kind(), PoolEventKind::Burn);
+
+    // Unknown topics, missing topics and truncated data are errors, not prices
+    let unknown = H256::repeat_byte(9);
+    assert_eq!(PoolEvent::decode(&log(vec![unknown], vec![])), Err(DecodeError::UnknownTopic(unknown)));
+    assert_eq!(PoolEvent::decode(&log(vec![], vec![])), Err(DecodeError::MissingTopic));
+    let truncated = log(mint.topics.clone(), vec![0; 32]);
+    assert_eq!(PoolEvent::decode(&truncated), Err(DecodeError::Malformed(PoolEventKind::Mint)));
+
+    // Liquidity follows the swap, then in-range mints and burns
+    let mut activity = PoolActivity::default();
+    for log in [&swap, &mint, &burn] {
+        activity.apply(&PoolEvent::decode(log).unwrap());
+    }
+    activity.record_failure(&PoolEvent::decode(&truncated).unwrap_err());
+    assert_eq!((activity.liquidity, activity.tick, activity.net_liquidity), (Some(10_600), Some(-200_311), 600));
+    assert_eq!(activity.events.get(&PoolEventKind::Mint), Some(&1));
+    assert_eq!(activity.decode_failures.get("mint"), Some(&1));
+}
+
+#[test]
+fn test_tick_volatility_and_consistency_check() {
+    let start = SystemTime::now();
+    let mut calculator = TickVolatilityCalculator::new(60_000);
+    for (i, tick) in [200_000, 200_010, 200_000, 200_010].iter().enumerate() {
+        calculator.add_tick_at(start + Duration::from_secs(i as u64), *tick);
+    }
+    assert_eq!(calculator.calculate_volatility(), None);
+    calculator.add_tick_at(start + Duration::from_secs(4), 200_000);
+
+    // Ten-tick moves are log returns of ±10·ln(1.0001)
+    let returns: Vec<f64> = [1.0001f64.powi(10).ln(), -1.0001f64.powi(10).ln()].repeat(2);
+    let mean = returns.iter().sum::<f64>() / returns.len() as f64;
+    let expected = (returns.iter().map(|r| (r - mean).powi(2)).sum::<f64>() / returns.len() as f64).sqrt();
+    assert_close(calculator.calculate_volatility(), expected);
+
+    // Ticks older than the window drop out
+    calculator.add_tick_at(start + Duration::from_secs(120), 200_000);
+    assert_eq!(calculator.calculate_volatility(), None);
+
+    let sqrt_price_x96 = U256::from_dec_str("1771595571142957166518320255467520").unwrap();
+    assert_eq!(sqrt_price_x96_to_tick(sqrt_price_x96), Some(200_311));
+    assert_eq!(sqrt_price_x96_to_tick(U256::one() << 96), Some(0));
+    assert_eq!(sqrt_price_x96_to_tick(U256::from(4_295_128_739u64)), Some(-887_272));
+    assert!(tick_matches_sqrt_price(200_311, sqrt_price_x96));
+    assert!(tick_matches_sqrt_price(200_310, sqrt_price_x96)); // Ended on a boundary moving down
+    assert!(!tick_matches_sqrt_price(-200_311, sqrt_price_x96));
+
+    let mut swap = Swap {
+        sender: Address::zero(),
+        recipient: Address::zero(),
+        amount0: 0,
+        amount1: 0,
+        sqrt_price_x96,
+        liquidity: 1,
+        tick: 200_311,
+    };
+    let mut activity = PoolActivity::default();
+    activity.apply(&PoolEvent::Swap(swap.clone()));
+    swap.tick = 200_411;
+    assert!(!swap.tick_consistent());
+    activity.apply(&PoolEvent::Swap(swap));
+    assert_eq!(activity.tick_mismatches, 1);
+}
+
+#[test]
+fn test_backfill_resumes_after_last_processed_log() {
+    let positioned = |block: u64, index: u64| Log {
+        block_number: Some(block.into()),
+        log_index: Some(index.into()),
+        ..log(swap_topics(), vec![])
+    };
+    assert_eq!(LogCursor::of(&positioned(7, 3)), Some(LogCursor { block: 7, log_index: 3 }));
+    assert_eq!(LogCursor::of(&log(vec![], vec![])), None);
+
+    // eth_getLogs starts at the cursor's block, so earlier logs in it are dropped
+    let cursor = LogCursor { block: 100, log_index: 4 };
+    let fetched = vec![positioned(101, 0), positioned(100, 2), positioned(100, 4), positioned(100, 9), log(vec![], vec![])];
+    let missed: Vec<Option<LogCursor>> = logs_after(Some(cursor), fetched).iter().map(LogCursor::of).collect();
+    assert_eq!(
+        missed,
+        vec![Some(LogCursor { block: 100, log_index: 9 }), Some(LogCursor { block: 101, log_index: 0 })]
+    );
+
+    let mut activity = SubscriptionActivity::default();
+    activity.record_gap(2);
+    activity.record_gap(3);
+    assert_eq!((activity.log_gaps, activity.backfilled_logs), (2, 5));
+}
+
+fn swap_at(block: u64, log_index: u64, price: f64) -> MarketEvent {
+    MarketEvent {
+        source: "uniswap".to_string(),
+        instrument: "ethusdc".to_string(),
+        event_time: None,
+        receive_time: 0,
+        price,
+        size: None,
+        detail: EventDetail::Swap {
+            amount0: 0,
+            amount1: 0,
+            liquidity: 0,
+            tick: 0,
+            block_number: Some(block),
+            log_index: Some(log_index),
+            removed: false,
+        },
+    }
+}
+
+#[test]
+fn test_reorg_buffer_retracts_and_holds_back_swaps() {
+    let (hash_a, hash_b) = (H256::repeat_byte(0xa), H256::repeat_byte(0xb));
+
+    // Swaps go out at once, and come back as retractions when their block is orphaned
+    let mut buffer = ReorgBuffer::new(0);
+    assert_eq!(buffer.push(hash_a, 100, 0, swap_at(100, 0, 1.0)).len(), 1);
+    assert_eq!(buffer.push(hash_b, 101, 3, swap_at(101, 3, 2.0)).len(), 1);
+    let retraction = buffer.remove(hash_b, 101, 3).unwrap();
+    assert!(matches!(retraction.detail, EventDetail::Swap { removed: true, log_index: Some(3), .. }));
+    assert_eq!(buffer.remove(hash_b, 101, 3), None);
+    assert_eq!(buffer.end_reorg(), Some(1));
+    assert_eq!(buffer.end_reorg(), None);
+
+    // Finality mode emits a swap once its block has three confirmations
+    let mut buffer = ReorgBuffer::new(3);
+    assert!(buffer.push(hash_a, 200, 0, swap_at(200, 0, 1.0)).is_empty());
+    assert!(buffer.push(H256::repeat_byte(1), 201, 0, swap_at(201, 0, 2.0)).is_empty());
+    let ready = buffer.push(H256::repeat_byte(2), 202, 0, swap_at(202, 0, 3.0));
+    assert_eq!(ready.iter().map(|swap| swap.price).collect::<Vec<_>>(), vec![1.0]);
+    // A swap orphaned before it was final is dropped without a retraction
+    assert_eq!(buffer.remove(H256::repeat_byte(1), 201, 0), None);
+    assert_eq!(buffer.pending(), 1);
+    assert_eq!(buffer.end_reorg(), Some(2));
+    // Blocks without swaps count as confirmations too
+    assert!(buffer.advance_head(203).is_empty());
+    let ready = buffer.advance_head(204);
+    assert_eq!(ready.iter().map(|swap| swap.price).collect::<Vec<_>>(), vec![3.0]);
+    assert_eq!(buffer.pending(), 0);
+
+    assert_eq!(LogCursor { block: 5, log_index: 0 }.previous(), Some(LogCursor { block: 4, log_index: u64::MAX }));
+    assert_eq!(LogCursor { block: 0, log_index: 0 }.previous(), None);
+
+    // Calculators drop retracted samples by key
+    let mut calculator = VolatilityCalculator::new(60_000);
+    let mut tick_calculator = TickVolatilityCalculator::new(60_000);
+    let start = SystemTime::now();
+    for i in 0..5u64 {
+        calculator.add_keyed_value((100, i), start, 2000.0 + i as f64);
+        tick_calculator.add_keyed_tick((100, i), start, 200_000 + i as i32);
+    }
+    assert!(calculator.calculate_volatility().is_some());
+    assert!(calculator.remove_value((100, 4)));
+    assert!(!calculator.remove_value((100, 4)));
+    assert!(tick_calculator.remove_tick((100, 2)));
+    assert_eq!((calculator.calculate_volatility(), tick_calculator.calculate_volatility()), (None, None));
+
+    let mut activity = SubscriptionActivity::default();
+    activity.record_reorg(2);
+    activity.record_reorg(2);
+    assert_eq!((activity.reorgs, activity.reorg_depths.get(&2)), (2, Some(&2)));
+}
+
+#[test]
+fn test_header_cache_and_chain_time_window() {
+    let mut cache = HeaderCache::new(2);
+    let time = |number: u64| BlockTime { number, timestamp: 1_700_000_000 + number * 12 };
+    cache.insert(H256::repeat_byte(1), time(1));
+    cache.insert(H256::repeat_byte(2), time(2));
+    cache.insert(H256::repeat_byte(2), time(2));
+    assert_eq!(cache.len(), 2);
+    cache.insert(H256::repeat_byte(3), time(3));
+    assert_eq!(cache.get(&H256::repeat_byte(1)), None);
+    assert_eq!(cache.get(&H256::repeat_byte(3)).map(|t| t.timestamp_millis()), Some(1_700_000_036_000));
+    // A block whose header is missing borrows the newest earlier block's time
+    assert_eq!(cache.latest_at_or_before(7), Some(time(3)));
+    assert_eq!(cache.latest_at_or_before(2), Some(time(2)));
+    assert_eq!(cache.latest_at_or_before(1), None);
+
+    // Swaps replayed in a burst keep their block times, so the oldest expire
+    let mut calculator = VolatilityCalculator::new(3_600_000);
+    let genesis = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+    for block in 0..5u64 {
+        calculator.add_keyed_value((block, 0), genesis + Duration::from_secs(block * 600), 2000.0 + block as f64);
+    }
+    assert!(calculator.calculate_volatility().is_some());
+    calculator.add_keyed_value((5, 0), genesis + Duration::from_secs(4_500), 2005.0);
+    // Blocks 0 and 1 fell out of the hour behind block 5
+    assert!(!calculator.remove_value((1, 0)));
+    assert!(calculator.remove_value((2, 0)));
+
+    // Unkeyed prices run on the same clock: wall time would expire them all
+    let mut unkeyed = VolatilityCalculator::new(3_600_000);
+    for block in 0..5u64 {
+        unkeyed.add_value_at(genesis + Duration::from_secs(block * 600), 2000.0 + block as f64);
+    }
+    assert!(unkeyed.calculate_volatility().is_some());
+    unkeyed.add_value_at(genesis + Duration::from_secs(4_500), 2005.0);
+    assert_eq!(unkeyed.calculate_volatility(), None);
+
+    // A late sample is slotted in by time instead of wiping newer ones, and
+    // one already outside the window is rejected
+    calculator.add_keyed_value((2, 0), genesis + Duration::from_secs(1_200), 2002.0);
+    calculator.add_keyed_value((0, 0), genesis, 2000.0);
+    assert!(!calculator.remove_value((0, 0)));
+    for block in [2, 3, 4, 5] {
+        assert!(calculator.remove_value((block, 0)), "block {} was evicted", block);
+    }
+
+    let mut tick_calculator = TickVolatilityCalculator::new(60_000);
+    for (seconds, tick) in [(0, 200_000), (1, 200_010), (3, 200_000), (4, 200_010)] {
+        tick_calculator.add_tick_at(genesis + Duration::from_secs(seconds), tick);
+    }
+    // Arrives last but sits between seconds 1 and 3: moves of +10, -5, -5, +10
+    tick_calculator.add_tick_at(genesis + Duration::from_secs(2), 200_005);
+    tick_calculator.add_tick_at(genesis - Duration::from_secs(120), 0);
+    assert_close(tick_calculator.calculate_volatility(), 7.5 * 1.0001f64.ln());
+}
+
+#[test]
+fn test_backfill_chunks_shrink_on_provider_limits() {
+    // Ten minutes of 12 second blocks
+    assert_eq!(backfill_start(19_000_000, 600), 18_999_950);
+    assert_eq!(backfill_start(10, 600), 0);
+
+    let mut chunker = AdaptiveChunker::new(2_000);
+    assert_eq!(chunker.end_for(100, 10_000), 2_099);
+    assert_eq!(chunker.end_for(9_500, 10_000), 10_000);
+    assert!(chunker.shrink());
+    assert!(chunker.shrink());
+    assert_eq!(chunker.end_for(100, 10_000), 599);
+    chunker.grow();
+    assert_eq!(chunker.size(), 1_000);
+    for _ in 0..4 {
+        chunker.grow();
+    }
+    assert_eq!(chunker.size(), 2_000);
+
+    let mut single = AdaptiveChunker::new(1);
+    assert!(!single.shrink());
+
+    assert!(is_too_many_results("RPC error: Error { code: ServerError(-32005), message: \"query returned more than 10000 results\" }"));
+    assert!(is_too_many_results("Log response size exceeded"));
+    assert!(is_too_many_results("eth_getLogs is limited to a 10,000 range"));
+    assert!(!is_too_many_results("connection reset by peer"));
+}
+
+#[test]
+fn test_pools_are_routed_by_log_address() {
+    let usdc_weth = PoolConfig::default();
+    let wbtc_weth = PoolConfig::new(
+        "0xCBCdF9626bC03E24f779434178A73a0B4bad62eD".parse().unwrap(),
+        3_000,
+        TokenInfo::new("WBTC", 8),
+        TokenInfo::new("WETH", 18),
+        Orientation::Token0Base,
+    );
+    assert_eq!(usdc_weth.instrument(), "0x88e6a0c2ddd26feeb64f039a2c41296fcb3f5640");
+    assert_eq!((usdc_weth.fee, wbtc_weth.fee), (500, 3_000));
+
+    // The same sqrtPriceX96 is priced with each pool's own decimals
+    let swap = Swap {
+        sender: Address::zero(),
+        recipient: Address::zero(),
+        amount0: 1,
+        amount1: -1,
+        sqrt_price_x96: U256::from_dec_str("31192158774040239845801218050570798").unwrap(),
+        liquidity: 1_000,
+        tick: 257_000,
+    };
+    let wbtc = swap_event(&swap, &wbtc_weth.instrument(), &wbtc_weth, None).unwrap();
+    assert_eq!(wbtc.instrument, "0xcbcdf9626bc03e24f779434178a73a0b4bad62ed");
+    assert_close(Some(wbtc.price), 15.5);
+    let usdc = swap_event(&swap, &usdc_weth.instrument(), &usdc_weth, None).unwrap();
+    assert_ne!(usdc.instrument, wbtc.instrument);
+
+    // Activity is kept apart per pool; gaps belong to the shared subscription
+    let tracker = ActivityTracker::new();
+    tracker.record(usdc_weth.address, &PoolEvent::Swap(swap.clone()));
+    tracker.record(usdc_weth.address, &PoolEvent::Swap(swap.clone()));
+    tracker.record(wbtc_weth.address, &PoolEvent::Swap(swap));
+    tracker.record_retraction(wbtc_weth.address);
+    tracker.record_gap(4);
+    let snapshot = tracker.snapshot();
+    let swaps = |pool: &PoolConfig| snapshot.pools.get(&pool.address).and_then(|activity| activity.events.get(&PoolEventKind::Swap).copied());
+    assert_eq!((swaps(&usdc_weth), swaps(&wbtc_weth)), (Some(2), Some(1)));
+    assert_eq!(snapshot.pools.get(&wbtc_weth.address).map(|activity| activity.retracted_swaps), Some(1));
+    assert_eq!((snapshot.pools.len(), snapshot.backfilled_logs), (2, 4));
+}
+
+#[test]
+fn test_pool_metadata_from_eth_call_outputs() {
+    let selectors: Vec<[u8; 4]> = [&MetadataCall::POOL[..], &MetadataCall::TOKEN[..]]
+        .concat()
+        .iter()
+        .map(MetadataCall::selector)
+        .collect();
+    assert_eq!(
+        selectors,
+        [
+            [0x0d, 0xfe, 0x16, 0x81],
+            [0xd2, 0x12, 0x20, 0xa7],
+            [0xdd, 0xca, 0x3f, 0x43],
+            [0xd0, 0xc9, 0x3a, 0x7c],
+            [0x95, 0xd8, 0x9b, 0x41],
+            [0x31, 0x3c, 0xe5, 0x67],
+        ]
+    );
+
+    // aggregate3 calldata starts with its selector; results keep call order
+    let usdc: Address = "0xA0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48".parse().unwrap();
+    let weth: Address = "0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2".parse().unwrap();
+    let calldata = encode_aggregate3(&[(usdc, MetadataCall::Symbol.selector().to_vec())]);
+    assert_eq!(calldata[..4], [0x82, 0xad, 0x56, 0xcb]);
+    let word = |value: U256| encode(&[Token::Uint(value)]);
+    let output = encode(&[Token::Array(vec![
+        Token::Tuple(vec![Token::Bool(true), Token::Bytes(encode(&[Token::Address(usdc)]))]),
+        Token::Tuple(vec![Token::Bool(true), Token::Bytes(encode(&[Token::Address(weth)]))]),
+        Token::Tuple(vec![Token::Bool(true), Token::Bytes(word(U256::from(500)))]),
+        Token::Tuple(vec![Token::Bool(true), Token::Bytes(word(U256::from(10)))]),
+        Token::Tuple(vec![Token::Bool(false), Token::Bytes(vec![])]),
+    ])]);
+    let results = decode_aggregate3(&output).unwrap();
+    assert_eq!(results[4], None);
+    let metadata = PoolMetadata::decode(&results[..4]).unwrap();
+    assert_eq!(metadata, PoolMetadata { token0: usdc, token1: weth, fee: 500, tick_spacing: 10 });
+    assert!(decode_aggregate3(&[0; 7]).is_none());
+    // A reverted call, or a contract that is not a pool, resolves nothing
+    assert!(PoolMetadata::decode(&results[1..]).is_none());
+
+    // Symbols are ABI strings or, for some older tokens, NUL-padded bytes32
+    assert_eq!(decode_symbol(&encode(&[Token::String("WETH".to_string())])).as_deref(), Some("WETH"));
+    let mut mkr = [0u8; 32];
+    mkr[..3].copy_from_slice(b"MKR");
+    assert_eq!(decode_symbol(&mkr).as_deref(), Some("MKR"));
+    let usdc_token = decode_token(&[Some(encode(&[Token::String("USDC".to_string())])), Some(word(U256::from(6)))]);
+    assert_eq!(usdc_token, Some(TokenInfo::new("USDC", 6)));
+    assert!(decode_token(&[Some(mkr.to_vec()), Some(word(U256::from(256)))]).is_none());
+
+    // Cached pool and tokens give a priced config, quote token inferred
+    let mut cache = MetadataCache::default();
+    let pool_address = PoolConfig::default().address;
+    cache.insert_pool(pool_address, metadata);
+    cache.insert_token(usdc, TokenInfo::new("USDC", 6));
+    assert!(cache.config(pool_address).is_none());
+    cache.insert_token(weth, TokenInfo::new("WETH", 18));
+    assert_eq!(cache.config(pool_address), Some(PoolConfig::default()));
+
+    let infer = |token0: &str, token1: &str| Orientation::infer(&TokenInfo::new(token0, 18), &TokenInfo::new(token1, 18));
+    assert_eq!(infer("WBTC", "WETH"), Orientation::Token0Base);
+    assert_eq!(infer("LINK", "UNI"), Orientation::Token0Base);
+    assert_eq!(infer("DAI", "USDC"), Orientation::Token0Base);
+    assert_eq!(infer("USDC", "USDT"), Orientation::Token1Base);
+}
+
+#[test]
+fn test_factory_discovery_filters_and_registers_pools() {
+    let usdc: Address = "0xA0b86991c6218b36c1d19D4a2e9Eb0cE3606eB48".parse().unwrap();
+    let weth: Address = "0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2".parse().unwrap();
+    let pool = PoolConfig::default();
+    let topics = vec![PoolCreated::topic0(), H256::from(usdc), H256::from(weth), H256::from_low_u64_be(500)];
+    let data = encode(&[Token::Int(U256::from(10)), Token::Address(pool.address)]);
+    let created = PoolCreated::decode(&log(topics.clone(), data.clone())).unwrap();
+    assert_eq!(
+        created,
+        PoolCreated { token0: usdc, token1: weth, fee: 500, tick_spacing: 10, pool: pool.address }
+    );
+    // Swap logs and truncated events are not pool creations
+    assert!(PoolCreated::decode(&log(swap_topics(), data)).is_none());
+    assert!(PoolCreated::decode(&log(topics, vec![0; 32])).is_none());
+
+    // Both tokens must be allowlisted and the fee tier listed; empty sets allow any
+    assert!(DiscoveryFilter::new().accepts(&created));
+    let filter = DiscoveryFilter::new().with_tokens(vec![usdc, weth]).with_fee_tiers(vec![500, 3_000]).with_min_liquidity(10_000.0);
+    assert!(filter.accepts(&created));
+    assert!(!filter.accepts(&PoolCreated { fee: 10_000, ..created }));
+    assert!(!filter.accepts(&PoolCreated { token1: Address::repeat_byte(7), ..created }));
+    // The floor is in whole tokens: the same depth passes whatever the decimals
+    let dai_weth = PoolConfig::new(Address::repeat_byte(8), 500, TokenInfo::new("DAI", 18), TokenInfo::new("WETH", 18), Orientation::Token1Base);
+    assert!((pool.whole_token_liquidity(15_000_000_000_000_000) - 15_000.0).abs() < 1e-6);
+    assert!(filter.has_liquidity(&pool, 15_000_000_000_000_000));
+    assert!(!filter.has_liquidity(&pool, 9_000_000_000_000_000));
+    assert!(filter.has_liquidity(&dai_weth, 15_000_000_000_000_000_000_000));
+    assert!(!filter.has_liquidity(&dai_weth, 15_000_000_000_000_000));
+
+    // Adding pools signals the running subscription; re-adding does not
+    let registry = PoolRegistry::new();
+    let mut changes = registry.watch();
+    assert_eq!(registry.extend(vec![pool.clone()]), 1);
+    assert!(changes.has_changed().unwrap());
+    changes.borrow_and_update();
+    assert_eq!(registry.extend(vec![pool.clone()]), 0);
+    assert!(!changes.has_changed().unwrap());
+    // Clones share the pools
+    assert_eq!(registry.clone().list(), vec![pool.clone()]);
+    assert_eq!(registry.get(&pool.address).map(|pool| pool.fee), Some(500));
+}
+
+// A swap in the default pool at `block`
+fn pool_swap(block: u64) -> Log {
+    let data = encode(&[
+        Token::Int(U256::from(2_000_000_000u64)),
+        Token::Int(negative(1_000_000_000_000_000_000)),
+        Token::Uint(U256::from_dec_str("1771595571142957166518320255467520").unwrap()),
+        Token::Uint(U256::from(5_000_000_000_000_000_000u128)),
+        Token::Int(negative(200_311)),
+    ]);
+    Log {
+        address: PoolConfig::default().address,
+        block_hash: Some(H256::from_low_u64_be(block)),
+        block_number: Some(block.into()),
+        log_index: Some(U256::zero()),
+        ..log(swap_topics(), data)
+    }
+}
+
+// One `eth_subscribe`: the notifications pushed once it is answered, and
+// whether the node then closes the socket, shortly after
+struct ScriptedSubscription {
+    push: Vec<Value>,
+    then_close: bool,
+}
+
+impl ScriptedSubscription {
+    fn logs(logs: Vec<Log>, then_close: bool) -> Self {
+        Self {
+            push: logs.iter().map(|log| json!(log)).collect(),
+            then_close,
+        }
+    }
+
+    // newHeads headers for blocks `numbers`
+    fn heads(numbers: impl IntoIterator<Item = u64>) -> Self {
+        Self {
+            push: numbers.into_iter().map(|number| json!(chain_block(number))).collect(),
+            then_close: false,
+        }
+    }
+}
+
+// Block `number` of the mock chain: hashed as its number, 12 seconds apart
+fn chain_block(number: u64) -> Block<H256> {
+    Block {
+        hash: Some(H256::from_low_u64_be(number)),
+        parent_hash: H256::from_low_u64_be(number.saturating_sub(1)),
+        number: Some(number.into()),
+        timestamp: U256::from(1_700_000_000 + number * 12),
+        logs_bloom: Some(H2048::zero()),
+        ..Default::default()
+    }
+}
+
+// JSON-RPC node over WebSocket: its head is `head`, `eth_getLogs` serves
+// `chain`, and subscriptions follow `script` in order across connections
+async fn serve_node(head: u64, chain: Vec<Log>, script: Vec<ScriptedSubscription>) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let script = Arc::new(Mutex::new(VecDeque::from(script)));
+
+    tokio::spawn(async move {
+        let mut subscriptions = 0;
+        while let Ok((tcp, _)) = listener.accept().await {
+            let mut ws = tokio_tungstenite::accept_async(tcp).await.unwrap();
+            let mut close_at = None;
+            loop {
+                // Requests made before the close, such as for headers, are still answered
+                let message = match close_at {
+                    Some(deadline) => match tokio::time::timeout_at(deadline, ws.next()).await {
+                        Ok(message) => message,
+                        Err(_) => {
+                            let _ = ws.close(None).await;
+                            break;
+                        }
+                    },
+                    None => ws.next().await,
+                };
+                let Some(Ok(Message::Text(text))) = message else { break };
+                let request: Value = serde_json::from_str(&text).unwrap();
+                let block = |key: &str| u64::from_str_radix(request["params"][0][key].as_str().unwrap().trim_start_matches("0x"), 16).unwrap();
+                let (result, scripted) = match request["method"].as_str().unwrap() {
+                    "eth_blockNumber" => (json!(format!("0x{:x}", head)), None),
+                    "eth_getBlockByHash" => {
+                        let hash: H256 = serde_json::from_value(request["params"][0].clone()).unwrap();
+                        (json!(chain_block(hash.to_low_u64_be())), None)
+                    }
+                    "eth_getLogs" => {
+                        let (from, to) = (block("fromBlock"), block("toBlock"));
+                        let logs: Vec<&Log> = chain
+                            .iter()
+                            .filter(|log| (from..=to).contains(&log.block_number.unwrap().as_u64()))
+                            .collect();
+                        (json!(logs), None)
+                    }
+                    "eth_subscribe" => {
+                        subscriptions += 1;
+                        (json!(format!("0x{:x}", subscriptions)), script.lock().unwrap().pop_front())
+                    }
+                    "eth_unsubscribe" => (json!(true), None),
+                    _ => (Value::Null, None),
+                };
+                let response = json!({"jsonrpc": "2.0", "id": request["id"], "result": result});
+                ws.send(Message::Text(response.to_string())).await.unwrap();
+
+                let Some(scripted) = scripted else { continue };
+                // The client registers the subscription id after the answer
+                tokio::time::sleep(Duration::from_millis(50)).await;
+                for result in scripted.push {
+                    let notification = json!({
+                        "jsonrpc": "2.0",
+                        "method": "eth_subscription",
+                        "params": {"subscription": format!("0x{:x}", subscriptions), "result": result},
+                    });
+                    ws.send(Message::Text(notification.to_string())).await.unwrap();
+                }
+                if scripted.then_close {
+                    close_at = Some(tokio::time::Instant::now() + Duration::from_millis(200));
+                }
+            }
+        }
+    });
+
+    format!("ws://{}", addr)
+}
+
+// Blocks of the swaps received until the channel stays empty
+async fn swap_blocks(receiver: &mut EventReceiver<MarketEvent>) -> Vec<u64> {
+    let mut blocks = Vec::new();
+    while let Ok(Some(swap)) = tokio::time::timeout(Duration::from_millis(200), receiver.recv()).await {
+        if let EventDetail::Swap { block_number: Some(block), .. } = swap.detail {
+            blocks.push(block);
+        }
+    }
+    blocks
+}
+
+#[tokio::test]
+async fn test_dropped_subscription_gap_is_filled_exactly_once() {
+    let url = serve_node(
+        12,
+        vec![pool_swap(10), pool_swap(11), pool_swap(12)],
+        vec![
+            ScriptedSubscription::logs(vec![pool_swap(10)], true),
+            // Live again at block 12, which the gap fill also returns
+            ScriptedSubscription::logs(vec![pool_swap(12), pool_swap(13)], false),
+        ],
+    )
+    .await;
+    let client = InfuraClient::new(&url).await.unwrap();
+    let (sender, mut receiver) = bounded(16, BackpressurePolicy::Block);
+
+    // The subscription ends with the socket; blocks 11 and 12 are missed
+    assert_eq!(client.fetch_prices(&sender, 10).await.unwrap(), 1);
+    client.reconnect().await.unwrap();
+    assert_eq!(client.fetch_prices(&sender, 3).await.unwrap(), 3);
+
+    assert_eq!(swap_blocks(&mut receiver).await, vec![10, 11, 12, 13]);
+    let activity = client.activity().snapshot();
+    assert_eq!((activity.log_gaps, activity.backfilled_logs), (1, 2));
+}
+
+#[tokio::test]
+async fn test_resubscribe_for_new_pools_is_not_a_gap() {
+    let url = serve_node(
+        11,
+        vec![pool_swap(10), pool_swap(11)],
+        vec![
+            ScriptedSubscription::logs(vec![pool_swap(10)], false),
+            ScriptedSubscription::logs(vec![pool_swap(12)], false),
+        ],
+    )
+    .await;
+    let client = InfuraClient::new(&url).await.unwrap();
+    let (registry, activity) = (client.registry(), client.activity());
+    let (sender, mut receiver) = bounded(16, BackpressurePolicy::Block);
+    let fetch = tokio::spawn(async move { client.fetch_prices(&sender, 3).await });
+
+    assert!(receiver.recv().await.is_some());
+    let wbtc_weth = PoolConfig::new(
+        "0xCBCdF9626bC03E24f779434178A73a0B4bad62eD".parse().unwrap(),
+        3_000,
+        TokenInfo::new("WBTC", 8),
+        TokenInfo::new("WETH", 18),
+        Orientation::Token0Base,
+    );
+    registry.extend(vec![wbtc_weth]);
+
+    // The swap emitted while resubscribing is still fetched
+    assert_eq!(fetch.await.unwrap().unwrap(), 3);
+    assert_eq!(swap_blocks(&mut receiver).await, vec![11, 12]);
+    let activity = activity.snapshot();
+    assert_eq!((activity.log_gaps, activity.backfilled_logs), (0, 0));
+}
+
+#[tokio::test]
+async fn test_new_heads_confirm_swaps_of_a_quiet_pool() {
+    let url = serve_node(
+        10,
+        Vec::new(),
+        vec![
+            ScriptedSubscription::logs(vec![pool_swap(10)], false),
+            ScriptedSubscription::heads(11..=12),
+        ],
+    )
+    .await;
+    let client = InfuraClient::new(&url).await.unwrap().with_confirmations(3);
+    let (sender, mut receiver) = bounded(16, BackpressurePolicy::Block);
+    let (prices, heads, price_sender) = (client.clone(), client.clone(), sender.clone());
+    tokio::spawn(async move { prices.fetch_prices(&price_sender, usize::MAX).await });
+
+    // No later block has a swap, so block 10 is held
+    assert!(swap_blocks(&mut receiver).await.is_empty());
+    tokio::spawn(async move { heads.watch_heads(&sender).await });
+    assert_eq!(swap_blocks(&mut receiver).await, vec![10]);
+}
+
+#[tokio::test]
+async fn test_subscribe_keeps_pools_discovered_while_connecting() {
+    let url = serve_node(10, Vec::new(), Vec::new()).await;
+    let mut client = InfuraClient::new(&url).await.unwrap();
+    let registry = client.registry();
+    client.connect().await.unwrap();
+
+    // Discovery admits a pool before the price task subscribes
+    let wbtc_weth = PoolConfig::new(
+        "0xCBCdF9626bC03E24f779434178A73a0B4bad62eD".parse().unwrap(),
+        3_000,
+        TokenInfo::new("WBTC", 8),
+        TokenInfo::new("WETH", 18),
+        Orientation::Token0Base,
+    );
+    registry.extend(vec![wbtc_weth]);
+
+    let unknown = format!("{:?}", Address::repeat_byte(9));
+    assert!(client.subscribe(&[unknown]).await.is_err());
+    let configured = format!("{:?}", PoolConfig::default().address);
+    client.subscribe(&[configured]).await.unwrap();
+    assert_eq!(registry.len(), 2);
+}
@@ -0,0 +1,379 @@
+use std::collections::{HashMap, VecDeque};
+use std::pin::pin;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use prometheus::{CounterVec, GaugeVec, Opts, Registry};
+use tokio::sync::mpsc::error::SendError;
+use tokio::sync::mpsc::UnboundedSender;
+use tokio::sync::Notify;
+
+use crate::event::{EventDetail, MarketEvent};
+
+/// What a bounded channel does with a new event when it is full.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BackpressurePolicy {
+    /// Wait for space, slowing the producer down.
+    Block,
+    /// Discard the oldest queued event to make room.
+    DropOldest,
+    /// Discard the new event.
+    DropNewest,
+    /// Replace a queued event with the same conflation key, so only the
+    /// latest per key is kept. Events without a key, or arriving when the
+    /// queue is full with no match, fall back to dropping the oldest.
+    ConflateLatest,
+}
+
+/// Events that can be conflated under [`BackpressurePolicy::ConflateLatest`].
+pub trait Conflate {
+    /// Events sharing a key supersede each other; `None` is never replaced.
+    fn conflation_key(&self) -> Option<String>;
+}
+
+impl Conflate for MarketEvent {
+    fn conflation_key(&self) -> Option<String> {
+        // A retraction must reach the consumer even when newer events follow
+        if let EventDetail::Swap { removed: true, .. } = self.detail {
+            return None;
+        }
+        Some(format!("{}:{}:{}", self.source, self.instrument, self.kind().as_str()))
+    }
+}
+
+/// Point-in-time counters for a channel.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ChannelStats {
+    pub depth: usize,
+    pub high_water: usize, // Deepest the queue has been
+    pub dropped: u64,      // Events discarded because the queue was full
+    pub conflated: u64,    // Events replaced by a newer one with the same key
+}
+
+// Queue plus counters, guarded by one lock
+struct State<T> {
+    queue: VecDeque<(u64, Option<String>, T)>, // (sequence, conflation key, event)
+    next_sequence: u64,
+    latest: HashMap<String, u64>, // Conflation key -> sequence of its queued event
+    stats: ChannelStats,
+}
+
+impl<T> State<T> {
+    fn push(&mut self, key: Option<String>, item: T) {
+        let sequence = self.next_sequence;
+        self.next_sequence += 1;
+        if let Some(key) = &key {
+            self.latest.insert(key.clone(), sequence);
+        }
+        self.queue.push_back((sequence, key, item));
+        self.stats.high_water = self.stats.high_water.max(self.queue.len());
+    }
+
+    fn pop(&mut self) -> Option<T> {
+        let (sequence, key, item) = self.queue.pop_front()?;
+        if let Some(key) = key {
+            if self.latest.get(&key) == Some(&sequence) {
+                self.latest.remove(&key);
+            }
+        }
+        Some(item)
+    }
+
+    // Replaces the queued event with the same key, handing the item back if none
+    fn replace(&mut self, key: &str, item: T) -> Result<(), T> {
+        let Some(sequence) = self.latest.get(key) else { return Err(item) };
+        let front = self.queue.front().map(|(sequence, _, _)| *sequence).unwrap_or_default();
+        match self.queue.get_mut((sequence - front) as usize) {
+            Some(entry) => {
+                entry.2 = item;
+                self.stats.conflated += 1;
+                Ok(())
+            }
+            None => Err(item),
+        }
+    }
+}
+
+struct Shared<T> {
+    state: Mutex<State<T>>,
+    capacity: usize,
+    policy: BackpressurePolicy,
+    senders: AtomicUsize,
+    receiver_closed: AtomicBool,
+    item_ready: Notify,
+    space_ready: Notify,
+}
+
+impl<T: Conflate> Shared<T> {
+    async fn send(&self, item: T) -> Result<(), SendError<T>> {
+        let key = match self.policy {
+            BackpressurePolicy::ConflateLatest => item.conflation_key(),
+            _ => None,
+        };
+        let mut item = Some(item);
+        loop {
+            // Register for wakeups before checking, so a pop in between is not missed
+            let mut space = pin!(self.space_ready.notified());
+            space.as_mut().enable();
+            {
+                let Ok(mut state) = self.state.lock() else {
+                    return Err(SendError(item.take().expect("item is sent once")));
+                };
+                let value = item.take().expect("item is sent once");
+                if self.receiver_closed.load(Ordering::SeqCst) {
+                    return Err(SendError(value));
+                }
+                let value = match &key {
+                    Some(key) => match state.replace(key, value) {
+                        Ok(()) => return Ok(()),
+                        Err(value) => value,
+                    },
+                    None => value,
+                };
+                if state.queue.len() < self.capacity {
+                    state.push(key, value);
+                    drop(state);
+                    self.item_ready.notify_one();
+                    return Ok(());
+                }
+                match self.policy {
+                    BackpressurePolicy::Block => item = Some(value),
+                    BackpressurePolicy::DropNewest => {
+                        state.stats.dropped += 1;
+                        return Ok(());
+                    }
+                    BackpressurePolicy::DropOldest | BackpressurePolicy::ConflateLatest => {
+                        state.pop();
+                        state.stats.dropped += 1;
+                        state.push(key, value);
+                        drop(state);
+                        self.item_ready.notify_one();
+                        return Ok(());
+                    }
+                }
+            }
+            space.await;
+        }
+    }
+}
+
+// Lets a monitor read counters without knowing the event type
+trait StatsSource: Send + Sync {
+    fn stats(&self) -> ChannelStats;
+}
+
+impl<T: Send> StatsSource for Shared<T> {
+    fn stats(&self) -> ChannelStats {
+        self.state
+            .lock()
+            .map(|state| ChannelStats {
+                depth: state.queue.len(),
+                ..state.stats
+            })
+            .unwrap_or_default()
+    }
+}
+
+/// Reads a channel's counters, e.g. for metrics.
+#[derive(Clone)]
+pub struct ChannelMonitor {
+    shared: Arc<dyn StatsSource>,
+}
+
+impl ChannelMonitor {
+    pub fn stats(&self) -> ChannelStats {
+        self.shared.stats()
+    }
+}
+
+enum Inner<T> {
+    Bounded(Arc<Shared<T>>),
+    Unbounded(UnboundedSender<T>),
+}
+
+/// Sending half of an event channel. Wraps either a bounded channel with a
+/// [`BackpressurePolicy`] or a plain unbounded tokio sender.
+pub struct EventSender<T> {
+    inner: Inner<T>,
+}
+
+impl<T: Conflate + Send + 'static> EventSender<T> {
+    /// Queues an event. Only waits under [`BackpressurePolicy::Block`]; fails
+    /// once the receiver is gone.
+    pub async fn send(&self, item: T) -> Result<(), SendError<T>> {
+        match &self.inner {
+            Inner::Bounded(shared) => shared.send(item).await,
+            Inner::Unbounded(sender) => sender.send(item),
+        }
+    }
+
+    /// Whether the receiver is gone, so further sends fail.
+    pub fn is_closed(&self) -> bool {
+        match &self.inner {
+            Inner::Bounded(shared) => shared.receiver_closed.load(Ordering::SeqCst),
+            Inner::Unbounded(sender) => sender.is_closed(),
+        }
+    }
+
+    /// Counters for a bounded channel; `None` for an unbounded one.
+    pub fn monitor(&self) -> Option<ChannelMonitor> {
+        match &self.inner {
+            Inner::Bounded(shared) => Some(ChannelMonitor { shared: shared.clone() }),
+            Inner::Unbounded(_) => None,
+        }
+    }
+}
+
+impl<T> Clone for EventSender<T> {
+    fn clone(&self) -> Self {
+        let inner = match &self.inner {
+            Inner::Bounded(shared) => {
+                shared.senders.fetch_add(1, Ordering::SeqCst);
+                Inner::Bounded(shared.clone())
+            }
+            Inner::Unbounded(sender) => Inner::Unbounded(sender.clone()),
+        };
+        Self { inner }
+    }
+}
+
+impl<T> Drop for EventSender<T> {
+    fn drop(&mut self) {
+        if let Inner::Bounded(shared) = &self.inner {
+            if shared.senders.fetch_sub(1, Ordering::SeqCst) == 1 {
+                // Wake the receiver so it sees the channel closed
+                shared.item_ready.notify_one();
+            }
+        }
+    }
+}
+
+impl<T> From<UnboundedSender<T>> for EventSender<T> {
+    fn from(sender: UnboundedSender<T>) -> Self {
+        Self {
+            inner: Inner::Unbounded(sender),
+        }
+    }
+}
+
+/// Receiving half of a bounded event channel.
+pub struct EventReceiver<T> {
+    shared: Arc<Shared<T>>,
+}
+
+impl<T: Send + 'static> EventReceiver<T> {
+    /// Next event, or `None` once every sender is dropped and the queue is drained.
+    pub async fn recv(&mut self) -> Option<T> {
+        loop {
+            let mut ready = pin!(self.shared.item_ready.notified());
+            ready.as_mut().enable();
+            {
+                let mut state = self.shared.state.lock().ok()?;
+                if let Some(item) = state.pop() {
+                    drop(state);
+                    self.shared.space_ready.notify_one();
+                    return Some(item);
+                }
+                if self.shared.senders.load(Ordering::SeqCst) == 0 {
+                    return None;
+                }
+            }
+            ready.await;
+        }
+    }
+
+    pub fn monitor(&self) -> ChannelMonitor {
+        ChannelMonitor {
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+impl<T> Drop for EventReceiver<T> {
+    fn drop(&mut self) {
+        self.shared.receiver_closed.store(true, Ordering::SeqCst);
+        // Release producers blocked on a full queue
+        self.shared.space_ready.notify_waiters();
+    }
+}
+
+/// A channel holding at most `capacity` events, applying `policy` when full.
+pub fn bounded<T>(capacity: usize, policy: BackpressurePolicy) -> (EventSender<T>, EventReceiver<T>) {
+    let shared = Arc::new(Shared {
+        state: Mutex::new(State {
+            queue: VecDeque::with_capacity(capacity),
+            next_sequence: 0,
+            latest: HashMap::new(),
+            stats: ChannelStats::default(),
+        }),
+        capacity: capacity.max(1),
+        policy,
+        senders: AtomicUsize::new(1),
+        receiver_closed: AtomicBool::new(false),
+        item_ready: Notify::new(),
+        space_ready: Notify::new(),
+    });
+    (
+        EventSender {
+            inner: Inner::Bounded(shared.clone()),
+        },
+        EventReceiver { shared },
+    )
+}
+
+// How often `ChannelMetrics::run` publishes
+const PUBLISH_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Prometheus series for channel queue depth, high-water mark and drops,
+/// labelled by channel name: `channel_depth`, `channel_high_water` and
+/// `channel_dropped_total{reason}` (`overflow` or `conflated`).
+pub struct ChannelMetrics {
+    depth: GaugeVec,
+    high_water: GaugeVec,
+    dropped: CounterVec,
+    published: HashMap<&'static str, (u64, u64)>, // Dropped and conflated counts already published
+}
+
+impl ChannelMetrics {
+    pub fn register(registry: &Registry) -> prometheus::Result<Self> {
+        let depth = GaugeVec::new(Opts::new("channel_depth", "Events queued in the channel"), &["channel"])?;
+        let high_water = GaugeVec::new(
+            Opts::new("channel_high_water", "Deepest the channel queue has been"),
+            &["channel"],
+        )?;
+        let dropped = CounterVec::new(
+            Opts::new("channel_dropped_total", "Events discarded by a channel's backpressure policy"),
+            &["channel", "reason"],
+        )?;
+        registry.register(Box::new(depth.clone()))?;
+        registry.register(Box::new(high_water.clone()))?;
+        registry.register(Box::new(dropped.clone()))?;
+        Ok(Self { depth, high_water, dropped, published: HashMap::new() })
+    }
+
+    /// Publishes one reading of `channel`'s counters.
+    pub fn record(&mut self, channel: &'static str, stats: &ChannelStats) {
+        self.depth.with_label_values(&[channel]).set(stats.depth as f64);
+        self.high_water.with_label_values(&[channel]).set(stats.high_water as f64);
+
+        // Counters are cumulative; only the increase since the last reading is added
+        let (dropped, conflated) = self.published.entry(channel).or_default();
+        self.dropped
+            .with_label_values(&[channel, "overflow"])
+            .inc_by(stats.dropped.saturating_sub(*dropped) as f64);
+        self.dropped
+            .with_label_values(&[channel, "conflated"])
+            .inc_by(stats.conflated.saturating_sub(*conflated) as f64);
+        (*dropped, *conflated) = (stats.dropped, stats.conflated);
+    }
+
+    /// Publishes every channel's counters each second until `is_running` is cleared.
+    pub async fn run(mut self, channels: Vec<(&'static str, ChannelMonitor)>, is_running: Arc<AtomicBool>) {
+        while is_running.load(Ordering::SeqCst) {
+            for (name, monitor) in &channels {
+                self.record(name, &monitor.stats());
+            }
+            tokio::time::sleep(PUBLISH_INTERVAL).await;
+        }
+    }
+}
@@ -0,0 +1,206 @@
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+
+use crate::event::MarketEvent;
+
+/// How venue prices are combined into the composite.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CompositeMethod {
+    Median,
+    /// Weighted by each venue's traded quantity over the volume window.
+    /// Falls back to equal weights when no venue reports quantities.
+    VolumeWeighted,
+    /// Mean after dropping `trim` (0.0..0.5) of the venues from each end.
+    TrimmedMean { trim: f64 },
+}
+
+/// Why a venue was left out of a composite price.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Exclusion {
+    Stale,
+    Outlier,
+}
+
+impl Exclusion {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Exclusion::Stale => "stale",
+            Exclusion::Outlier => "outlier",
+        }
+    }
+}
+
+impl fmt::Display for Exclusion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A composite price and the venues that did or did not contribute to it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompositePrice {
+    pub price: f64,
+    pub timestamp: u64, // Milliseconds since epoch the price was computed at
+    pub venues: Vec<String>,
+    pub excluded: Vec<(String, Exclusion)>,
+}
+
+// Latest price and recent traded quantity for one venue
+#[derive(Debug)]
+struct VenueQuote {
+    price: f64,
+    timestamp: u64,
+    volume: VecDeque<(u64, f64)>, // (timestamp, quantity)
+}
+
+/// Composite index for one asset built from the latest price on each venue.
+///
+/// A venue is excluded when its last tick is older than the staleness limit,
+/// or when its price is further than the deviation limit from the median of
+/// the fresh venues. No price is produced with fewer than `min_venues`
+/// contributors.
+#[derive(Debug)]
+pub struct CompositeIndex {
+    method: CompositeMethod,
+    max_staleness_ms: u64,
+    max_deviation: f64,
+    min_venues: usize,
+    volume_window_ms: u64,
+    quotes: HashMap<String, VenueQuote>,
+}
+
+impl CompositeIndex {
+    pub fn new(method: CompositeMethod) -> Self {
+        Self {
+            method,
+            max_staleness_ms: 30_000,
+            max_deviation: 0.02,
+            min_venues: 1,
+            volume_window_ms: 60_000,
+            quotes: HashMap::new(),
+        }
+    }
+
+    /// Exclude venues whose last tick is older than this.
+    pub fn with_max_staleness_ms(mut self, max_staleness_ms: u64) -> Self {
+        self.max_staleness_ms = max_staleness_ms;
+        self
+    }
+
+    /// Exclude venues deviating from the median by more than this fraction.
+    pub fn with_max_deviation(mut self, max_deviation: f64) -> Self {
+        self.max_deviation = max_deviation;
+        self
+    }
+
+    pub fn with_min_venues(mut self, min_venues: usize) -> Self {
+        self.min_venues = min_venues.max(1);
+        self
+    }
+
+    /// Window over which traded quantity is summed for volume weighting.
+    pub fn with_volume_window_ms(mut self, volume_window_ms: u64) -> Self {
+        self.volume_window_ms = volume_window_ms;
+        self
+    }
+
+    /// Record a venue's event. Events older than the venue's latest are ignored.
+    pub fn update(&mut self, tick: &MarketEvent) {
+        if !tick.price.is_finite() || tick.price <= 0.0 {
+            return;
+        }
+        let timestamp = tick.timestamp();
+        let quote = self.quotes.entry(tick.source.clone()).or_insert(VenueQuote {
+            price: tick.price,
+            timestamp,
+            volume: VecDeque::new(),
+        });
+        if timestamp < quote.timestamp {
+            return;
+        }
+        quote.price = tick.price;
+        quote.timestamp = timestamp;
+        if let Some(size) = tick.size.filter(|q| *q > 0.0) {
+            quote.volume.push_back((timestamp, size));
+        }
+        let cutoff = timestamp.saturating_sub(self.volume_window_ms);
+        while quote.volume.front().is_some_and(|(ts, _)| *ts < cutoff) {
+            quote.volume.pop_front();
+        }
+    }
+
+    /// The composite price as of `now_ms`, or `None` if too few venues qualify.
+    pub fn price(&self, now_ms: u64) -> Option<CompositePrice> {
+        let mut excluded = Vec::new();
+        let mut fresh: Vec<(&str, f64, f64)> = Vec::new(); // (venue, price, volume)
+        for (venue, quote) in &self.quotes {
+            if now_ms.saturating_sub(quote.timestamp) > self.max_staleness_ms {
+                excluded.push((venue.clone(), Exclusion::Stale));
+                continue;
+            }
+            let cutoff = now_ms.saturating_sub(self.volume_window_ms);
+            let volume = quote.volume.iter().filter(|(ts, _)| *ts >= cutoff).map(|(_, q)| q).sum();
+            fresh.push((venue, quote.price, volume));
+        }
+        if fresh.is_empty() {
+            return None;
+        }
+
+        let mut prices: Vec<f64> = fresh.iter().map(|(_, price, _)| *price).collect();
+        let reference = median(&mut prices);
+        fresh.retain(|(venue, price, _)| {
+            let keep = (price / reference - 1.0).abs() <= self.max_deviation;
+            if !keep {
+                excluded.push((venue.to_string(), Exclusion::Outlier));
+            }
+            keep
+        });
+        if fresh.len() < self.min_venues {
+            return None;
+        }
+
+        let price = match self.method {
+            CompositeMethod::Median => {
+                let mut prices: Vec<f64> = fresh.iter().map(|(_, price, _)| *price).collect();
+                median(&mut prices)
+            }
+            CompositeMethod::VolumeWeighted => {
+                let total: f64 = fresh.iter().map(|(_, _, volume)| volume).sum();
+                if total > 0.0 {
+                    fresh.iter().map(|(_, price, volume)| price * volume).sum::<f64>() / total
+                } else {
+                    fresh.iter().map(|(_, price, _)| price).sum::<f64>() / fresh.len() as f64
+                }
+            }
+            CompositeMethod::TrimmedMean { trim } => {
+                let mut prices: Vec<f64> = fresh.iter().map(|(_, price, _)| *price).collect();
+                prices.sort_by(f64::total_cmp);
+                let cut = ((prices.len() as f64 * trim.clamp(0.0, 0.5)).floor() as usize)
+                    .min((prices.len() - 1) / 2);
+                let kept = &prices[cut..prices.len() - cut];
+                kept.iter().sum::<f64>() / kept.len() as f64
+            }
+        };
+
+        let mut venues: Vec<String> = fresh.iter().map(|(venue, _, _)| venue.to_string()).collect();
+        venues.sort();
+        excluded.sort();
+        Some(CompositePrice {
+            price,
+            timestamp: now_ms,
+            venues,
+            excluded,
+        })
+    }
+}
+
+// Median of a non-empty slice; averages the middle pair for even lengths
+fn median(values: &mut [f64]) -> f64 {
+    values.sort_by(f64::total_cmp);
+    let mid = values.len() / 2;
+    if values.len().is_multiple_of(2) {
+        (values[mid - 1] + values[mid]) / 2.0
+    } else {
+        values[mid]
+    }
+}
@@ -0,0 +1,113 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// What kind of market data an event carries.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EventKind {
+    Trade,
+    Bar,
+    Quote,
+    Swap,
+}
+
+impl EventKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EventKind::Trade => "trade",
+            EventKind::Bar => "bar",
+            EventKind::Quote => "quote",
+            EventKind::Swap => "swap",
+        }
+    }
+}
+
+/// Fields specific to each kind of event.
+#[derive(Debug, Clone, PartialEq)]
+pub enum EventDetail {
+    Trade {
+        trade_id: Option<u64>,
+        is_buyer_maker: Option<bool>, // True when the seller was the aggressor
+    },
+    Bar {
+        open_time: u64,
+        close_time: u64,
+        open: f64,
+        high: f64,
+        low: f64,
+        close: f64,
+        volume: f64,
+        closed: bool, // False for intra-candle updates
+    },
+    Quote {
+        bid: f64,
+        bid_size: f64,
+        ask: f64,
+        ask_size: f64,
+    },
+    /// An AMM swap; amounts are in raw token units, signed from the pool's side.
+    Swap {
+        amount0: i128,
+        amount1: i128,
+        liquidity: u128,
+        tick: i32,
+        block_number: Option<u64>,
+        log_index: Option<u64>,
+        /// The swap's log was dropped by a chain reorganization; retracts the
+        /// swap previously sent with the same block number and log index.
+        removed: bool,
+    },
+}
+
+/// A timestamped market data event, normalized across venues and chains.
+///
+/// `price` is the single price downstream estimators use: the trade price,
+/// bar close, quote mid or post-swap pool price.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MarketEvent {
+    pub source: String,     // Venue or chain, e.g. `binance`, `uniswap`
+    pub instrument: String, // Source-native instrument id, e.g. `ETH-USD`
+    pub event_time: Option<u64>, // Milliseconds since epoch, as reported by the source
+    pub receive_time: u64, // Milliseconds since epoch, when we received it
+    pub price: f64,
+    pub size: Option<f64>,
+    pub detail: EventDetail,
+}
+
+impl MarketEvent {
+    /// A trade received now, without trade id or aggressor side.
+    pub fn trade(source: &str, instrument: &str, price: f64, size: Option<f64>, event_time: Option<u64>) -> Self {
+        Self {
+            source: source.to_string(),
+            instrument: instrument.to_string(),
+            event_time,
+            receive_time: now_millis(),
+            price,
+            size,
+            detail: EventDetail::Trade {
+                trade_id: None,
+                is_buyer_maker: None,
+            },
+        }
+    }
+
+    pub fn kind(&self) -> EventKind {
+        match self.detail {
+            EventDetail::Trade { .. } => EventKind::Trade,
+            EventDetail::Bar { .. } => EventKind::Bar,
+            EventDetail::Quote { .. } => EventKind::Quote,
+            EventDetail::Swap { .. } => EventKind::Swap,
+        }
+    }
+
+    /// Event time when the source reports one, else receive time.
+    pub fn timestamp(&self) -> u64 {
+        self.event_time.unwrap_or(self.receive_time)
+    }
+}
+
+/// Milliseconds since epoch.
+pub fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or_default()
+}
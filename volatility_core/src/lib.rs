@@ -0,0 +1,4 @@
+pub mod channel;
+pub mod composite;
+pub mod event;
+pub mod source;
@@ -0,0 +1,185 @@
+use async_trait::async_trait;
+use futures_util::stream::{self, BoxStream, StreamExt};
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+use crate::channel::{bounded, BackpressurePolicy, ChannelMonitor, EventReceiver, EventSender};
+use crate::event::MarketEvent;
+
+/// Ticks a [`TickChannel`] holds before its source has to wait.
+pub const DEFAULT_TICK_CAPACITY: usize = 10_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectionState {
+    Idle,
+    Connected,
+    Reconnecting,
+    Closed,
+}
+
+/// Connection health reported by a [`PriceSource`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Health {
+    pub state: ConnectionState,
+    pub last_tick: Option<u64>, // Timestamp of the latest event
+    pub reconnects: u64,
+    pub last_error: Option<String>,
+}
+
+impl Health {
+    /// Whether the source is connected and has ticked within `max_age_ms` of `now_ms`.
+    pub fn is_live(&self, now_ms: u64, max_age_ms: u64) -> bool {
+        self.state == ConnectionState::Connected
+            && self.last_tick.is_some_and(|tick| now_ms.saturating_sub(tick) <= max_age_ms)
+    }
+}
+
+impl Default for Health {
+    fn default() -> Self {
+        Self {
+            state: ConnectionState::Idle,
+            last_tick: None,
+            reconnects: 0,
+            last_error: None,
+        }
+    }
+}
+
+/// Shared, cheaply cloned [`Health`] that background tasks update.
+#[derive(Debug, Clone, Default)]
+pub struct HealthTracker {
+    inner: Arc<Mutex<Health>>,
+}
+
+impl HealthTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn snapshot(&self) -> Health {
+        self.inner.lock().map(|health| health.clone()).unwrap_or_default()
+    }
+
+    pub fn set_state(&self, state: ConnectionState) {
+        self.update(|health| health.state = state);
+    }
+
+    pub fn record_tick(&self, timestamp: u64) {
+        self.update(|health| health.last_tick = Some(timestamp));
+    }
+
+    /// Marks the connection as lost and about to be retried.
+    pub fn record_reconnect(&self, error: &str) {
+        self.update(|health| {
+            health.state = ConnectionState::Reconnecting;
+            health.reconnects += 1;
+            health.last_error = Some(error.to_string());
+        });
+    }
+
+    fn update(&self, f: impl FnOnce(&mut Health)) {
+        if let Ok(mut health) = self.inner.lock() {
+            f(&mut health);
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SourceError {
+    Connect(String),
+    Subscribe(String),
+    /// `subscribe` was called before `connect`.
+    NotConnected,
+    /// The tick stream was already taken.
+    StreamTaken,
+}
+
+impl fmt::Display for SourceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SourceError::Connect(detail) => write!(f, "connect failed: {}", detail),
+            SourceError::Subscribe(detail) => write!(f, "subscribe failed: {}", detail),
+            SourceError::NotConnected => write!(f, "not connected"),
+            SourceError::StreamTaken => write!(f, "tick stream already taken"),
+        }
+    }
+}
+
+impl std::error::Error for SourceError {}
+
+/// A venue that streams prices: connect, subscribe to symbols, then consume
+/// the tick stream. Sources reconnect on their own once subscribed.
+#[async_trait]
+pub trait PriceSource: Send {
+    /// Short venue name used in logs and metric labels, e.g. `coinbase`.
+    fn venue(&self) -> &str;
+
+    async fn connect(&mut self) -> Result<(), SourceError>;
+
+    /// Starts streaming the given venue-native symbols.
+    async fn subscribe(&mut self, symbols: &[String]) -> Result<(), SourceError>;
+
+    /// Takes the tick stream; may be called once, before or after `subscribe`.
+    fn ticks(&mut self) -> Result<BoxStream<'static, MarketEvent>, SourceError>;
+
+    fn health(&self) -> Health;
+}
+
+/// Channel from a source's background tasks to its tick stream. Bounded, so
+/// a consumer that stops reading slows the source down instead of growing
+/// memory.
+#[derive(Clone)]
+pub struct TickChannel {
+    sender: EventSender<MarketEvent>,
+    receiver: Arc<Mutex<Option<EventReceiver<MarketEvent>>>>,
+}
+
+impl TickChannel {
+    /// A channel of [`DEFAULT_TICK_CAPACITY`] ticks that blocks when full.
+    pub fn new() -> Self {
+        Self::with_capacity(DEFAULT_TICK_CAPACITY, BackpressurePolicy::Block)
+    }
+
+    pub fn with_capacity(capacity: usize, policy: BackpressurePolicy) -> Self {
+        let (sender, receiver) = bounded(capacity, policy);
+        Self {
+            sender,
+            receiver: Arc::new(Mutex::new(Some(receiver))),
+        }
+    }
+
+    pub fn sender(&self) -> EventSender<MarketEvent> {
+        self.sender.clone()
+    }
+
+    /// Queue depth and drop counters of the channel.
+    pub fn monitor(&self) -> Option<ChannelMonitor> {
+        self.sender.monitor()
+    }
+
+    pub fn take_stream(&self) -> Result<BoxStream<'static, MarketEvent>, SourceError> {
+        let receiver = self
+            .receiver
+            .lock()
+            .ok()
+            .and_then(|mut receiver| receiver.take())
+            .ok_or(SourceError::StreamTaken)?;
+
+        Ok(stream::unfold(receiver, |mut receiver| async move {
+            receiver.recv().await.map(|tick| (tick, receiver))
+        })
+        .boxed())
+    }
+}
+
+impl Default for TickChannel {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for TickChannel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TickChannel").finish_non_exhaustive()
+    }
+}
@@ -0,0 +1,105 @@
+use std::time::Duration;
+use volatility_core::channel::{bounded, BackpressurePolicy, ChannelMetrics, ChannelStats};
+use volatility_core::event::MarketEvent;
+
+fn event(instrument: &str, price: f64) -> MarketEvent {
+    MarketEvent::trade("test", instrument, price, None, Some(0))
+}
+
+async fn drain(receiver: &mut volatility_core::channel::EventReceiver<MarketEvent>) -> Vec<(String, f64)> {
+    let mut events = Vec::new();
+    while let Ok(Some(event)) = tokio::time::timeout(Duration::from_millis(50), receiver.recv()).await {
+        events.push((event.instrument, event.price));
+    }
+    events
+}
+
+#[tokio::test]
+async fn test_drop_policies_when_full() {
+    let (sender, mut receiver) = bounded(2, BackpressurePolicy::DropOldest);
+    for price in [1.0, 2.0, 3.0] {
+        sender.send(event("ETH", price)).await.unwrap();
+    }
+    let stats = receiver.monitor().stats();
+    assert_eq!(stats, ChannelStats { depth: 2, high_water: 2, dropped: 1, conflated: 0 });
+    assert_eq!(drain(&mut receiver).await, vec![("ETH".to_string(), 2.0), ("ETH".to_string(), 3.0)]);
+
+    let (sender, mut receiver) = bounded(2, BackpressurePolicy::DropNewest);
+    for price in [1.0, 2.0, 3.0] {
+        sender.send(event("ETH", price)).await.unwrap();
+    }
+    assert_eq!(drain(&mut receiver).await, vec![("ETH".to_string(), 1.0), ("ETH".to_string(), 2.0)]);
+    assert_eq!(sender.monitor().unwrap().stats().dropped, 1);
+}
+
+#[tokio::test]
+async fn test_conflate_keeps_latest_per_key_in_place() {
+    let (sender, mut receiver) = bounded(4, BackpressurePolicy::ConflateLatest);
+    sender.send(event("ETH", 1.0)).await.unwrap();
+    sender.send(event("BTC", 10.0)).await.unwrap();
+    sender.send(event("ETH", 2.0)).await.unwrap();
+    sender.send(event("ETH", 3.0)).await.unwrap();
+
+    let stats = receiver.monitor().stats();
+    assert_eq!((stats.depth, stats.conflated, stats.dropped), (2, 2, 0));
+    assert_eq!(drain(&mut receiver).await, vec![("ETH".to_string(), 3.0), ("BTC".to_string(), 10.0)]);
+
+    // Once delivered, a key queues afresh
+    sender.send(event("ETH", 4.0)).await.unwrap();
+    assert_eq!(drain(&mut receiver).await, vec![("ETH".to_string(), 4.0)]);
+}
+
+#[tokio::test]
+async fn test_block_waits_for_space_and_closes() {
+    let (sender, mut receiver) = bounded(1, BackpressurePolicy::Block);
+    sender.send(event("ETH", 1.0)).await.unwrap();
+
+    let producer = tokio::spawn(async move {
+        sender.send(event("ETH", 2.0)).await.unwrap();
+        // Dropping the last sender ends the stream after the queue drains
+    });
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert!(!producer.is_finished(), "send should block while the queue is full");
+
+    assert_eq!(receiver.recv().await.unwrap().price, 1.0);
+    producer.await.unwrap();
+    assert_eq!(receiver.recv().await.unwrap().price, 2.0);
+    assert!(receiver.recv().await.is_none());
+    assert_eq!(receiver.monitor().stats().dropped, 0);
+
+    // A blocked producer is released with an error when the receiver goes away
+    let (sender, receiver) = bounded(1, BackpressurePolicy::Block);
+    sender.send(event("ETH", 1.0)).await.unwrap();
+    let producer = tokio::spawn(async move { sender.send(event("ETH", 2.0)).await.is_err() });
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    drop(receiver);
+    assert!(tokio::time::timeout(Duration::from_secs(1), producer).await.unwrap().unwrap());
+}
+
+#[tokio::test]
+async fn test_channel_metrics_publish_increments() {
+    let registry = prometheus::Registry::new();
+    let mut metrics = ChannelMetrics::register(&registry).unwrap();
+    let (sender, receiver) = bounded(1, BackpressurePolicy::DropNewest);
+    for price in [1.0, 2.0, 3.0] {
+        sender.send(event("ETH", price)).await.unwrap();
+    }
+
+    // Reading the same counters twice publishes the drops once
+    metrics.record("swaps", &receiver.monitor().stats());
+    metrics.record("swaps", &receiver.monitor().stats());
+
+    let value = |name: &str| {
+        registry
+            .gather()
+            .iter()
+            .filter(|family| family.get_name() == name)
+            .flat_map(|family| family.get_metric().to_vec())
+            .filter(|metric| metric.get_label().iter().all(|label| label.get_value() != "conflated"))
+            .map(|metric| metric.get_gauge().get_value() + metric.get_counter().get_value())
+            .sum::<f64>()
+    };
+    assert_eq!(value("channel_depth"), 1.0);
+    assert_eq!(value("channel_high_water"), 1.0);
+    assert_eq!(value("channel_dropped_total"), 2.0);
+}
@@ -0,0 +1,64 @@
+use volatility_core::composite::{CompositeIndex, CompositeMethod, Exclusion};
+use volatility_core::event::MarketEvent;
+
+fn tick(venue: &str, price: f64, size: Option<f64>, timestamp: u64) -> MarketEvent {
+    MarketEvent::trade(venue, "ETHUSDC", price, size, Some(timestamp))
+}
+
+#[test]
+fn test_median_excludes_stale_and_outlier_venues() {
+    let mut index = CompositeIndex::new(CompositeMethod::Median)
+        .with_max_staleness_ms(5_000)
+        .with_max_deviation(0.01);
+    index.update(&tick("binance", 2000.0, None, 10_000));
+    index.update(&tick("coinbase", 2002.0, None, 10_000));
+    index.update(&tick("kraken", 2001.0, None, 10_000));
+    index.update(&tick("okx", 2300.0, None, 10_000)); // Glitch
+    index.update(&tick("bybit", 1990.0, None, 1_000)); // Stale
+
+    let composite = index.price(11_000).unwrap();
+    assert_eq!(composite.price, 2001.0);
+    assert_eq!(composite.venues, vec!["binance", "coinbase", "kraken"]);
+    assert_eq!(
+        composite.excluded,
+        vec![("bybit".to_string(), Exclusion::Stale), ("okx".to_string(), Exclusion::Outlier)]
+    );
+}
+
+#[test]
+fn test_volume_weighted_uses_recent_quantity() {
+    let mut index = CompositeIndex::new(CompositeMethod::VolumeWeighted).with_volume_window_ms(1_000);
+    index.update(&tick("binance", 2000.0, Some(10.0), 0)); // Falls out of the volume window
+    index.update(&tick("binance", 2000.0, Some(3.0), 5_000));
+    index.update(&tick("coinbase", 2004.0, Some(1.0), 5_000));
+
+    let composite = index.price(5_000).unwrap();
+    assert!((composite.price - 2001.0).abs() < 1e-9);
+
+    // Without quantities every venue counts equally
+    let mut index = CompositeIndex::new(CompositeMethod::VolumeWeighted);
+    index.update(&tick("kraken", 2000.0, None, 0));
+    index.update(&tick("okx", 2004.0, None, 0));
+    assert_eq!(index.price(0).unwrap().price, 2002.0);
+}
+
+#[test]
+fn test_trimmed_mean_drops_extremes() {
+    let mut index = CompositeIndex::new(CompositeMethod::TrimmedMean { trim: 0.25 }).with_max_deviation(0.05);
+    for (venue, price) in [("a", 1990.0), ("b", 2000.0), ("c", 2002.0), ("d", 2050.0)] {
+        index.update(&tick(venue, price, None, 0));
+    }
+    assert_eq!(index.price(0).unwrap().price, 2001.0);
+}
+
+#[test]
+fn test_min_venues_and_out_of_order_ticks() {
+    let mut index = CompositeIndex::new(CompositeMethod::Median).with_min_venues(2);
+    index.update(&tick("binance", 2000.0, None, 2_000));
+    assert!(index.price(2_000).is_none());
+
+    // An older tick does not overwrite the latest price
+    index.update(&tick("binance", 1500.0, None, 1_000));
+    index.update(&tick("kraken", 2002.0, None, 2_000));
+    assert_eq!(index.price(2_000).unwrap().price, 2001.0);
+}
@@ -0,0 +1,80 @@
+use futures_util::StreamExt;
+use volatility_core::channel::Conflate;
+use volatility_core::event::{EventDetail, EventKind, MarketEvent};
+use volatility_core::source::{ConnectionState, HealthTracker, SourceError, TickChannel};
+
+fn tick(price: f64, timestamp: u64) -> MarketEvent {
+    MarketEvent::trade("test", "ETH-USD", price, None, Some(timestamp))
+}
+
+#[tokio::test]
+async fn test_tick_channel_streams_once() {
+    let channel = TickChannel::new();
+    channel.sender().send(tick(2050.0, 1)).await.unwrap();
+    channel.sender().send(tick(2051.0, 2)).await.unwrap();
+
+    let mut stream = channel.take_stream().unwrap();
+    assert_eq!(stream.next().await.unwrap().price, 2050.0);
+    assert_eq!(stream.next().await.unwrap().price, 2051.0);
+    assert_eq!(channel.take_stream().err(), Some(SourceError::StreamTaken));
+}
+
+#[test]
+fn test_health_tracker_reports_liveness() {
+    let tracker = HealthTracker::new();
+    assert!(!tracker.snapshot().is_live(1_000, 500));
+
+    tracker.set_state(ConnectionState::Connected);
+    tracker.record_tick(900);
+    assert!(tracker.snapshot().is_live(1_000, 500));
+    assert!(!tracker.snapshot().is_live(2_000, 500));
+
+    tracker.record_reconnect("connection reset");
+    let health = tracker.snapshot();
+    assert_eq!((health.state, health.reconnects), (ConnectionState::Reconnecting, 1));
+    assert_eq!(health.last_error.as_deref(), Some("connection reset"));
+    assert!(!health.is_live(1_000, 500));
+}
+
+#[test]
+fn test_market_event_kind_and_timestamp() {
+    let trade = tick(2050.0, 1_000);
+    assert_eq!((trade.kind(), trade.timestamp()), (EventKind::Trade, 1_000));
+
+    // Without a source timestamp the receive time stands in
+    let swap = MarketEvent {
+        source: "uniswap".to_string(),
+        instrument: "ethusdc".to_string(),
+        event_time: None,
+        receive_time: 2_000,
+        price: 2050.0,
+        size: None,
+        detail: EventDetail::Swap {
+            amount0: -1_000_000,
+            amount1: 487_000_000_000_000,
+            liquidity: 1,
+            tick: 198_000,
+            block_number: Some(19_000_000),
+            log_index: Some(12),
+            removed: false,
+        },
+    };
+    assert_eq!((swap.kind(), swap.timestamp()), (EventKind::Swap, 2_000));
+    assert_eq!(swap.kind().as_str(), "swap");
+
+    // A reorg retraction is never conflated away
+    assert!(swap.conflation_key().is_some());
+    let retraction = MarketEvent {
+        detail: EventDetail::Swap {
+            amount0: -1_000_000,
+            amount1: 487_000_000_000_000,
+            liquidity: 1,
+            tick: 198_000,
+            block_number: Some(19_000_000),
+            log_index: Some(12),
+            removed: true,
+        },
+        ..swap
+    };
+    assert_eq!(retraction.conflation_key(), None);
+}